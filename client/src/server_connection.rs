@@ -1,7 +1,11 @@
 use anyhow::Result;
+use common::channel::ChannelUpdate;
 use common::connection::Connection;
+use common::file::{Attachment, MAX_CHUNK_SIZE};
 use common::id::ID;
-use common::messages::{Request, RequestType, Response};
+use common::messages::{HistoryOrder, HistoryQuery, Request, RequestType, Response, SearchFilters};
+use common::server::ServerUpdate;
+use common::user::{CustomStatus, Presence, ProfileImage, ProfileUpdate};
 use std::time;
 use tokio::net::TcpStream;
 
@@ -57,6 +61,87 @@ pub async fn delete_server(conn: &mut Connection, server_id: ID, session_id: ID)
     send_request(conn, Request::new(req_tp, Some(session_id))).await
 }
 
+pub async fn get_server(conn: &mut Connection, server_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::GetServer(server_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn update_server(
+    conn: &mut Connection,
+    server_id: ID,
+    update: ServerUpdate,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::UpdateServer(server_id, update);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn transfer_ownership(
+    conn: &mut Connection,
+    server_id: ID,
+    new_owner: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::TransferOwnership(server_id, new_owner);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_members(
+    conn: &mut Connection,
+    server_id: ID,
+    page: u32,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetMembers(server_id, page);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn set_nickname(
+    conn: &mut Connection,
+    server_id: ID,
+    nickname: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::SetNickname(server_id, nickname);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn list_emoji(conn: &mut Connection, server_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::ListEmoji(server_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn create_emoji(
+    conn: &mut Connection,
+    server_id: ID,
+    name: String,
+    image: Vec<u8>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::CreateEmoji(server_id, name, image);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn delete_emoji(
+    conn: &mut Connection,
+    server_id: ID,
+    emoji_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::DeleteEmoji(server_id, emoji_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_emoji_image(
+    conn: &mut Connection,
+    server_id: ID,
+    emoji_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetEmojiImage(server_id, emoji_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
 pub async fn new_channel(conn: &mut Connection, server_id: ID, channel_name: String, session_id: ID) -> Result<Response> {
     let req_tp = RequestType::NewChannel(server_id, channel_name);
     send_request(conn, Request::new(req_tp, Some(session_id))).await
@@ -72,16 +157,618 @@ pub async fn get_channels(conn: &mut Connection, server_id: ID, session_id: ID)
     send_request(conn, Request::new(req_tp, Some(session_id))).await
 }
 
-pub async fn send_message(conn: &mut Connection, server_id: ID, channel_name: String, message_content: String, session_id: ID) -> Result<Response> {
-    let req_tp = RequestType::SendMessage(server_id, channel_name, message_content);
+pub async fn send_message(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_content: String,
+    reply_to: Option<ID>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::SendMessage(server_id, channel_name, message_content, reply_to);
     send_request(conn, Request::new(req_tp, Some(session_id))).await
 }
 
+pub async fn send_attachments(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_content: String,
+    attachment_ids: Vec<ID>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp =
+        RequestType::SendAttachments(server_id, channel_name, message_content, attachment_ids);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+///a chunk of an attachment of a message or of one of its thumbnails
+pub struct AttachmentChunk {
+    pub server_id: ID,
+    pub channel_name: String,
+    pub message_id: ID,
+    pub attachment_id: ID,
+    ///the size of the thumbnail, None for the attachment itself
+    pub thumbnail: Option<u32>,
+    pub offset: u64,
+}
+
+pub async fn download_attachment(
+    conn: &mut Connection,
+    chunk: AttachmentChunk,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::DownloadAttachment(
+        chunk.server_id,
+        chunk.channel_name,
+        chunk.message_id,
+        chunk.attachment_id,
+        chunk.thumbnail,
+        chunk.offset,
+    );
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+///downloads the complete attachment or its thumbnail of the size chunk by chunk over the
+///connection, returns the first error response if a chunk can't be downloaded. see
+///Attachment::preview for choosing a thumbnail
+pub async fn download_file(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    attachment: &Attachment,
+    thumbnail: Option<u32>,
+    session_id: ID,
+) -> Result<Result<Vec<u8>, Response>> {
+    let mut content = Vec::new();
+    loop {
+        let chunk = AttachmentChunk {
+            server_id: server_id.clone(),
+            channel_name: channel_name.clone(),
+            message_id: message_id.clone(),
+            attachment_id: attachment.id.clone(),
+            thumbnail,
+            offset: content.len() as u64,
+        };
+        match download_attachment(conn, chunk, session_id.clone()).await? {
+            Response::Chunk(bytes) if bytes.is_empty() => return Ok(Ok(content)),
+            Response::Chunk(bytes) => content.extend(bytes),
+            other => return Ok(Err(other)),
+        }
+    }
+}
+
+pub async fn start_upload(
+    conn: &mut Connection,
+    filename: String,
+    size: u64,
+    mime_type: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::StartUpload(filename, size, mime_type);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn upload_chunk(
+    conn: &mut Connection,
+    upload_id: ID,
+    offset: u64,
+    bytes: Vec<u8>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::UploadChunk(upload_id, offset, bytes);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn finish_upload(
+    conn: &mut Connection,
+    upload_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::FinishUpload(upload_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+///uploads the file in chunks of MAX_CHUNK_SIZE bytes over the connection and returns the
+///Attachment response or the first error response
+pub async fn upload_file(
+    conn: &mut Connection,
+    filename: String,
+    mime_type: String,
+    content: &[u8],
+    session_id: ID,
+) -> Result<Response> {
+    let upload_id = match start_upload(
+        conn,
+        filename,
+        content.len() as u64,
+        mime_type,
+        session_id.clone(),
+    )
+    .await?
+    {
+        Response::UploadStarted(upload_id) => upload_id,
+        other => return Ok(other),
+    };
+    let mut offset = 0;
+    for chunk in content.chunks(MAX_CHUNK_SIZE) {
+        let response = upload_chunk(
+            conn,
+            upload_id.clone(),
+            offset,
+            chunk.to_vec(),
+            session_id.clone(),
+        )
+        .await?;
+        if !response.succeeded() {
+            return Ok(response);
+        }
+        offset += chunk.len() as u64;
+    }
+    finish_upload(conn, upload_id, session_id).await
+}
+
 pub async fn get_messages(conn: &mut Connection, server_id: ID, channel_name: String, block_nr: u32, session_id: ID) -> Result<Response> {
     let req_tp = RequestType::GetMessages(server_id, channel_name, block_nr);
     send_request(conn, Request::new(req_tp, Some(session_id))).await
 }
 
+pub async fn get_history(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    query: HistoryQuery,
+    order: HistoryOrder,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetHistory(server_id, channel_name, query, order);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn edit_message(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    content: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::EditMessage(server_id, channel_name, message_id, content);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn delete_message(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::DeleteMessage(server_id, channel_name, message_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_edit_history(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetEditHistory(server_id, channel_name, message_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn add_reaction(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    emoji: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::AddReaction(server_id, channel_name, message_id, emoji);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn remove_reaction(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    emoji: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::RemoveReaction(server_id, channel_name, message_id, emoji);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn create_thread(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    name: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::CreateThread(server_id, channel_name, message_id, name);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_threads(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetThreads(server_id, channel_name);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn send_thread_message(
+    conn: &mut Connection,
+    server_id: ID,
+    thread_id: ID,
+    message_content: String,
+    reply_to: Option<ID>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::SendThreadMessage(server_id, thread_id, message_content, reply_to);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_thread_history(
+    conn: &mut Connection,
+    server_id: ID,
+    thread_id: ID,
+    query: HistoryQuery,
+    order: HistoryOrder,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetThreadHistory(server_id, thread_id, query, order);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn archive_thread(
+    conn: &mut Connection,
+    server_id: ID,
+    thread_id: ID,
+    archived: bool,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::ArchiveThread(server_id, thread_id, archived);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_mentions(
+    conn: &mut Connection,
+    server_id: ID,
+    limit: u32,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetMentions(server_id, limit);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn pin_message(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::PinMessage(server_id, channel_name, message_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn unpin_message(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::UnpinMessage(server_id, channel_name, message_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_pins(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetPins(server_id, channel_name);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn search_messages(
+    conn: &mut Connection,
+    server_id: ID,
+    query: String,
+    filters: SearchFilters,
+    page: u32,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::SearchMessages(server_id, query, filters, page);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn open_dm(conn: &mut Connection, other_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::OpenDm(other_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn list_dms(conn: &mut Connection, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::ListDms();
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn send_dm(
+    conn: &mut Connection,
+    dm_id: ID,
+    content: String,
+    reply_to: Option<ID>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::SendDm(dm_id, content, reply_to);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_dm_history(
+    conn: &mut Connection,
+    dm_id: ID,
+    query: HistoryQuery,
+    order: HistoryOrder,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetDmHistory(dm_id, query, order);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn edit_dm_message(
+    conn: &mut Connection,
+    dm_id: ID,
+    message_id: ID,
+    content: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::EditDmMessage(dm_id, message_id, content);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn delete_dm_message(
+    conn: &mut Connection,
+    dm_id: ID,
+    message_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::DeleteDmMessage(dm_id, message_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn create_group_dm(
+    conn: &mut Connection,
+    name: String,
+    others: Vec<ID>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::CreateGroupDm(name, others);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn add_dm_participant(
+    conn: &mut Connection,
+    dm_id: ID,
+    user_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::AddDmParticipant(dm_id, user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn remove_dm_participant(
+    conn: &mut Connection,
+    dm_id: ID,
+    user_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::RemoveDmParticipant(dm_id, user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn rename_group_dm(
+    conn: &mut Connection,
+    dm_id: ID,
+    name: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::RenameGroupDm(dm_id, name);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn leave_group_dm(conn: &mut Connection, dm_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::LeaveGroupDm(dm_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_friends(conn: &mut Connection, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::GetFriends();
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn add_friend(conn: &mut Connection, user_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::AddFriend(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn accept_friend(conn: &mut Connection, user_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::AcceptFriend(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn decline_friend(
+    conn: &mut Connection,
+    user_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::DeclineFriend(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn cancel_friend_request(
+    conn: &mut Connection,
+    user_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::CancelFriendRequest(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn remove_friend(conn: &mut Connection, user_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::RemoveFriend(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn block_user(conn: &mut Connection, user_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::BlockUser(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn unblock_user(conn: &mut Connection, user_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::UnblockUser(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn set_presence(
+    conn: &mut Connection,
+    presence: Presence,
+    custom_status: Option<CustomStatus>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::SetPresence(presence, custom_status);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_presence(
+    conn: &mut Connection,
+    user_ids: Vec<ID>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetPresence(user_ids);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_events(
+    conn: &mut Connection,
+    after: Option<ID>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetEvents(after);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_profile(conn: &mut Connection, user_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::GetProfile(user_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn update_profile(
+    conn: &mut Connection,
+    update: ProfileUpdate,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::UpdateProfile(update);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_profile_image(
+    conn: &mut Connection,
+    user_id: ID,
+    image: ProfileImage,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetProfileImage(user_id, image);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn start_typing(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::StartTyping(server_id, channel_name);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_typing(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::GetTyping(server_id, channel_name);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+///subscribes to the typing users of the channel, the connection only receives Typing responses
+///afterwards, read them with next_typing
+pub async fn subscribe_typing(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::SubscribeTyping(server_id, channel_name);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+///waits for the next change of the typing users of a subscribed channel
+pub async fn next_typing(conn: &mut Connection) -> Result<Response> {
+    conn.read().await
+}
+
+pub async fn ack(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    message_id: ID,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::Ack(server_id, channel_name, message_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn get_unread(conn: &mut Connection, server_id: ID, session_id: ID) -> Result<Response> {
+    let req_tp = RequestType::GetUnread(server_id);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn update_channel(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    update: ChannelUpdate,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::UpdateChannel(server_id, channel_name, update);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
+pub async fn move_channel(
+    conn: &mut Connection,
+    server_id: ID,
+    channel_name: String,
+    position: u32,
+    category: Option<String>,
+    session_id: ID,
+) -> Result<Response> {
+    let req_tp = RequestType::MoveChannel(server_id, channel_name, position, category);
+    send_request(conn, Request::new(req_tp, Some(session_id))).await
+}
+
 #[cfg(test)]
 mod test {
     use std::{sync::Arc, clone, time::Duration};
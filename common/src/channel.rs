@@ -0,0 +1,82 @@
+use crate::error::NameError;
+use crate::framing::Frameable;
+use crate::id::ID;
+use crate::validation::validate_name;
+use macros::Frame;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+///metadata of a channel as it is returned to the client
+#[derive(Debug, Serialize, Deserialize, Frame, Clone, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub id: ID,
+    pub name: String,
+    pub topic: Option<String>,
+    pub position: u32,
+    pub category: Option<String>,
+}
+
+///changes that should be applied to a channel, fields that are None are left untouched
+///an empty topic removes the topic of the channel
+#[derive(Debug, Serialize, Deserialize, Frame, Clone, Default)]
+pub struct ChannelUpdate {
+    pub name: Option<String>,
+    pub topic: Option<String>,
+}
+
+///metadata of a thread, a conversation that hangs off a message of a channel
+#[derive(Debug, Serialize, Deserialize, Frame, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    ///the id of a thread is the id of the message it hangs off
+    pub id: ID,
+    pub name: String,
+    ///id of the channel the thread belongs to
+    pub channel: ID,
+    ///users that created the thread or sent a message to it
+    pub participants: Vec<ID>,
+    ///no messages can be sent to archived threads
+    pub archived: bool,
+    pub created_at: SystemTime,
+}
+
+///the unread messages and mentions of a user in a channel, deleted messages and messages sent by
+///the server are not counted
+#[derive(Debug, Serialize, Deserialize, Frame, Clone, PartialEq, Eq)]
+pub struct ChannelUnread {
+    ///name of the channel
+    pub channel: String,
+    pub unread: u64,
+    ///unread messages that mention the user
+    pub mentions: u64,
+}
+
+impl ChannelInfo {
+    pub fn new(
+        id: ID,
+        name: String,
+        topic: Option<String>,
+        position: u32,
+        category: Option<String>,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            topic,
+            position,
+            category,
+        }
+    }
+}
+
+pub const MAX_CHANNEL_NAME_LENGTH: usize = 100;
+pub const MAX_THREAD_NAME_LENGTH: usize = 100;
+
+///checks whether the name can be used as a channel name, see validate_name
+pub fn validate_channel_name(name: &str) -> Result<(), NameError> {
+    validate_name(name, MAX_CHANNEL_NAME_LENGTH)
+}
+
+///checks whether the name can be used as a thread name, see validate_name
+pub fn validate_thread_name(name: &str) -> Result<(), NameError> {
+    validate_name(name, MAX_THREAD_NAME_LENGTH)
+}
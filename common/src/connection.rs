@@ -9,11 +9,16 @@ use log::{info, error};
 #[derive(Debug)]
 pub struct Connection {
     stream: TcpStream,
+    ///bytes that were read but not yet deframed, the peer may send several frames at once
+    buffer: Vec<u8>,
 }
 
 impl Connection {
     pub fn new(stream: TcpStream) -> Self {
-        Self { stream }
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
     }
     pub fn get_addr(&self) -> Result<IpAddr>{
         Ok(self.stream.peer_addr()?.ip())
@@ -36,12 +41,13 @@ impl Connection {
     where
         T: Frameable,
     {
-        let mut buffer = Vec::new();
         let v = loop {
-            let n = self.stream.read_buf(&mut buffer).await?;
-            if let Some(val) = T::deframe(&buffer)? {
+            if let Some(val) = T::deframe(&self.buffer)? {
+                let consumed = frame_length(&self.buffer)? + 7;
+                self.buffer.drain(..consumed);
                 break val;
             };
+            let n = self.stream.read_buf(&mut self.buffer).await?;
             if n == 0 {
                 error!("connection closed by peer, while still listening");
                 return Err(anyhow!("connection closed by peer"));
@@ -90,4 +96,33 @@ mod test {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn test_several_frames() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(stream);
+            //both frames are sent at once
+            let mut frames = messages::Response::Pong("first".to_string())
+                .enframe()
+                .unwrap();
+            frames.extend(
+                messages::Response::Pong("second".to_string())
+                    .enframe()
+                    .unwrap(),
+            );
+            conn.stream.write_all(&frames).await.unwrap();
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut conn = Connection::new(stream);
+        for expected in ["first", "second"] {
+            match conn.read::<messages::Response>().await.unwrap() {
+                messages::Response::Pong(txt) => assert_eq!(txt, expected),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+    }
 }
@@ -0,0 +1,52 @@
+use crate::error::NameError;
+use crate::framing::Frameable;
+use crate::id::ID;
+use crate::user::User;
+use crate::validation::validate_name;
+use macros::Frame;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+///maximum number of participants of a group conversation, including the owner
+pub const MAX_GROUP_DM_MEMBERS: usize = 10;
+pub const MAX_GROUP_DM_NAME_LENGTH: usize = 100;
+
+///metadata of a direct message conversation, either between two users or a group
+#[derive(Debug, Serialize, Deserialize, Frame)]
+pub struct DmInfo {
+    pub id: ID,
+    ///name of a group conversation, None for conversations between two users
+    pub name: Option<String>,
+    ///owner of a group conversation, None for conversations between two users
+    pub owner: Option<ID>,
+    ///users that take part in the conversation
+    pub participants: Vec<User>,
+    pub created_at: SystemTime,
+    ///time of the last message, the creation time if no message was sent yet
+    pub last_activity: SystemTime,
+}
+
+impl DmInfo {
+    pub fn new(
+        id: ID,
+        name: Option<String>,
+        owner: Option<ID>,
+        participants: Vec<User>,
+        created_at: SystemTime,
+        last_activity: SystemTime,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            owner,
+            participants,
+            created_at,
+            last_activity,
+        }
+    }
+}
+
+///checks whether the name can be used as the name of a group conversation, see validate_name
+pub fn validate_group_dm_name(name: &str) -> Result<(), NameError> {
+    validate_name(name, MAX_GROUP_DM_NAME_LENGTH)
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+use crate::error::NameError;
+use crate::id::ID;
+
+///maximum number of custom emoji a server can have
+pub const MAX_EMOJI_PER_SERVER: usize = 50;
+pub const MAX_EMOJI_NAME_LENGTH: usize = 32;
+///maximum size of the image of a custom emoji in bytes
+pub const MAX_EMOJI_IMAGE_SIZE: usize = 256 * 1024;
+
+///an image uploaded to a server that is used like an emoji with the :name: syntax
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CustomEmoji {
+    pub id: ID,
+    ///unique on the server
+    pub name: String,
+    pub created_by: ID,
+    pub created_at: SystemTime,
+}
+
+impl CustomEmoji {
+    ///the text reactions with the emoji are stored as, see parse_emoji_reference
+    pub fn reference(&self) -> String {
+        format!("<:{}:{}>", self.name, self.id.id)
+    }
+}
+
+///checks whether the name can be used as the name of a custom emoji, names consist of ascii
+///letters, digits and underscores only and must not exceed MAX_EMOJI_NAME_LENGTH characters
+pub fn validate_emoji_name(name: &str) -> Result<(), NameError> {
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if name.chars().count() > MAX_EMOJI_NAME_LENGTH {
+        return Err(NameError::TooLong(MAX_EMOJI_NAME_LENGTH));
+    }
+    match name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '_')
+    {
+        Some(c) => Err(NameError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+///returns the names used with the :name: syntax in the content, each name once in the order of
+///their first use
+pub fn parse_emoji_names(content: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        let end = match after.find(':') {
+            Some(end) => end,
+            None => break,
+        };
+        let name = &after[..end];
+        if validate_emoji_name(name).is_ok() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+            rest = &after[end + 1..];
+        } else {
+            //the closing colon may open the next name
+            rest = &after[end..];
+        }
+    }
+    names
+}
+
+///returns the name and id of the custom emoji a reaction refers to, None for other emojis
+pub fn parse_emoji_reference(emoji: &str) -> Option<(&str, ID)> {
+    let inner = emoji.strip_prefix("<:")?.strip_suffix('>')?;
+    let (name, id) = inner.split_once(':')?;
+    validate_emoji_name(name).ok()?;
+    Some((name, ID::new(id.to_string())?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_emoji_name() {
+        assert!(validate_emoji_name("party_parrot").is_ok());
+        assert!(validate_emoji_name("100").is_ok());
+        assert_eq!(validate_emoji_name(""), Err(NameError::Empty));
+        assert_eq!(
+            validate_emoji_name("party parrot"),
+            Err(NameError::InvalidCharacter(' '))
+        );
+        assert_eq!(
+            validate_emoji_name("blåhaj"),
+            Err(NameError::InvalidCharacter('å'))
+        );
+        assert_eq!(
+            validate_emoji_name(&"a".repeat(MAX_EMOJI_NAME_LENGTH + 1)),
+            Err(NameError::TooLong(MAX_EMOJI_NAME_LENGTH))
+        );
+    }
+
+    #[test]
+    fn test_parse_emoji_names() {
+        assert_eq!(parse_emoji_names("hi :wave: and :wave:"), vec!["wave"]);
+        assert_eq!(parse_emoji_names(":a::b:"), vec!["a", "b"]);
+        assert_eq!(
+            parse_emoji_names("note: :party_parrot:"),
+            vec!["party_parrot"]
+        );
+        assert_eq!(parse_emoji_names("a: b: c"), Vec::<&str>::new());
+        assert_eq!(parse_emoji_names(":unclosed"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_emoji_reference() {
+        let emoji = CustomEmoji {
+            id: ID::new("123123123123123123123123".to_string()).unwrap(),
+            name: "wave".to_string(),
+            created_by: ID::new("123123123123123123123124".to_string()).unwrap(),
+            created_at: SystemTime::now(),
+        };
+        let reference = emoji.reference();
+        assert_eq!(reference, "<:wave:123123123123123123123123>");
+        assert_eq!(parse_emoji_reference(&reference), Some(("wave", emoji.id)));
+        assert_eq!(parse_emoji_reference(":wave:"), None);
+        assert_eq!(parse_emoji_reference("<:wave:123>"), None);
+    }
+}
@@ -11,6 +11,42 @@ pub enum ServerError {
     SessionExpired,
     InvalidCredentials,
     BadRequest,
+    InvalidName(NameError),
+    /// the request would exceed a limit, e.g. the number of reactions on a message
+    LimitExceeded,
+    InvalidMessage(ContentError),
+    InvalidFile(FileError),
+}
+
+/// Reason why the content of a message provided by the Client was rejected
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ContentError {
+    /// empty or whitespace only
+    Empty,
+    /// contains the maximum number of characters
+    TooLong(usize),
+    /// contains the maximum number of bytes
+    TooLarge(usize),
+    InvalidCharacter(char),
+}
+
+/// Reason why a name provided by the Client was rejected
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum NameError {
+    Empty,
+    /// contains the maximum number of characters
+    TooLong(usize),
+    InvalidCharacter(char),
+    AlreadyTaken,
+}
+
+/// Reason why a file provided by the Client was rejected
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum FileError {
+    Empty,
+    /// contains more than the maximum number of bytes
+    TooLarge(usize),
+    UnsupportedFormat,
 }
 
 impl Frameable for ServerError {}
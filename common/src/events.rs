@@ -0,0 +1,22 @@
+use crate::id::ID;
+use crate::user::User;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+///maximum number of events that are returned per request
+pub const MAX_EVENTS_PER_REQUEST: usize = 100;
+
+///something that happened and that a user is notified about
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum EventKind {
+    ///the presence or the custom status of a friend or a member of a shared server changed
+    PresenceChanged(User),
+}
+
+///an event in the event feed of a user, the ids of events increase in the order they happened
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Event {
+    pub id: ID,
+    pub created_at: SystemTime,
+    pub kind: EventKind,
+}
@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::error::{FileError, NameError};
+use crate::id::ID;
+use crate::validation::validate_name;
+
+///maximum size of an attachment in bytes
+pub const MAX_ATTACHMENT_SIZE: u64 = 25 * 1024 * 1024;
+///maximum number of bytes uploaded or downloaded with one request, frames encode bytes as json
+///numbers so chunks have to stay well below the maximum frame size
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+pub const MAX_ATTACHMENTS_PER_MESSAGE: usize = 10;
+pub const MAX_FILENAME_LENGTH: usize = 255;
+pub const MAX_MIME_TYPE_LENGTH: usize = 127;
+///time after the last chunk after which an unfinished upload is discarded
+pub const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+///maximum width and height of the thumbnails generated for image attachments, smaller images
+///only get the thumbnails smaller than themselves
+pub const THUMBNAIL_SIZES: [u32; 3] = [128, 256, 512];
+
+///a file attached to a message
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    ///assigned by the server when the upload is finished
+    pub id: ID,
+    pub filename: String,
+    ///size in bytes
+    pub size: u64,
+    ///as declared by the uploader
+    pub mime_type: String,
+    ///hex encoded sha256 hash of the content
+    pub hash: String,
+    ///set if the file is an image the server could decode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageInfo>,
+}
+
+impl Attachment {
+    ///returns the smallest thumbnail that is at least max_size pixels wide or high, the largest
+    ///thumbnail if there is none. None if the attachment is no image or smaller than all
+    ///thumbnail sizes, the image itself can be shown in that case
+    pub fn preview(&self, max_size: u32) -> Option<&Thumbnail> {
+        let thumbnails = &self.image.as_ref()?.thumbnails;
+        thumbnails
+            .iter()
+            .filter(|thumbnail| thumbnail.width.max(thumbnail.height) >= max_size)
+            .min_by_key(|thumbnail| thumbnail.width.max(thumbnail.height))
+            .or_else(|| thumbnails.last())
+    }
+}
+
+///dimensions and previews of an image attachment
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    ///png images ordered by their size, downloaded like the attachment itself
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+///a scaled down version of an image attachment
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    ///one of THUMBNAIL_SIZES, the larger side of the thumbnail has this length
+    pub max_size: u32,
+    pub width: u32,
+    pub height: u32,
+    ///size in bytes
+    pub size: u64,
+    ///hex encoded sha256 hash of the content
+    pub hash: String,
+}
+
+///image formats the server accepts for avatars and banners
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl ImageFormat {
+    ///detects the format of the image by the signature at the start of the bytes
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+///checks whether the name can be used as the name of an attachment, see validate_name. names
+///must not contain path separators
+pub fn validate_filename(name: &str) -> Result<(), NameError> {
+    validate_name(name, MAX_FILENAME_LENGTH)?;
+    match name.chars().find(|c| matches!(c, '/' | '\\')) {
+        Some(c) => Err(NameError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+///whether the text has the form type/subtype of a mime type, parameters are not allowed
+pub fn is_valid_mime_type(mime_type: &str) -> bool {
+    let token = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+    match mime_type.split_once('/') {
+        Some((tp, subtype)) => {
+            mime_type.len() <= MAX_MIME_TYPE_LENGTH && token(tp) && token(subtype)
+        }
+        None => false,
+    }
+}
+
+///checks whether the bytes are an image of a supported format that does not exceed max_size bytes
+pub fn validate_image(bytes: &[u8], max_size: usize) -> Result<ImageFormat, FileError> {
+    if bytes.is_empty() {
+        return Err(FileError::Empty);
+    }
+    if bytes.len() > max_size {
+        return Err(FileError::TooLarge(max_size));
+    }
+    ImageFormat::detect(bytes).ok_or(FileError::UnsupportedFormat)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_image() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(validate_image(png, 100), Ok(ImageFormat::Png));
+        assert_eq!(
+            validate_image(b"\xff\xd8\xff\xe0", 100),
+            Ok(ImageFormat::Jpeg)
+        );
+        assert_eq!(validate_image(b"GIF89a", 100), Ok(ImageFormat::Gif));
+        assert_eq!(
+            validate_image(b"RIFF\0\0\0\0WEBPVP8 ", 100),
+            Ok(ImageFormat::Webp)
+        );
+        assert_eq!(
+            validate_image(b"RIFF\0\0\0\0WAVEfmt ", 100),
+            Err(FileError::UnsupportedFormat)
+        );
+        assert_eq!(
+            validate_image(b"<svg></svg>", 100),
+            Err(FileError::UnsupportedFormat)
+        );
+        assert_eq!(validate_image(png, 10), Err(FileError::TooLarge(10)));
+        assert_eq!(validate_image(b"", 10), Err(FileError::Empty));
+    }
+
+    #[test]
+    fn test_preview() {
+        let thumbnail = |max_size: u32| Thumbnail {
+            max_size,
+            width: max_size,
+            height: max_size / 2,
+            size: 100,
+            hash: String::new(),
+        };
+        let mut attachment = Attachment {
+            id: ID::new("123123123123123123123123".to_string()).unwrap(),
+            filename: "cat.png".to_string(),
+            size: 1000,
+            mime_type: "image/png".to_string(),
+            hash: String::new(),
+            image: None,
+        };
+        assert_eq!(attachment.preview(100), None);
+        attachment.image = Some(ImageInfo {
+            width: 1000,
+            height: 500,
+            thumbnails: vec![thumbnail(128), thumbnail(256)],
+        });
+        assert_eq!(attachment.preview(100), Some(&thumbnail(128)));
+        assert_eq!(attachment.preview(200), Some(&thumbnail(256)));
+        assert_eq!(attachment.preview(400), Some(&thumbnail(256)));
+    }
+
+    #[test]
+    fn test_validate_attachment_metadata() {
+        assert!(validate_filename("report 2023.pdf").is_ok());
+        assert_eq!(
+            validate_filename("../passwd"),
+            Err(NameError::InvalidCharacter('/'))
+        );
+        assert_eq!(
+            validate_filename("a\\b"),
+            Err(NameError::InvalidCharacter('\\'))
+        );
+        assert_eq!(validate_filename(" "), Err(NameError::Empty));
+        assert!(is_valid_mime_type("application/pdf"));
+        assert!(is_valid_mime_type(
+            "application/vnd.oasis.opendocument.text"
+        ));
+        assert!(!is_valid_mime_type("text/plain; charset=utf-8"));
+        assert!(!is_valid_mime_type("text"));
+        assert!(!is_valid_mime_type("/plain"));
+    }
+}
@@ -18,10 +18,8 @@ where
         if bytes.len() <= 7 {
             return Ok(None);
         }
-        //convert
-        let bstr = String::from_utf8(bytes.to_vec())?;
-        let l = bstr[0..7].parse::<usize>()?;
-        if bstr.len() < l + 7 {
+        let l = frame_length(bytes)?;
+        if bytes.len() < l + 7 {
             return Ok(None);
         }
         Ok(Some(serde_json::from_slice::<Self>(&bytes[7..l + 7])?))
@@ -46,6 +44,13 @@ where
     }
 }
 
+///returns the length of the frame at the start of the bytes without the 7 leading length bytes,
+///there have to be at least 7 bytes
+pub fn frame_length(bytes: &[u8]) -> Result<usize> {
+    //only the length is decoded, the rest may end in the middle of a character
+    Ok(std::str::from_utf8(&bytes[0..7])?.parse::<usize>()?)
+}
+
 #[cfg(test)]
 mod test{
     use serde::Deserialize;
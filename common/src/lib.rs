@@ -1,6 +1,13 @@
+pub mod channel;
 pub mod connection;
+pub mod dm;
+pub mod emoji;
 pub mod error;
+pub mod events;
+pub mod file;
 pub mod framing;
+pub mod id;
 pub mod messages;
+pub mod server;
 pub mod user;
-pub mod id;
+pub mod validation;
@@ -1,8 +1,22 @@
-use crate::{error::ServerError, id::ID};
+use crate::{
+    channel::{ChannelInfo, ChannelUnread, ChannelUpdate, ThreadInfo},
+    dm::DmInfo,
+    emoji::CustomEmoji,
+    error::{ContentError, ServerError},
+    events::Event,
+    file::Attachment,
+    id::ID,
+    server::{ServerInfo, ServerUpdate},
+    user::{
+        CustomStatus, Member, Presence, Profile, ProfileImage, ProfileUpdate, Relationship, User,
+    },
+};
 use serde::{Deserialize, Serialize};
 
 use crate::framing::Frameable;
 use macros::Frame;
+use mongodb::bson::oid::ObjectId;
+use std::time::{Duration, SystemTime};
 
 #[derive(Serialize, Deserialize, Debug, Frame, Clone)]
 pub enum RequestType {
@@ -17,12 +31,70 @@ pub enum RequestType {
     NewChannel(ID, String),
     DeleteChannel(ID, String),
     GetChannels(ID),
-    SendMessage(ID, String, String), //ServerId, Channelname, Message
-    GetMessages(ID, String, u32), //ServerId, Channelname, block id
-    /*
-    SendMessage(Message),
-    GetFriends,
-    AddFriend(UserId),*/
+    SendMessage(ID, String, String, Option<ID>), //ServerId, Channelname, Message, MessageId of the message that is replied to
+    SendAttachments(ID, String, String, Vec<ID>), //ServerId, Channelname, Message that may be empty, AttachmentIds of finished uploads
+    DownloadAttachment(ID, String, ID, ID, Option<u32>, u64), //ServerId, Channelname, MessageId, AttachmentId, max size of the Thumbnail or None for the file, Offset in bytes
+    StartUpload(String, u64, String),                         //Filename, Size in bytes, MIME type
+    UploadChunk(ID, u64, Vec<u8>), //UploadId, Offset in bytes, up to MAX_CHUNK_SIZE bytes
+    FinishUpload(ID),              //UploadId
+    GetMessages(ID, String, u32),  //ServerId, Channelname, block id
+    UpdateChannel(ID, String, ChannelUpdate), //ServerId, Channelname, changes
+    MoveChannel(ID, String, u32, Option<String>), //ServerId, Channelname, position, category
+    GetServer(ID),
+    UpdateServer(ID, ServerUpdate),
+    TransferOwnership(ID, ID),           //ServerId, UserId of the new owner
+    GetMembers(ID, u32),                 //ServerId, page
+    SetNickname(ID, String),             //ServerId, nickname, an empty nickname removes it
+    ListEmoji(ID),                       //ServerId
+    CreateEmoji(ID, String, Vec<u8>), //ServerId, name, image of at most MAX_EMOJI_IMAGE_SIZE bytes
+    DeleteEmoji(ID, ID),              //ServerId, EmojiId
+    GetEmojiImage(ID, ID),            //ServerId, EmojiId
+    EditMessage(ID, String, ID, String), //ServerId, Channelname, MessageId, new content
+    DeleteMessage(ID, String, ID),    //ServerId, Channelname, MessageId
+    GetEditHistory(ID, String, ID),   //ServerId, Channelname, MessageId
+    GetHistory(ID, String, HistoryQuery, HistoryOrder), //ServerId, Channelname, query, order
+    AddReaction(ID, String, ID, String), //ServerId, Channelname, MessageId, emoji
+    RemoveReaction(ID, String, ID, String), //ServerId, Channelname, MessageId, emoji
+    CreateThread(ID, String, ID, String), //ServerId, Channelname, MessageId the thread hangs off, thread name
+    GetThreads(ID, String),               //ServerId, Channelname
+    SendThreadMessage(ID, ID, String, Option<ID>), //ServerId, ThreadId, Message, MessageId of the message that is replied to
+    GetThreadHistory(ID, ID, HistoryQuery, HistoryOrder), //ServerId, ThreadId, query, order
+    ArchiveThread(ID, ID, bool),                   //ServerId, ThreadId, archived
+    GetMentions(ID, u32),                          //ServerId, limit
+    PinMessage(ID, String, ID),                    //ServerId, Channelname, MessageId
+    UnpinMessage(ID, String, ID),                  //ServerId, Channelname, MessageId
+    GetPins(ID, String),                           //ServerId, Channelname
+    SearchMessages(ID, String, SearchFilters, u32), //ServerId, query, filters, page
+    OpenDm(ID),                                    //UserId of the other user
+    ListDms(),
+    SendDm(ID, String, Option<ID>), //DmId, Message, MessageId of the message that is replied to
+    GetDmHistory(ID, HistoryQuery, HistoryOrder), //DmId, query, order
+    EditDmMessage(ID, ID, String),  //DmId, MessageId, new content
+    DeleteDmMessage(ID, ID),        //DmId, MessageId
+    CreateGroupDm(String, Vec<ID>), //name, UserIds of the other participants
+    AddDmParticipant(ID, ID),       //DmId, UserId
+    RemoveDmParticipant(ID, ID),    //DmId, UserId
+    RenameGroupDm(ID, String),      //DmId, name
+    LeaveGroupDm(ID),               //DmId
+    GetFriends(),
+    AddFriend(ID), //UserId, sends a friend request or accepts the request of the user
+    AcceptFriend(ID), //UserId of the user that sent the request
+    DeclineFriend(ID), //UserId of the user that sent the request
+    CancelFriendRequest(ID), //UserId of the user the request was sent to
+    RemoveFriend(ID), //UserId
+    BlockUser(ID), //UserId
+    UnblockUser(ID), //UserId
+    SetPresence(Presence, Option<CustomStatus>), //presence, custom status, None removes it
+    GetPresence(Vec<ID>), //UserIds
+    GetEvents(Option<ID>), //EventId of the last received event, None for all events
+    GetProfile(ID), //UserId
+    UpdateProfile(ProfileUpdate),
+    GetProfileImage(ID, ProfileImage), //UserId, which image
+    StartTyping(ID, String),           //ServerId, Channelname
+    GetTyping(ID, String),             //ServerId, Channelname
+    SubscribeTyping(ID, String), //ServerId, Channelname, the connection stays open and receives the typing users whenever they change
+    Ack(ID, String, ID),         //ServerId, Channelname, MessageId of the last read message
+    GetUnread(ID),               //ServerId
 }
 
 #[derive(Serialize, Deserialize, Debug, Frame)]
@@ -37,21 +109,333 @@ pub enum Response {
     Error(ServerError),
     SessionCreated(ID),
     ServerCreated(ID),
-    ChannelList(Vec<String>),
+    ThreadCreated(ID),
+    EmojiCreated(ID),
+    DmOpened(ID),
+    UploadStarted(ID),
+    Attachment(Attachment),
+    ///part of a file, empty at the end of the file
+    Chunk(Vec<u8>),
+    ServerInfo(ServerInfo),
+    ChannelList(Vec<ChannelInfo>),
+    ThreadList(Vec<ThreadInfo>),
+    DmList(Vec<DmInfo>),
+    Relationships(Vec<Relationship>),
+    Presences(Vec<User>),
+    Events(Vec<Event>),
+    Profile(Profile),
+    Image(Vec<u8>),
+    EmojiList(Vec<CustomEmoji>),
+    Typing(Vec<Typing>),
+    Unread(Vec<ChannelUnread>),
+    MemberList(Vec<Member>, bool), //members, has more pages
     MessagesFound(Vec<Message>),
+    EditHistory(Vec<MessageRevision>),
+    History(MessageHistory),
+    Mentions(Vec<Mention>),
+    Pins(Vec<Pin>),
+    SearchResults(Vec<SearchResult>, bool), //results, has more pages
     EndOfChannel,
     Success,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Message {
+    ///unique id of the message, assigned by the server
+    pub id: ID,
     pub content: String,
+    ///name of the author as it should be displayed, this is the nickname of the author on the
+    ///server if one is set
     pub author: String,
+    ///id of the user that wrote the message, None for messages sent by the server
+    #[serde(default)]
+    pub author_id: Option<ID>,
+    ///time the server received the message
+    pub created_at: SystemTime,
+    ///time of the last edit, None if the message was never edited
+    #[serde(default)]
+    pub edited_at: Option<SystemTime>,
+    ///position of the message in its channel, assigned by the server when the message is stored
+    ///and strictly increasing in the order the messages were stored
+    #[serde(default)]
+    pub seq: u64,
+    ///reactions of all users to the message, not stored with the message but attached when the
+    ///message is returned to a user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<Reaction>,
+    ///the message this message replies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<Reply>,
+    ///ids of the users mentioned in the content, resolved by the server when the message is sent
+    ///users mentioned with @everyone are not listed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<ID>,
+    ///the author mentioned all users of the server with @everyone
+    #[serde(default)]
+    pub mentions_everyone: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    ///ids of the custom emoji of the server used with the :name: syntax in the content, resolved
+    ///when the message is sent or edited, ids of emoji deleted later on are kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emojis: Vec<ID>,
+}
+
+///upper bound of the maximum size of a message in bytes a server can configure, a block of 50
+///messages of this size stays far below the maximum size of a mongodb document
+pub const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+pub const DEFAULT_MAX_MESSAGE_CHARACTERS: usize = 4000;
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024;
+
+///limits of the content of the messages of a server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageLimits {
+    pub max_characters: usize,
+    ///capped at MAX_MESSAGE_BYTES
+    pub max_bytes: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_characters: DEFAULT_MAX_MESSAGE_CHARACTERS,
+            max_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        }
+    }
+}
+
+impl MessageLimits {
+    ///whether the limits can be configured for a server
+    pub fn is_valid(&self) -> bool {
+        self.max_characters > 0 && self.max_bytes > 0 && self.max_bytes <= MAX_MESSAGE_BYTES
+    }
+
+    ///checks whether the content can be sent as a message, it must not be empty or whitespace
+    ///only, must not exceed the limits and must not contain control characters other than line
+    ///breaks and tabs
+    pub fn validate(&self, content: &str) -> Result<(), ContentError> {
+        if content.trim().is_empty() {
+            return Err(ContentError::Empty);
+        }
+        let max_bytes = self.max_bytes.min(MAX_MESSAGE_BYTES);
+        if content.len() > max_bytes {
+            return Err(ContentError::TooLarge(max_bytes));
+        }
+        if content.chars().count() > self.max_characters {
+            return Err(ContentError::TooLong(self.max_characters));
+        }
+        if let Some(c) = content
+            .chars()
+            .find(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+        {
+            return Err(ContentError::InvalidCharacter(c));
+        }
+        Ok(())
+    }
+}
+
+///maximum number of messages that can be pinned in a channel
+pub const MAX_PINS_PER_CHANNEL: usize = 50;
+
+///a pinned message of a channel
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Pin {
+    pub message: Message,
+    ///id of the user that pinned the message
+    pub pinned_by: ID,
+    pub pinned_at: SystemTime,
+}
+
+///number of results that are returned per page of a search
+pub const SEARCH_PAGE_SIZE: usize = 25;
+
+///restricts the messages a search can find, fields that are None or false don't restrict the
+///search
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilters {
+    ///id of the author of the messages
+    pub author: Option<ID>,
+    ///name of the channel, messages of the threads of the channel are included
+    pub channel: Option<String>,
+    ///only messages sent at or after this time
+    pub after: Option<SystemTime>,
+    ///only messages sent before this time
+    pub before: Option<SystemTime>,
+    ///only messages that mention at least one user
+    pub has_mention: bool,
+    ///only messages that are pinned
+    pub pinned: bool,
+}
+
+///a message found by a search
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct SearchResult {
+    pub message_id: ID,
+    ///id of the channel or thread the message was sent to
+    pub channel: ID,
+    pub author_id: Option<ID>,
+    pub created_at: SystemTime,
+    ///part of the content around the first match
+    pub snippet: String,
+}
+
+///maximum number of mentions that are returned for a GetMentions request
+pub const MAX_MENTIONS_LIMIT: u32 = 50;
+
+///a message the user was mentioned in
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Mention {
+    ///id of the channel or thread the message was sent to
+    pub channel: ID,
+    pub message: Message,
+}
+
+///maximum number of characters of the replied message that are returned with a reply
+pub const REPLY_SNIPPET_LENGTH: usize = 100;
+
+///reference to the message a message replies to, only the id is stored, the other fields are
+///filled in when the reply is returned to a user and stay None if the message was deleted
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Reply {
+    pub message_id: ID,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_id: Option<ID>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    ///the first REPLY_SNIPPET_LENGTH characters of the replied message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+///maximum number of different emojis a message can be reacted with
+pub const MAX_REACTIONS_PER_MESSAGE: usize = 20;
+///maximum number of characters of an emoji used as reaction
+pub const MAX_EMOJI_LENGTH: usize = 32;
+
+///all reactions to a message with the same emoji
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Reaction {
+    pub emoji: String,
+    ///number of users that reacted with the emoji
+    pub count: u32,
+    ///the user the message was returned to reacted with the emoji
+    pub me: bool,
 }
 
 impl Message {
+    ///creates a message that is sent by the server itself
     pub fn new(content: String, author: String) -> Self {
-        Self { content, author }
+        Self {
+            id: ID::new(ObjectId::new().to_hex()).expect("is an object id"),
+            content,
+            author,
+            author_id: None,
+            created_at: SystemTime::now(),
+            edited_at: None,
+            seq: 0,
+            reactions: Vec::new(),
+            reply_to: None,
+            mentions: Vec::new(),
+            mentions_everyone: false,
+            attachments: Vec::new(),
+            emojis: Vec::new(),
+        }
+    }
+
+    ///creates a message written by the user with the id author_id
+    pub fn from_user(content: String, author: String, author_id: ID) -> Self {
+        Self {
+            author_id: Some(author_id),
+            ..Self::new(content, author)
+        }
+    }
+}
+
+///maximum number of messages that are returned for a history query
+pub const MAX_HISTORY_LIMIT: u32 = 100;
+
+///a position in the message history of a channel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Cursor {
+    Message(ID),
+    Time(SystemTime),
+}
+
+///the part of the message history that should be returned, the number is the maximum number of
+///messages and is capped at MAX_HISTORY_LIMIT
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum HistoryQuery {
+    Latest(u32),
+    ///messages sent before the cursor, excluding the message the cursor points to
+    Before(Cursor, u32),
+    ///messages sent after the cursor, excluding the message the cursor points to
+    After(Cursor, u32),
+    ///messages sent around the cursor, including the message the cursor points to
+    Around(Cursor, u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryOrder {
+    NewestFirst,
+    OldestFirst,
+}
+
+///a part of the message history of a channel
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct MessageHistory {
+    pub messages: Vec<Message>,
+    ///there are older messages than the ones returned
+    pub has_more_before: bool,
+    ///there are newer messages than the ones returned
+    pub has_more_after: bool,
+}
+
+///a previous version of an edited message
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct MessageRevision {
+    pub content: String,
+    ///time the content was replaced by an edit
+    pub replaced_at: SystemTime,
+}
+
+impl Reply {
+    pub fn new(message_id: ID) -> Self {
+        Self {
+            message_id,
+            author_id: None,
+            author: None,
+            snippet: None,
+        }
+    }
+
+    ///fills in the author and the snippet of the replied message
+    pub fn resolve(&mut self, message: &Message) {
+        self.author_id = message.author_id.clone();
+        self.author = Some(message.author.clone());
+        self.snippet = Some(message.content.chars().take(REPLY_SNIPPET_LENGTH).collect());
+    }
+}
+
+impl Reaction {
+    pub fn new(emoji: String, count: u32, me: bool) -> Self {
+        Self { emoji, count, me }
+    }
+}
+
+///checks whether the emoji can be used as a reaction, it must not be empty, be at most
+///MAX_EMOJI_LENGTH characters long and must not contain whitespace or control characters
+pub fn is_valid_emoji(emoji: &str) -> bool {
+    !emoji.is_empty()
+        && emoji.chars().count() <= MAX_EMOJI_LENGTH
+        && !emoji.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+impl MessageRevision {
+    pub fn new(content: String, replaced_at: SystemTime) -> Self {
+        Self {
+            content,
+            replaced_at,
+        }
     }
 }
 
@@ -69,3 +453,15 @@ impl Request {
         Self { tp, session_cookie }
     }
 }
+
+///time a user is shown as typing after the last StartTyping request
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(8);
+
+///a user that is typing in a channel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Typing {
+    pub user_id: ID,
+    ///name of the user as it should be displayed, the nickname if one is set
+    pub name: String,
+    pub expires_at: SystemTime,
+}
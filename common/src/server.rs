@@ -0,0 +1,40 @@
+use crate::error::NameError;
+use crate::framing::Frameable;
+use crate::id::ID;
+use crate::messages::MessageLimits;
+use crate::validation::validate_name;
+use macros::Frame;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_SERVER_NAME_LENGTH: usize = 100;
+pub const MAX_SERVER_DESCRIPTION_LENGTH: usize = 1000;
+
+///settings of a server as they are returned to the client
+#[derive(Debug, Serialize, Deserialize, Frame, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub id: ID,
+    pub name: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    ///id of the channel clients should open when entering the server
+    pub default_channel: Option<ID>,
+    pub owner: ID,
+    pub message_limits: MessageLimits,
+}
+
+///changes that should be applied to the server settings, fields that are None are left untouched
+///an empty description, icon or default channel removes the setting
+#[derive(Debug, Serialize, Deserialize, Frame, Clone, Default)]
+pub struct ServerUpdate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    ///name of the channel
+    pub default_channel: Option<String>,
+    pub message_limits: Option<MessageLimits>,
+}
+
+///checks whether the name can be used as a server name, see validate_name
+pub fn validate_server_name(name: &str) -> Result<(), NameError> {
+    validate_name(name, MAX_SERVER_NAME_LENGTH)
+}
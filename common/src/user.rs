@@ -1,15 +1,259 @@
-use serde::{Serialize, Deserialize};
-use macros::Frame;
+use crate::error::{ContentError, NameError};
 use crate::framing::Frameable;
+use crate::id::ID;
+use crate::messages::MessageLimits;
+use crate::validation::validate_name;
+use macros::Frame;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
 
-#[derive(Debug, Serialize, Deserialize, Frame)]
+///number of members that are returned per page of the member list
+pub const MEMBER_PAGE_SIZE: usize = 100;
+pub const MAX_NICKNAME_LENGTH: usize = 32;
+
+#[derive(Debug, Serialize, Deserialize, Frame, Clone)]
 pub struct User{
+    pub id: ID,
     pub username: String,
+    ///false while the user is shown as offline, also for users that are invisible
     pub is_online: bool,
+    ///presence as other users see it, users that are invisible are shown as offline
+    #[serde(default)]
+    pub presence: Presence,
+    #[serde(default)]
+    pub custom_status: Option<CustomStatus>,
 }
 
 impl User {
-   pub fn new(username: String, is_online: bool) -> Self {
-       Self { username,  is_online }
-   }
+    pub fn new(id: ID, username: String, is_online: bool) -> Self {
+        let presence = if is_online {
+            Presence::Online
+        } else {
+            Presence::Offline
+        };
+        Self {
+            id,
+            username,
+            is_online,
+            presence,
+            custom_status: None,
+        }
+    }
+}
+
+///time without requests after which an online user is shown as idle
+pub const IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+pub const MAX_CUSTOM_STATUS_LENGTH: usize = 128;
+///maximum number of users whose presence can be requested at once
+pub const MAX_PRESENCE_QUERY: usize = 100;
+
+///presence of a user, all but offline can be chosen by the user
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Presence {
+    #[default]
+    Online,
+    Idle,
+    DoNotDisturb,
+    ///signed in but shown as offline to other users
+    Invisible,
+    Offline,
+}
+
+///a text the user shows next to their presence
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CustomStatus {
+    pub text: String,
+    ///the status is removed after this time, None if it never expires
+    pub expires_at: Option<SystemTime>,
+}
+
+impl CustomStatus {
+    pub fn new(text: String, expires_at: Option<SystemTime>) -> Self {
+        Self { text, expires_at }
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+///checks whether the text can be used as a custom status, see validate_name
+pub fn validate_custom_status(text: &str) -> Result<(), NameError> {
+    validate_name(text, MAX_CUSTOM_STATUS_LENGTH)
+}
+
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 32;
+pub const MAX_PRONOUNS_LENGTH: usize = 40;
+pub const MAX_BIO_LENGTH: usize = 190;
+///maximum size of an avatar in bytes, frames encode bytes as json numbers so images have to stay
+///well below the maximum frame size
+pub const MAX_AVATAR_SIZE: usize = 1024 * 1024;
+///maximum size of a banner in bytes
+pub const MAX_BANNER_SIZE: usize = 2 * 1024 * 1024;
+
+///the public profile of a user
+#[derive(Debug, Serialize, Deserialize, Frame, Clone)]
+pub struct Profile {
+    pub user: User,
+    ///shown instead of the username if set
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    ///hash of the avatar, the image is requested with GetProfileImage
+    pub avatar: Option<String>,
+    ///hash of the banner, the image is requested with GetProfileImage
+    pub banner: Option<String>,
+    pub created_at: SystemTime,
+}
+
+///the images of a profile
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileImage {
+    Avatar,
+    Banner,
+}
+
+impl ProfileImage {
+    ///maximum size of the image in bytes
+    pub fn max_size(&self) -> usize {
+        match self {
+            ProfileImage::Avatar => MAX_AVATAR_SIZE,
+            ProfileImage::Banner => MAX_BANNER_SIZE,
+        }
+    }
+}
+
+///changes to a profile, fields that are None are left unchanged and empty texts remove the field
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub avatar: Option<ImageUpdate>,
+    pub banner: Option<ImageUpdate>,
+}
+
+///a new image of a profile
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ImageUpdate {
+    ///png, jpeg, gif or webp image
+    Set(Vec<u8>),
+    Remove,
+}
+
+///checks whether the name can be used as a display name, see validate_name
+pub fn validate_display_name(name: &str) -> Result<(), NameError> {
+    validate_name(name, MAX_DISPLAY_NAME_LENGTH)
+}
+
+///checks whether the text can be used as pronouns, see validate_name
+pub fn validate_pronouns(pronouns: &str) -> Result<(), NameError> {
+    validate_name(pronouns, MAX_PRONOUNS_LENGTH)
+}
+
+///checks whether the text can be used as a bio, line breaks are allowed like in messages
+pub fn validate_bio(bio: &str) -> Result<(), ContentError> {
+    let limits = MessageLimits {
+        max_characters: MAX_BIO_LENGTH,
+        max_bytes: MAX_BIO_LENGTH * 4,
+    };
+    limits.validate(bio)
+}
+
+///roles a user can have on a server, ordered by their hierarchy with the highest role first
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Owner,
+    Admin,
+    Member,
+}
+
+///actions on a server that require a permission
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ///delete messages of other users and view the edit history of messages
+    ManageMessages,
+    ///mention all users of a server with @everyone
+    MentionEveryone,
+    ///pin and unpin messages of a channel
+    PinMessages,
+    ///create and delete the custom emoji of a server
+    ManageEmoji,
+}
+
+impl Role {
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::ManageMessages => matches!(self, Role::Owner | Role::Admin),
+            Permission::MentionEveryone => matches!(self, Role::Owner | Role::Admin),
+            Permission::PinMessages => matches!(self, Role::Owner | Role::Admin),
+            Permission::ManageEmoji => matches!(self, Role::Owner | Role::Admin),
+        }
+    }
+
+    ///name of the role as it is used in mentions
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Admin => "admin",
+            Role::Member => "member",
+        }
+    }
+}
+
+///a user as a member of a server
+#[derive(Debug, Serialize, Deserialize, Frame)]
+pub struct Member {
+    pub user: User,
+    pub roles: Vec<Role>,
+    pub nickname: Option<String>,
+}
+
+impl Member {
+    pub fn new(user: User, roles: Vec<Role>, nickname: Option<String>) -> Self {
+        Self {
+            user,
+            roles,
+            nickname,
+        }
+    }
+
+    ///the nickname if the member has one, else the username
+    pub fn display_name(&self) -> &str {
+        self.nickname.as_deref().unwrap_or(&self.user.username)
+    }
+
+    pub fn highest_role(&self) -> Role {
+        self.roles.iter().min().copied().unwrap_or(Role::Member)
+    }
+}
+
+///relationship of a user to another user as seen by the user
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RelationshipKind {
+    Friend,
+    ///the other user sent a friend request to the user
+    IncomingRequest,
+    ///the user sent a friend request to the other user
+    OutgoingRequest,
+    ///the user blocked the other user
+    Blocked,
+}
+
+///another user and the relationship of the user to them
+#[derive(Debug, Serialize, Deserialize, Frame)]
+pub struct Relationship {
+    pub user: User,
+    pub kind: RelationshipKind,
+}
+
+impl Relationship {
+    pub fn new(user: User, kind: RelationshipKind) -> Self {
+        Self { user, kind }
+    }
+}
+
+///checks whether the name can be used as a nickname, see validate_name
+pub fn validate_nickname(nickname: &str) -> Result<(), NameError> {
+    validate_name(nickname, MAX_NICKNAME_LENGTH)
 }
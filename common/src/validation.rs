@@ -0,0 +1,37 @@
+use crate::error::NameError;
+
+///checks whether the name can be used as the name of a channel, server, etc.
+///names must not be empty or consist of whitespace only, must not exceed max_length characters
+///and must not contain control characters
+pub fn validate_name(name: &str, max_length: usize) -> Result<(), NameError> {
+    if name.trim().is_empty() {
+        return Err(NameError::Empty);
+    }
+    if name.chars().count() > max_length {
+        return Err(NameError::TooLong(max_length));
+    }
+    if let Some(c) = name.chars().find(|c| c.is_control()) {
+        return Err(NameError::InvalidCharacter(c));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("general", 10).is_ok());
+        assert!(validate_name("config", 10).is_ok());
+        assert!(validate_name("$system.users", 20).is_ok());
+        assert_eq!(validate_name("", 10), Err(NameError::Empty));
+        assert_eq!(validate_name("  \t", 10), Err(NameError::Empty));
+        assert_eq!(validate_name("abcdef", 5), Err(NameError::TooLong(5)));
+        assert!(validate_name("äöüß", 4).is_ok());
+        assert_eq!(
+            validate_name("gen\0eral", 10),
+            Err(NameError::InvalidCharacter('\0'))
+        );
+    }
+}
@@ -0,0 +1,558 @@
+use anyhow::Result;
+use common::{
+    error::{FileError, ServerError},
+    file::{
+        is_valid_mime_type, validate_filename, Attachment, ImageInfo, Thumbnail,
+        MAX_ATTACHMENT_SIZE, MAX_CHUNK_SIZE, UPLOAD_TIMEOUT,
+    },
+    id::ID,
+    messages::Response,
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, spec::BinarySubtype, Binary, DateTime},
+    options::{FindOptions, IndexOptions},
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+use crate::blob_store::BlobStore;
+use crate::images::{prepare_image, strip_metadata};
+
+pub const ATTACHMENT_DATABASE: &str = "ATTACHMENTS";
+
+///an unfinished upload, stored in the uploads collection of the attachment database
+#[derive(Serialize, Deserialize, Debug)]
+struct Upload {
+    _id: ObjectId,
+    uploader: ID,
+    filename: String,
+    size: u64,
+    mime_type: String,
+    ///number of bytes received so far, chunks are uploaded in order
+    received: u64,
+    ///the upload is removed by the ttl index after this time
+    expires: DateTime,
+}
+
+///a chunk of an unfinished upload, stored in the chunks collection of the attachment database
+#[derive(Serialize, Deserialize, Debug)]
+struct Chunk {
+    upload_id: ObjectId,
+    offset: u64,
+    data: Binary,
+    ///the chunk is removed by the ttl index after this time
+    expires: DateTime,
+}
+
+///a finished upload, stored in the attachments collection of the attachment database
+#[derive(Serialize, Deserialize, Debug)]
+struct StoredAttachment {
+    _id: ObjectId,
+    uploader: ID,
+    attachment: Attachment,
+    created_at: SystemTime,
+}
+
+///uploads files in chunks into the blob store, finished uploads become attachments that the
+///uploader can attach to messages
+pub struct AttachmentHandler;
+
+impl AttachmentHandler {
+    fn uploads(database: &Database) -> Collection<Upload> {
+        database.collection("uploads")
+    }
+
+    fn chunks(database: &Database) -> Collection<Chunk> {
+        database.collection("chunks")
+    }
+
+    fn attachments(database: &Database) -> Collection<StoredAttachment> {
+        database.collection("attachments")
+    }
+
+    fn expires() -> DateTime {
+        DateTime::from_system_time(SystemTime::now() + UPLOAD_TIMEOUT)
+    }
+
+    ///creates the ttl indexes that remove abandoned uploads and their chunks
+    pub async fn create_indexes(database: &Database) -> Result<()> {
+        //creating an index that already exists has no effect
+        let ttl = || {
+            IndexModel::builder()
+                .keys(doc! {"expires": 1})
+                .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                .build()
+        };
+        Self::uploads(database).create_index(ttl(), None).await?;
+        Self::chunks(database).create_index(ttl(), None).await?;
+        let index = IndexModel::builder()
+            .keys(doc! {"upload_id": 1, "offset": 1})
+            .build();
+        Self::chunks(database).create_index(index, None).await?;
+        Ok(())
+    }
+
+    ///starts an upload of a file of the size, returns the id of the upload. returns invalid name
+    ///if the filename is invalid, invalid file if the file is empty or larger than
+    ///MAX_ATTACHMENT_SIZE and bad request if the mime type is invalid
+    pub async fn start_upload(
+        database: &Database,
+        user_id: &ID,
+        filename: String,
+        size: u64,
+        mime_type: String,
+    ) -> Result<Response> {
+        if let Err(e) = validate_filename(&filename) {
+            return Ok(Response::Error(ServerError::InvalidName(e)));
+        }
+        if size == 0 {
+            return Ok(Response::Error(ServerError::InvalidFile(FileError::Empty)));
+        }
+        if size > MAX_ATTACHMENT_SIZE {
+            let e = FileError::TooLarge(MAX_ATTACHMENT_SIZE as usize);
+            return Ok(Response::Error(ServerError::InvalidFile(e)));
+        }
+        if !is_valid_mime_type(&mime_type) {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let upload = Upload {
+            _id: ObjectId::new(),
+            uploader: user_id.clone(),
+            filename,
+            size,
+            mime_type,
+            received: 0,
+            expires: Self::expires(),
+        };
+        Self::uploads(database).insert_one(&upload, None).await?;
+        let id = ID::new(upload._id.to_hex()).expect("is an object id");
+        Ok(Response::UploadStarted(id))
+    }
+
+    ///appends the chunk to the upload of the user, the offset has to be the number of bytes
+    ///received so far. returns bad request if the upload doesn't exist, the offset is wrong or the
+    ///chunk exceeds the size of the file, limit exceeded if the chunk is larger than
+    ///MAX_CHUNK_SIZE
+    pub async fn upload_chunk(
+        database: &Database,
+        user_id: &ID,
+        upload_id: &ID,
+        offset: u64,
+        bytes: Vec<u8>,
+    ) -> Result<Response> {
+        if bytes.len() > MAX_CHUNK_SIZE {
+            return Ok(Response::Error(ServerError::LimitExceeded));
+        }
+        let oid = match ObjectId::parse_str(&upload_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let length = bytes.len() as u64;
+        //claiming the range atomically makes concurrent chunks for the same offset fail
+        let claimed = Self::uploads(database)
+            .update_one(
+                doc! {
+                    "_id": oid,
+                    "uploader.id": &user_id.id,
+                    "received": offset as i64,
+                    "size": {"$gte": (offset + length) as i64},
+                },
+                doc! {
+                    "$inc": {"received": length as i64},
+                    "$set": {"expires": Self::expires()},
+                },
+                None,
+            )
+            .await?;
+        if claimed.modified_count == 0 {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let chunk = Chunk {
+            upload_id: oid,
+            offset,
+            data: Binary {
+                subtype: BinarySubtype::Generic,
+                bytes,
+            },
+            expires: Self::expires(),
+        };
+        Self::chunks(database).insert_one(chunk, None).await?;
+        Ok(Response::Success)
+    }
+
+    ///removes the metadata of the image and stores its thumbnails, returns the content that is
+    ///stored instead of the uploaded one. files that are no image are returned unchanged
+    async fn prepare_attachment(
+        blobs: &BlobStore,
+        content: Vec<u8>,
+    ) -> Result<(Vec<u8>, Option<ImageInfo>)> {
+        //decoding and scaling images takes too long to block the runtime
+        let (content, prepared) =
+            tokio::task::spawn_blocking(move || match strip_metadata(&content) {
+                Some(stripped) => {
+                    let prepared = prepare_image(&stripped);
+                    (stripped, prepared)
+                }
+                None => (content, None),
+            })
+            .await?;
+        let prepared = match prepared {
+            Some(prepared) => prepared,
+            None => return Ok((content, None)),
+        };
+
+        let mut thumbnails = Vec::new();
+        for scaled in prepared.thumbnails {
+            thumbnails.push(Thumbnail {
+                max_size: scaled.max_size,
+                width: scaled.width,
+                height: scaled.height,
+                size: scaled.png.len() as u64,
+                hash: blobs.put(&scaled.png).await?,
+            });
+        }
+        let image = ImageInfo {
+            width: prepared.width,
+            height: prepared.height,
+            thumbnails,
+        };
+        Ok((content, Some(image)))
+    }
+
+    ///moves the content of the completely received upload of the user into the blob store and
+    ///returns the attachment. metadata of images is removed, so the size of the attachment may
+    ///be smaller than the uploaded size. returns bad request if the upload doesn't exist or is
+    ///incomplete
+    pub async fn finish_upload(
+        database: &Database,
+        blobs: &BlobStore,
+        user_id: &ID,
+        upload_id: &ID,
+    ) -> Result<Response> {
+        let oid = match ObjectId::parse_str(&upload_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let uploads = Self::uploads(database);
+        let filter = doc! {"_id": oid, "uploader.id": &user_id.id};
+        let upload = match uploads.find_one(filter.clone(), None).await? {
+            Some(upload) if upload.received == upload.size => upload,
+            _ => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+
+        let opts = FindOptions::builder().sort(doc! {"offset": 1}).build();
+        let mut cursor = Self::chunks(database)
+            .find(doc! {"upload_id": oid}, opts)
+            .await?;
+        let mut content = Vec::with_capacity(upload.size as usize);
+        while cursor.advance().await? {
+            let chunk = cursor.deserialize_current()?;
+            content.extend_from_slice(&chunk.data.bytes);
+        }
+        //chunks that expired before the upload are gone
+        if content.len() as u64 != upload.size {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let (content, image) = Self::prepare_attachment(blobs, content).await?;
+        let hash = blobs.put(&content).await?;
+
+        let attachment_id = ObjectId::new();
+        let attachment = Attachment {
+            id: ID::new(attachment_id.to_hex()).expect("is an object id"),
+            filename: upload.filename,
+            size: content.len() as u64,
+            mime_type: upload.mime_type,
+            hash,
+            image,
+        };
+        let stored = StoredAttachment {
+            _id: attachment_id,
+            uploader: user_id.clone(),
+            attachment: attachment.clone(),
+            created_at: SystemTime::now(),
+        };
+        Self::attachments(database).insert_one(stored, None).await?;
+        uploads.delete_one(filter, None).await?;
+        Self::chunks(database)
+            .delete_many(doc! {"upload_id": oid}, None)
+            .await?;
+        Ok(Response::Attachment(attachment))
+    }
+
+    ///returns the attachments in the order of the ids, None if one of them doesn't exist or was
+    ///uploaded by another user
+    pub async fn find_attachments(
+        database: &Database,
+        user_id: &ID,
+        attachment_ids: &[ID],
+    ) -> Result<Option<Vec<Attachment>>> {
+        let oids = match attachment_ids
+            .iter()
+            .map(|id| ObjectId::parse_str(&id.id))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(oids) => oids,
+            Err(_) => return Ok(None),
+        };
+        let mut cursor = Self::attachments(database)
+            .find(
+                doc! {"_id": {"$in": &oids}, "uploader.id": &user_id.id},
+                None,
+            )
+            .await?;
+        let mut found = Vec::new();
+        while cursor.advance().await? {
+            found.push(cursor.deserialize_current()?);
+        }
+        Ok(oids
+            .iter()
+            .map(|oid| {
+                found
+                    .iter()
+                    .find(|stored| stored._id == *oid)
+                    .map(|stored| stored.attachment.clone())
+            })
+            .collect())
+    }
+
+    ///returns up to MAX_CHUNK_SIZE bytes of the attachment or its thumbnail of the size starting
+    ///at the offset, an empty chunk at the end of the file. returns bad request if the offset is
+    ///behind the end of the file or there is no thumbnail of the size
+    pub async fn download(
+        blobs: &BlobStore,
+        attachment: &Attachment,
+        thumbnail: Option<u32>,
+        offset: u64,
+    ) -> Result<Response> {
+        let (hash, size) = match thumbnail {
+            None => (&attachment.hash, attachment.size),
+            Some(max_size) => match attachment
+                .image
+                .iter()
+                .flat_map(|image| &image.thumbnails)
+                .find(|thumbnail| thumbnail.max_size == max_size)
+            {
+                Some(thumbnail) => (&thumbnail.hash, thumbnail.size),
+                None => return Ok(Response::Error(ServerError::BadRequest)),
+            },
+        };
+        if offset > size {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        match blobs.read(hash, offset, MAX_CHUNK_SIZE).await? {
+            Some(bytes) => Ok(Response::Chunk(bytes)),
+            None => Ok(Response::Error(ServerError::InternalServerError)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_upload() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_ATTACHMENTS1");
+        db.drop(None).await.unwrap();
+        AttachmentHandler::create_indexes(&db).await.unwrap();
+        let root = std::env::temp_dir().join("TEST_ATTACHMENTS1");
+        let blobs = BlobStore::new(&root);
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let moritz = ID::new("123123123123123123123124".to_string()).unwrap();
+        let content: Vec<u8> = (0..MAX_CHUNK_SIZE + 10).map(|i| i as u8).collect();
+
+        let too_large = AttachmentHandler::start_upload(
+            &db,
+            &max,
+            "a.bin".to_string(),
+            MAX_ATTACHMENT_SIZE + 1,
+            "application/octet-stream".to_string(),
+        )
+        .await
+        .unwrap();
+        let upload_id = match AttachmentHandler::start_upload(
+            &db,
+            &max,
+            "a.bin".to_string(),
+            content.len() as u64,
+            "application/octet-stream".to_string(),
+        )
+        .await
+        .unwrap()
+        {
+            Response::UploadStarted(id) => id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let (first, second) = content.split_at(MAX_CHUNK_SIZE);
+        assert!(
+            AttachmentHandler::upload_chunk(&db, &max, &upload_id, 0, first.to_vec())
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let incomplete = AttachmentHandler::finish_upload(&db, &blobs, &max, &upload_id)
+            .await
+            .unwrap();
+        //chunks have to be uploaded in order and by the uploader
+        let repeated = AttachmentHandler::upload_chunk(&db, &max, &upload_id, 0, first.to_vec())
+            .await
+            .unwrap();
+        let foreign = AttachmentHandler::upload_chunk(
+            &db,
+            &moritz,
+            &upload_id,
+            MAX_CHUNK_SIZE as u64,
+            second.to_vec(),
+        )
+        .await
+        .unwrap();
+        let too_long = AttachmentHandler::upload_chunk(
+            &db,
+            &max,
+            &upload_id,
+            MAX_CHUNK_SIZE as u64,
+            content.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(AttachmentHandler::upload_chunk(
+            &db,
+            &max,
+            &upload_id,
+            MAX_CHUNK_SIZE as u64,
+            second.to_vec()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let attachment = match AttachmentHandler::finish_upload(&db, &blobs, &max, &upload_id)
+            .await
+            .unwrap()
+        {
+            Response::Attachment(attachment) => attachment,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let found =
+            AttachmentHandler::find_attachments(&db, &max, std::slice::from_ref(&attachment.id))
+                .await
+                .unwrap();
+        let not_found =
+            AttachmentHandler::find_attachments(&db, &moritz, std::slice::from_ref(&attachment.id))
+                .await
+                .unwrap();
+        let mut downloaded = Vec::new();
+        loop {
+            match AttachmentHandler::download(&blobs, &attachment, None, downloaded.len() as u64)
+                .await
+                .unwrap()
+            {
+                Response::Chunk(bytes) if bytes.is_empty() => break,
+                Response::Chunk(bytes) => downloaded.extend(bytes),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+        db.drop(None).await.unwrap();
+        let _ = tokio::fs::remove_dir_all(&root).await;
+
+        assert!(matches!(
+            too_large,
+            Response::Error(ServerError::InvalidFile(FileError::TooLarge(_)))
+        ));
+        assert!(matches!(
+            incomplete,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert!(matches!(repeated, Response::Error(ServerError::BadRequest)));
+        assert!(matches!(foreign, Response::Error(ServerError::BadRequest)));
+        assert!(matches!(
+            too_long,
+            Response::Error(ServerError::LimitExceeded)
+        ));
+        assert_eq!(attachment.size, content.len() as u64);
+        assert_eq!(attachment.hash, BlobStore::hash(&content));
+        assert_eq!(attachment.image, None);
+        assert_eq!(found, Some(vec![attachment]));
+        assert_eq!(not_found, None);
+        assert_eq!(downloaded, content);
+    }
+
+    #[test]
+    async fn test_upload_image() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_ATTACHMENTS2");
+        db.drop(None).await.unwrap();
+        AttachmentHandler::create_indexes(&db).await.unwrap();
+        let root = std::env::temp_dir().join("TEST_ATTACHMENTS2");
+        let blobs = BlobStore::new(&root);
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let image = image::DynamicImage::ImageRgb8(image::RgbImage::new(300, 200));
+        let mut png = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        //a text chunk with the location is inserted after the header chunk
+        let mut tagged = png[..33].to_vec();
+        tagged.extend_from_slice(&15u32.to_be_bytes());
+        tagged.extend_from_slice(b"tEXtLocation\0Berlin");
+        tagged.extend_from_slice(&[0; 4]);
+        tagged.extend_from_slice(&png[33..]);
+
+        let upload_id = match AttachmentHandler::start_upload(
+            &db,
+            &max,
+            "map.png".to_string(),
+            tagged.len() as u64,
+            "image/png".to_string(),
+        )
+        .await
+        .unwrap()
+        {
+            Response::UploadStarted(id) => id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        assert!(
+            AttachmentHandler::upload_chunk(&db, &max, &upload_id, 0, tagged)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let attachment = match AttachmentHandler::finish_upload(&db, &blobs, &max, &upload_id)
+            .await
+            .unwrap()
+        {
+            Response::Attachment(attachment) => attachment,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let thumbnail = AttachmentHandler::download(&blobs, &attachment, Some(128), 0)
+            .await
+            .unwrap();
+        let missing = AttachmentHandler::download(&blobs, &attachment, Some(512), 0)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        let _ = tokio::fs::remove_dir_all(&root).await;
+
+        assert_eq!(attachment.size, png.len() as u64);
+        assert_eq!(attachment.hash, BlobStore::hash(&png));
+        let info = attachment.image.unwrap();
+        assert_eq!((info.width, info.height), (300, 200));
+        assert_eq!(info.thumbnails.len(), 2);
+        assert_eq!(info.thumbnails[0].width, 128);
+        assert!(info.thumbnails[0].height < 128);
+        let thumbnail_size = info.thumbnails[0].size;
+        assert!(
+            matches!(thumbnail, Response::Chunk(bytes) if bytes.len() as u64 == thumbnail_size)
+        );
+        assert!(matches!(missing, Response::Error(ServerError::BadRequest)));
+    }
+}
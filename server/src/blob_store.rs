@@ -0,0 +1,123 @@
+use anyhow::Result;
+use mongodb::bson::oid::ObjectId;
+use sha2::{Digest, Sha256};
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+///directory the server stores blobs in, relative to the working directory
+pub const BLOB_DIRECTORY: &str = "blobs";
+
+///content addressed storage for files on the local filesystem, blobs are identified by the hex
+///encoded sha256 hash of their content so identical files are stored once. blobs are never
+///deleted because they may be referenced more than once
+#[derive(Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    ///returns the hash that identifies the bytes in the store
+    pub fn hash(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    ///returns the path of the blob, blobs are spread over directories named by the first two
+    ///characters of their hash. returns None if the hash is not a valid sha256 hash so it can't
+    ///be used to leave the root directory
+    fn path(&self, hash: &str) -> Option<PathBuf> {
+        let valid = hash.len() == 64
+            && hash
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+        valid.then(|| self.root.join(&hash[..2]).join(hash))
+    }
+
+    ///stores the bytes and returns their hash, storing bytes that are already stored has no
+    ///effect
+    pub async fn put(&self, bytes: &[u8]) -> Result<String> {
+        let hash = Self::hash(bytes);
+        let path = self.path(&hash).expect("is a sha256 hash");
+        if fs::try_exists(&path).await? {
+            return Ok(hash);
+        }
+        //the blob is written to a temporary file first so readers never see a partial blob
+        let tmp_dir = self.root.join("tmp");
+        fs::create_dir_all(&tmp_dir).await?;
+        fs::create_dir_all(path.parent().expect("has a parent")).await?;
+        let tmp = tmp_dir.join(ObjectId::new().to_hex());
+        fs::write(&tmp, bytes).await?;
+        fs::rename(&tmp, &path).await?;
+        Ok(hash)
+    }
+
+    ///returns the content of the blob, None if there is no blob with the hash
+    pub async fn get(&self, hash: &str) -> Result<Option<Vec<u8>>> {
+        let path = match self.path(hash) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        match fs::read(path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    ///returns up to length bytes of the blob starting at the offset, None if there is no blob
+    ///with the hash
+    pub async fn read(&self, hash: &str, offset: u64, length: usize) -> Result<Option<Vec<u8>>> {
+        let path = match self.path(hash) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let mut file = match fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        file.seek(SeekFrom::Start(offset)).await?;
+        let mut bytes = Vec::new();
+        file.take(length as u64).read_to_end(&mut bytes).await?;
+        Ok(Some(bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_blob_store() {
+        let root = std::env::temp_dir().join("TEST_BLOBS1");
+        let _ = fs::remove_dir_all(&root).await;
+        let store = BlobStore::new(&root);
+
+        let hash = store.put(b"hello").await.unwrap();
+        assert_eq!(
+            hash,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(store.put(b"hello").await.unwrap(), hash);
+        assert_eq!(store.get(&hash).await.unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(
+            store.read(&hash, 1, 3).await.unwrap(),
+            Some(b"ell".to_vec())
+        );
+        assert_eq!(
+            store.read(&hash, 3, 10).await.unwrap(),
+            Some(b"lo".to_vec())
+        );
+        assert_eq!(store.read(&hash, 10, 10).await.unwrap(), Some(Vec::new()));
+        assert_eq!(store.get(&BlobStore::hash(b"other")).await.unwrap(), None);
+        assert_eq!(store.get("../../etc/passwd").await.unwrap(), None);
+        fs::remove_dir_all(&root).await.unwrap();
+    }
+}
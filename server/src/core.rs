@@ -1,11 +1,13 @@
 use anyhow::Result;
 use common::connection::Connection;
-use log::error;
+use log::{error, info};
 use mongodb::Client;
 use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
 
 use common::error::ServerError;
-use common::messages::{Request, RequestType, Response};
+use common::id::ID;
+use common::messages::{Request, RequestType, Response, Typing};
 
 use crate::handler::Handler;
 
@@ -45,6 +47,87 @@ async fn process_request(
             }
         },
 
+        RequestType::GetServer(server_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_server(&mongo_client, cookie, &server_id)
+                    .await?
+            }
+        },
+
+        RequestType::UpdateServer(server_id, update) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .update_server(&mongo_client, cookie, &server_id, update)
+                    .await?
+            }
+        },
+
+        RequestType::TransferOwnership(server_id, new_owner) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .transfer_ownership(&mongo_client, cookie, &server_id, &new_owner)
+                    .await?
+            }
+        },
+
+        RequestType::GetMembers(server_id, page) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_members(&mongo_client, cookie, &server_id, page)
+                    .await?
+            }
+        },
+
+        RequestType::SetNickname(server_id, nickname) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .set_nickname(&mongo_client, cookie, &server_id, nickname)
+                    .await?
+            }
+        },
+
+        RequestType::ListEmoji(server_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .list_emoji(&mongo_client, cookie, &server_id)
+                    .await?
+            }
+        },
+
+        RequestType::CreateEmoji(server_id, name, image) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .create_emoji(&mongo_client, cookie, &server_id, name, image)
+                    .await?
+            }
+        },
+
+        RequestType::DeleteEmoji(server_id, emoji_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .delete_emoji(&mongo_client, cookie, &server_id, &emoji_id)
+                    .await?
+            }
+        },
+
+        RequestType::GetEmojiImage(server_id, emoji_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_emoji_image(&mongo_client, cookie, &server_id, &emoji_id)
+                    .await?
+            }
+        },
+
         RequestType::NewChannel(server_id, name) => match request.session_cookie {
             None => Response::Error(ServerError::PermissionDenied),
             Some(cookie) => {
@@ -66,10 +149,21 @@ async fn process_request(
             }
         }
 
-        RequestType::SendMessage(server_id, channel_name, message_content) => match request.session_cookie {
-            None => Response::Error(ServerError::PermissionDenied),
-            Some(cookie) => {
-                handler.send_message(&mongo_client, cookie, &server_id, channel_name, message_content).await?
+        RequestType::SendMessage(server_id, channel_name, message_content, reply_to) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .send_message(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            channel_name,
+                            message_content,
+                            reply_to,
+                        )
+                        .await?
+                }
             }
         }
 
@@ -79,6 +173,546 @@ async fn process_request(
                 handler.get_message_block(&mongo_client, cookie, &server_id, channel_name, block_nr).await?
             }
         }
+
+        RequestType::GetHistory(server_id, channel_name, query, order) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .get_history(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            channel_name,
+                            query,
+                            order,
+                        )
+                        .await?
+                }
+            }
+        }
+
+        RequestType::EditMessage(server_id, channel_name, message_id, content) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .edit_message(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            channel_name,
+                            &message_id,
+                            content,
+                        )
+                        .await?
+                }
+            }
+        }
+
+        RequestType::DeleteMessage(server_id, channel_name, message_id) => match request
+            .session_cookie
+        {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .delete_message(&mongo_client, cookie, &server_id, channel_name, &message_id)
+                    .await?
+            }
+        },
+
+        RequestType::GetEditHistory(server_id, channel_name, message_id) => match request
+            .session_cookie
+        {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_edit_history(&mongo_client, cookie, &server_id, channel_name, &message_id)
+                    .await?
+            }
+        },
+
+        RequestType::AddReaction(server_id, channel_name, message_id, emoji) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .add_reaction(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            channel_name,
+                            &message_id,
+                            emoji,
+                        )
+                        .await?
+                }
+            }
+        }
+
+        RequestType::RemoveReaction(server_id, channel_name, message_id, emoji) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .remove_reaction(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            channel_name,
+                            &message_id,
+                            emoji,
+                        )
+                        .await?
+                }
+            }
+        }
+
+        RequestType::CreateThread(server_id, channel_name, message_id, name) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .create_thread(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            channel_name,
+                            &message_id,
+                            name,
+                        )
+                        .await?
+                }
+            }
+        }
+
+        RequestType::GetThreads(server_id, channel_name) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_threads(&mongo_client, cookie, &server_id, channel_name)
+                    .await?
+            }
+        },
+
+        RequestType::SendAttachments(server_id, channel_name, message_content, attachment_ids) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .send_attachments(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            channel_name,
+                            message_content,
+                            attachment_ids,
+                        )
+                        .await?
+                }
+            }
+        }
+
+        RequestType::DownloadAttachment(
+            server_id,
+            channel_name,
+            message_id,
+            attachment_id,
+            thumbnail,
+            offset,
+        ) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .download_attachment(
+                        &mongo_client,
+                        cookie,
+                        &server_id,
+                        &channel_name,
+                        &message_id,
+                        &attachment_id,
+                        thumbnail,
+                        offset,
+                    )
+                    .await?
+            }
+        },
+
+        RequestType::StartUpload(filename, size, mime_type) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .start_upload(&mongo_client, cookie, filename, size, mime_type)
+                    .await?
+            }
+        },
+
+        RequestType::UploadChunk(upload_id, offset, bytes) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .upload_chunk(&mongo_client, cookie, &upload_id, offset, bytes)
+                    .await?
+            }
+        },
+
+        RequestType::FinishUpload(upload_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .finish_upload(&mongo_client, cookie, &upload_id)
+                    .await?
+            }
+        },
+
+        RequestType::SendThreadMessage(server_id, thread_id, message_content, reply_to) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .send_thread_message(
+                            &mongo_client,
+                            cookie,
+                            &server_id,
+                            &thread_id,
+                            message_content,
+                            reply_to,
+                        )
+                        .await?
+                }
+            }
+        }
+
+        RequestType::GetThreadHistory(server_id, thread_id, query, order) => match request
+            .session_cookie
+        {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_thread_history(&mongo_client, cookie, &server_id, &thread_id, query, order)
+                    .await?
+            }
+        },
+
+        RequestType::ArchiveThread(server_id, thread_id, archived) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .archive_thread(&mongo_client, cookie, &server_id, &thread_id, archived)
+                        .await?
+                }
+            }
+        }
+
+        RequestType::GetMentions(server_id, limit) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_mentions(&mongo_client, cookie, &server_id, limit)
+                    .await?
+            }
+        },
+
+        RequestType::PinMessage(server_id, channel_name, message_id) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .pin_message(&mongo_client, cookie, &server_id, channel_name, &message_id)
+                        .await?
+                }
+            }
+        }
+
+        RequestType::UnpinMessage(server_id, channel_name, message_id) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .unpin_message(&mongo_client, cookie, &server_id, channel_name, &message_id)
+                        .await?
+                }
+            }
+        }
+
+        RequestType::GetPins(server_id, channel_name) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_pins(&mongo_client, cookie, &server_id, channel_name)
+                    .await?
+            }
+        },
+
+        RequestType::SearchMessages(server_id, query, filters, page) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .search_messages(&mongo_client, cookie, &server_id, query, filters, page)
+                        .await?
+                }
+            }
+        }
+
+        RequestType::OpenDm(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.open_dm(&mongo_client, cookie, &other_id).await?,
+        },
+
+        RequestType::ListDms() => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.list_dms(&mongo_client, cookie).await?,
+        },
+
+        RequestType::SendDm(dm_id, content, reply_to) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .send_dm(&mongo_client, cookie, &dm_id, content, reply_to)
+                    .await?
+            }
+        },
+
+        RequestType::GetDmHistory(dm_id, query, order) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_dm_history(&mongo_client, cookie, &dm_id, query, order)
+                    .await?
+            }
+        },
+
+        RequestType::EditDmMessage(dm_id, message_id, content) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .edit_dm_message(&mongo_client, cookie, &dm_id, &message_id, content)
+                    .await?
+            }
+        },
+
+        RequestType::DeleteDmMessage(dm_id, message_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .delete_dm_message(&mongo_client, cookie, &dm_id, &message_id)
+                    .await?
+            }
+        },
+
+        RequestType::CreateGroupDm(name, others) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .create_group_dm(&mongo_client, cookie, name, others)
+                    .await?
+            }
+        },
+
+        RequestType::AddDmParticipant(dm_id, added_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .add_dm_participant(&mongo_client, cookie, &dm_id, &added_id)
+                    .await?
+            }
+        },
+
+        RequestType::RemoveDmParticipant(dm_id, removed_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .remove_dm_participant(&mongo_client, cookie, &dm_id, &removed_id)
+                    .await?
+            }
+        },
+
+        RequestType::RenameGroupDm(dm_id, name) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .rename_group_dm(&mongo_client, cookie, &dm_id, name)
+                    .await?
+            }
+        },
+
+        RequestType::LeaveGroupDm(dm_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .leave_group_dm(&mongo_client, cookie, &dm_id)
+                    .await?
+            }
+        },
+
+        RequestType::GetFriends() => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.get_friends(&mongo_client, cookie).await?,
+        },
+
+        RequestType::AddFriend(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.add_friend(&mongo_client, cookie, &other_id).await?,
+        },
+
+        RequestType::AcceptFriend(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .accept_friend(&mongo_client, cookie, &other_id)
+                    .await?
+            }
+        },
+
+        RequestType::DeclineFriend(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .decline_friend(&mongo_client, cookie, &other_id)
+                    .await?
+            }
+        },
+
+        RequestType::CancelFriendRequest(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .cancel_friend_request(&mongo_client, cookie, &other_id)
+                    .await?
+            }
+        },
+
+        RequestType::RemoveFriend(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .remove_friend(&mongo_client, cookie, &other_id)
+                    .await?
+            }
+        },
+
+        RequestType::BlockUser(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.block_user(&mongo_client, cookie, &other_id).await?,
+        },
+
+        RequestType::UnblockUser(other_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .unblock_user(&mongo_client, cookie, &other_id)
+                    .await?
+            }
+        },
+
+        RequestType::SetPresence(presence, custom_status) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .set_presence(&mongo_client, cookie, presence, custom_status)
+                    .await?
+            }
+        },
+
+        RequestType::GetPresence(user_ids) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.get_presence(cookie, user_ids).await?,
+        },
+
+        RequestType::GetEvents(after) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.get_events(cookie, after).await?,
+        },
+
+        RequestType::GetProfile(user_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.get_profile(cookie, &user_id).await?,
+        },
+
+        RequestType::UpdateProfile(update) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.update_profile(cookie, update).await?,
+        },
+
+        RequestType::GetProfileImage(user_id, image) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => handler.get_profile_image(cookie, &user_id, image).await?,
+        },
+
+        RequestType::StartTyping(server_id, channel_name) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .start_typing(&mongo_client, cookie, &server_id, channel_name)
+                    .await?
+            }
+        },
+
+        RequestType::GetTyping(server_id, channel_name) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_typing(&mongo_client, cookie, &server_id, channel_name)
+                    .await?
+            }
+        },
+
+        //subscriptions keep the connection open and are handled by handler_fn
+        RequestType::SubscribeTyping(_, _) => Response::Error(ServerError::BadRequest),
+
+        RequestType::Ack(server_id, channel_name, message_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .ack(
+                        &mongo_client,
+                        cookie,
+                        &server_id,
+                        &channel_name,
+                        &message_id,
+                    )
+                    .await?
+            }
+        },
+
+        RequestType::GetUnread(server_id) => match request.session_cookie {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .get_unread(&mongo_client, cookie, &server_id)
+                    .await?
+            }
+        },
+
+        RequestType::UpdateChannel(server_id, channel_name, update) => match request.session_cookie
+        {
+            None => Response::Error(ServerError::PermissionDenied),
+            Some(cookie) => {
+                handler
+                    .update_channel(&mongo_client, cookie, &channel_name, &server_id, update)
+                    .await?
+            }
+        },
+
+        RequestType::MoveChannel(server_id, channel_name, position, category) => {
+            match request.session_cookie {
+                None => Response::Error(ServerError::PermissionDenied),
+                Some(cookie) => {
+                    handler
+                        .move_channel(
+                            &mongo_client,
+                            cookie,
+                            &channel_name,
+                            &server_id,
+                            position,
+                            category,
+                        )
+                        .await?
+                }
+            }
+        }
     })
 }
 
@@ -96,14 +730,94 @@ async fn fetch_request(conn: &mut Connection) -> Request {
     }
 }
 
+///keeps the connection open and writes the other users typing in the channel, first the current
+///ones and then whenever they change, until the connection is closed
+async fn stream_typing(
+    conn: &mut Connection,
+    mongo_client: &Client,
+    handler: &Handler,
+    session_cookie: Option<ID>,
+    server_id: &ID,
+    channel_name: &str,
+) -> Result<()> {
+    let user_id = match session_cookie {
+        None => {
+            return conn
+                .write(Response::Error(ServerError::PermissionDenied))
+                .await
+        }
+        Some(cookie) => cookie,
+    };
+    let (channel, mut changes) = match handler
+        .subscribe_typing(mongo_client, &user_id, server_id, channel_name)
+        .await?
+    {
+        Ok(subscription) => subscription,
+        Err(response) => return conn.write(response).await,
+    };
+    let others = |typing: Vec<Typing>| -> Vec<Typing> {
+        typing
+            .into_iter()
+            .filter(|entry| entry.user_id != user_id)
+            .collect()
+    };
+
+    conn.write(Response::Typing(others(handler.typing.typing(&channel))))
+        .await?;
+    loop {
+        match changes.recv().await {
+            Ok(change) if change.channel == channel => {
+                conn.write(Response::Typing(others(change.typing))).await?;
+            }
+            //changes of other channels, missed changes are made up for by the next one
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
 ///creates new connection from Stream and does all opperations on it
 async fn handler_fn(stream: TcpStream, mongo_client: Client, handler: Handler) {
     let mut conn = Connection::new(stream);
     let request = fetch_request(&mut conn).await;
-    let response = process_request(mongo_client, request, handler)
-        .await
-        .unwrap_or(Response::Error(ServerError::InternalServerError));
-    conn.write(response).await.unwrap();
+    if let RequestType::SubscribeTyping(server_id, channel_name) = &request.tp {
+        let streamed = stream_typing(
+            &mut conn,
+            &mongo_client,
+            &handler,
+            request.session_cookie.clone(),
+            server_id,
+            channel_name,
+        )
+        .await;
+        if let Err(e) = streamed {
+            //usually the client closed the connection
+            info!("typing subscription ended: {:?}", e);
+        }
+        return;
+    }
+    let mut request = request;
+    loop {
+        //files are transferred in chunks, so the connection stays open for the next chunk
+        let transfer = matches!(
+            request.tp,
+            RequestType::StartUpload(..)
+                | RequestType::UploadChunk(..)
+                | RequestType::DownloadAttachment(..)
+        );
+        let response = process_request(mongo_client.clone(), request, handler.clone())
+            .await
+            .unwrap_or(Response::Error(ServerError::InternalServerError));
+        conn.write(response).await.unwrap();
+        if !transfer {
+            return;
+        }
+        request = match conn.read().await {
+            Ok(request) => request,
+            //the client closed the connection after the transfer
+            Err(_) => return,
+        };
+    }
 }
 
 pub async fn accept_new_connections(mongo_client: Client, handler: Handler) -> Result<()> {
@@ -120,11 +834,14 @@ pub async fn accept_new_connections(mongo_client: Client, handler: Handler) -> R
 
 #[cfg(test)]
 mod test {
-    use common::messages::{Request, RequestType, Message};
-    use tokio::test;
     use super::*;
+    use common::messages::{Request, RequestType};
+    use tokio::test;
 
-    use crate::{mongodb::connect_mongo, handler::Handler, user::UserHandler, session::SessionHandler};
+    use crate::{
+        blob_store::BlobStore, handler::Handler, mongodb::connect_mongo, session::SessionHandler,
+        user::UserHandler,
+    };
 
     #[test]
     async fn happy_path(){
@@ -132,8 +849,14 @@ mod test {
         let request_type = RequestType::SignUp("TEST User".to_string(), "TEST User Password".to_string());
         let mut request = Request { tp: request_type, session_cookie: None};
         let test_db = client.database("TEST_DB");
-        let handler = Handler::new(SessionHandler::from_names(&client, "TEST_DB", "SESSIONS"), UserHandler::from_names(&client, "TEST_DB", "USERS"));
-        let resp = process_request(client.clone(), request, handler.clone()).await.unwrap();
+        let handler = Handler::new(
+            SessionHandler::from_names(&client, "TEST_DB", "SESSIONS"),
+            UserHandler::from_names(&client, "TEST_DB", "USERS"),
+            BlobStore::new(std::env::temp_dir().join("TEST_DB")),
+        );
+        let resp = process_request(client.clone(), request, handler.clone())
+            .await
+            .unwrap();
         let token = match resp {
             Response::SessionCreated(token) => token,
             other => {
@@ -157,16 +880,34 @@ mod test {
         assert!(process_request(client.clone(), request, handler.clone()).await.unwrap().succeeded());
 
         let content = "This is a test message".to_string();
-        request = Request::new(RequestType::SendMessage(server_id.clone(), channel_name.clone(), content.clone()), Some(token.clone()));
-        assert!(process_request(client.clone(), request, handler.clone()).await.unwrap().succeeded());
+        request = Request::new(
+            RequestType::SendMessage(
+                server_id.clone(),
+                channel_name.clone(),
+                content.clone(),
+                None,
+            ),
+            Some(token.clone()),
+        );
+        assert!(process_request(client.clone(), request, handler.clone())
+            .await
+            .unwrap()
+            .succeeded());
 
         
         request = Request::new(RequestType::GetMessages(server_id.clone(), channel_name.clone(), 0), Some(token.clone()));
         match process_request(client.clone(), request, handler.clone()).await.unwrap() {
             Response::MessagesFound(messages) => {
                 assert_eq!(messages.len(), 2);
-                assert_eq!(messages[0], Message::new("channel created...".to_string(), "SERVER".to_string()));
-                assert_eq!(messages[1], Message::new(content, "TEST User".to_string()));
+                assert_eq!(messages[0].content, "channel created...");
+                assert_eq!(messages[0].author, "SERVER");
+                assert_eq!(messages[0].author_id, None);
+                assert_eq!(messages[1].content, content);
+                assert_eq!(messages[1].author, "TEST User");
+                assert_eq!(messages[1].author_id, Some(token.clone()));
+                assert_ne!(messages[0].id, messages[1].id);
+                assert!(messages[0].created_at <= messages[1].created_at);
+                assert_eq!(messages[1].edited_at, None);
             }
             other => {
                 panic!("unexpected enum variant: {:?}", other);
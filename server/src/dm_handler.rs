@@ -0,0 +1,998 @@
+use anyhow::Result;
+use common::{
+    dm::{validate_group_dm_name, DmInfo, MAX_GROUP_DM_MEMBERS},
+    error::ServerError,
+    id::ID,
+    messages::{HistoryOrder, HistoryQuery, Message, MessageLimits, Reply, Response},
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson},
+    options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument},
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+use crate::friend_handler::Relationships;
+use crate::message_store::{is_duplicate_key, MessageStore};
+use crate::user::{FindError, UserHandler};
+
+///name of the database that holds the direct message conversations
+pub const DM_DATABASE: &str = "DMS";
+
+///metadata of a direct message conversation, stored in the conversations collection of the dm
+///database, the messages are stored in the collection named by the id of the conversation
+#[derive(Serialize, Deserialize)]
+struct DmConfig {
+    _id: ObjectId,
+    ///ids of both participants joined in ascending order, only set for conversations between two
+    ///users, there is only one such conversation per pair
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pair: Option<String>,
+    ///name of a group conversation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    ///the owner of a group conversation is the only participant allowed to remove others
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<ID>,
+    participants: Vec<ID>,
+    created_at: SystemTime,
+    last_activity: SystemTime,
+}
+
+pub struct DmHandler;
+
+impl DmConfig {
+    fn new(first: &ID, second: &ID) -> Self {
+        let now = SystemTime::now();
+        Self {
+            _id: ObjectId::new(),
+            pair: Some(Self::pair_of(first, second)),
+            name: None,
+            owner: None,
+            participants: vec![first.clone(), second.clone()],
+            created_at: now,
+            last_activity: now,
+        }
+    }
+
+    fn new_group(name: String, owner: &ID, participants: Vec<ID>) -> Self {
+        let now = SystemTime::now();
+        Self {
+            _id: ObjectId::new(),
+            pair: None,
+            name: Some(name),
+            owner: Some(owner.clone()),
+            participants,
+            created_at: now,
+            last_activity: now,
+        }
+    }
+
+    fn pair_of(first: &ID, second: &ID) -> String {
+        if first.id <= second.id {
+            format!("{}:{}", first.id, second.id)
+        } else {
+            format!("{}:{}", second.id, first.id)
+        }
+    }
+
+    fn store(&self, dms: &Database) -> MessageStore {
+        MessageStore::new(dms, &self._id.to_hex())
+    }
+
+    fn id(&self) -> ID {
+        ID::new(self._id.to_hex()).expect("is an object id")
+    }
+}
+
+impl DmHandler {
+    ///creates the index that keeps one conversation per pair of users
+    pub async fn create_indexes(dms: &Database) -> Result<()> {
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        //creating an index that already exists has no effect
+        //group conversations have no pair and are skipped by the sparse index
+        let index = IndexModel::builder()
+            .keys(doc! {"pair": 1})
+            .options(IndexOptions::builder().unique(true).sparse(true).build())
+            .build();
+        conversations.create_index(index, None).await?;
+        Ok(())
+    }
+
+    ///returns the conversation if the user takes part in it, bad request if it does not exist
+    async fn load_dm(dms: &Database, dm_id: &ID, user_id: &ID) -> Result<FindError<DmConfig>> {
+        let oid = match ObjectId::parse_str(&dm_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(FindError::Err(ServerError::BadRequest)),
+        };
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        match conversations.find_one(doc! {"_id": oid}, None).await? {
+            Some(dm) if dm.participants.contains(user_id) => Ok(FindError::Ok(dm)),
+            Some(_) => Ok(FindError::Err(ServerError::PermissionDenied)),
+            None => Ok(FindError::Err(ServerError::BadRequest)),
+        }
+    }
+
+    ///returns the group conversation if the user takes part in it, bad request if it does not
+    ///exist or is a conversation between two users
+    async fn load_group(dms: &Database, dm_id: &ID, user_id: &ID) -> Result<FindError<DmConfig>> {
+        match Self::load_dm(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) if dm.pair.is_some() => Ok(FindError::Err(ServerError::BadRequest)),
+            loaded => Ok(loaded),
+        }
+    }
+
+    ///returns the username of the user, None if the user does not exist
+    async fn username_of(user_handler: &UserHandler, user_id: &ID) -> Result<Option<String>> {
+        let oid = match ObjectId::parse_str(&user_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(None),
+        };
+        Ok(user_handler.get_user(oid).await?.map(|user| user.username))
+    }
+
+    ///adds a message of the server to the conversation
+    async fn announce(dms: &Database, dm: &DmConfig, content: String) -> Result<()> {
+        dm.store(dms)
+            .append(Message::new(content, "SERVER".to_string()))
+            .await?;
+        Ok(())
+    }
+
+    ///returns the message of the conversation if the user is its author
+    async fn load_own_message(
+        store: &MessageStore,
+        user_id: &ID,
+        message_id: &ID,
+    ) -> Result<FindError<Message>> {
+        match store.find_message(message_id).await? {
+            Some(message) if message.author_id.as_ref() == Some(user_id) => {
+                Ok(FindError::Ok(message))
+            }
+            Some(_) => Ok(FindError::Err(ServerError::PermissionDenied)),
+            None => Ok(FindError::Err(ServerError::BadRequest)),
+        }
+    }
+
+    ///returns the id of the conversation between the user and the other user, the conversation is
+    ///created if it does not exist yet. returns bad request if the other user does not exist or is
+    ///the user and permission denied if one of the users blocked the other
+    pub async fn open_dm(
+        dms: &Database,
+        user_handler: &UserHandler,
+        user_id: &ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if user_id == other_id {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let oid = match ObjectId::parse_str(&other_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if user_handler.get_user(oid).await?.is_none() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        if Relationships::new(dms)
+            .is_blocked_between(user_id, other_id)
+            .await?
+        {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        let pair = DmConfig::pair_of(user_id, other_id);
+        loop {
+            if let Some(dm) = conversations.find_one(doc! {"pair": &pair}, None).await? {
+                return Ok(Response::DmOpened(dm.id()));
+            }
+            let dm = DmConfig::new(user_id, other_id);
+            match conversations.insert_one(&dm, None).await {
+                Ok(_) => return Ok(Response::DmOpened(dm.id())),
+                //the conversation was opened concurrently by the other user
+                Err(e) if is_duplicate_key(&e) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    ///returns all conversations of the user, the most recently active first
+    pub async fn list_dms(
+        dms: &Database,
+        user_handler: &UserHandler,
+        user_id: &ID,
+    ) -> Result<Response> {
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        let opts = FindOptions::builder()
+            .sort(doc! {"last_activity": -1, "_id": -1})
+            .build();
+        let mut cursor = conversations
+            .find(doc! {"participants.id": &user_id.id}, opts)
+            .await?;
+        let mut infos = Vec::new();
+        while cursor.advance().await? {
+            let dm = cursor.deserialize_current()?;
+            let oids = dm
+                .participants
+                .iter()
+                .map(|id| ObjectId::parse_str(&id.id))
+                .collect::<Result<Vec<_>, _>>()?;
+            let participants = user_handler.get_users(&oids).await?;
+            infos.push(DmInfo::new(
+                dm.id(),
+                dm.name,
+                dm.owner,
+                participants,
+                dm.created_at,
+                dm.last_activity,
+            ));
+        }
+        Ok(Response::DmList(infos))
+    }
+
+    ///creates a group conversation owned by the user with the other users and returns its id
+    ///returns bad request if there are no other users or one of them does not exist, limit
+    ///exceeded if the group would have more than MAX_GROUP_DM_MEMBERS participants and permission
+    ///denied if the user blocked one of them or was blocked by one of them
+    pub async fn create_group_dm(
+        dms: &Database,
+        user_handler: &UserHandler,
+        user_id: &ID,
+        name: String,
+        others: Vec<ID>,
+    ) -> Result<Response> {
+        if let Err(e) = validate_group_dm_name(&name) {
+            return Ok(Response::Error(ServerError::InvalidName(e)));
+        }
+        let mut participants = vec![user_id.clone()];
+        for other in others {
+            if !participants.contains(&other) {
+                participants.push(other);
+            }
+        }
+        if participants.len() < 2 {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        if participants.len() > MAX_GROUP_DM_MEMBERS {
+            return Ok(Response::Error(ServerError::LimitExceeded));
+        }
+        let oids = match participants
+            .iter()
+            .map(|id| ObjectId::parse_str(&id.id))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(oids) => oids,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if user_handler.get_users(&oids).await?.len() != participants.len() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let relationships = Relationships::new(dms);
+        for other in &participants[1..] {
+            if relationships.is_blocked_between(user_id, other).await? {
+                return Ok(Response::Error(ServerError::PermissionDenied));
+            }
+        }
+
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        let dm = DmConfig::new_group(name, user_id, participants);
+        conversations.insert_one(&dm, None).await?;
+        Ok(Response::DmOpened(dm.id()))
+    }
+
+    ///adds the user to the group conversation if the user that adds them takes part in it
+    ///returns bad request if the added user does not exist or already takes part, limit
+    ///exceeded if the group already has MAX_GROUP_DM_MEMBERS participants and permission denied
+    ///if one of the users blocked the other
+    pub async fn add_dm_participant(
+        dms: &Database,
+        user_handler: &UserHandler,
+        dm_id: &ID,
+        user_id: &ID,
+        added_id: &ID,
+    ) -> Result<Response> {
+        let dm = match Self::load_group(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        let added = match Self::username_of(user_handler, added_id).await? {
+            Some(name) => name,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if Relationships::new(dms)
+            .is_blocked_between(user_id, added_id)
+            .await?
+        {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        let last_slot = format!("participants.{}", MAX_GROUP_DM_MEMBERS - 1);
+        let result = conversations
+            .update_one(
+                doc! {
+                    "_id": dm._id,
+                    "participants.id": {"$ne": &added_id.id},
+                    &last_slot: {"$exists": false},
+                },
+                doc! {"$push": {"participants": to_bson(added_id)?}},
+                None,
+            )
+            .await?;
+        if result.matched_count == 0 {
+            //the user takes part already or the group is full, possibly due to a concurrent update
+            return match Self::load_group(dms, dm_id, user_id).await? {
+                FindError::Ok(dm) if !dm.participants.contains(added_id) => {
+                    Ok(Response::Error(ServerError::LimitExceeded))
+                }
+                FindError::Ok(_) => Ok(Response::Error(ServerError::BadRequest)),
+                FindError::Err(e) => Ok(Response::Error(e)),
+            };
+        }
+        Self::announce(dms, &dm, format!("{} was added to the group", added)).await?;
+        Ok(Response::Success)
+    }
+
+    ///removes a participant from the group conversation if the user is the owner of the group
+    ///returns bad request if the removed user does not take part or is the owner, who has to
+    ///leave the group instead
+    pub async fn remove_dm_participant(
+        dms: &Database,
+        user_handler: &UserHandler,
+        dm_id: &ID,
+        user_id: &ID,
+        removed_id: &ID,
+    ) -> Result<Response> {
+        let dm = match Self::load_group(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if dm.owner.as_ref() != Some(user_id) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+        if removed_id == user_id {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        let result = conversations
+            .update_one(
+                doc! {"_id": dm._id, "participants.id": &removed_id.id},
+                doc! {"$pull": {"participants": {"id": &removed_id.id}}},
+                None,
+            )
+            .await?;
+        if result.modified_count == 0 {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let removed = Self::username_of(user_handler, removed_id)
+            .await?
+            .unwrap_or_else(|| "a user".to_string());
+        Self::announce(dms, &dm, format!("{} was removed from the group", removed)).await?;
+        Ok(Response::Success)
+    }
+
+    ///renames the group conversation if the user takes part in it
+    pub async fn rename_group_dm(
+        dms: &Database,
+        user_handler: &UserHandler,
+        dm_id: &ID,
+        user_id: &ID,
+        name: String,
+    ) -> Result<Response> {
+        if let Err(e) = validate_group_dm_name(&name) {
+            return Ok(Response::Error(ServerError::InvalidName(e)));
+        }
+        let dm = match Self::load_group(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        conversations
+            .update_one(doc! {"_id": dm._id}, doc! {"$set": {"name": &name}}, None)
+            .await?;
+        let renamer = Self::username_of(user_handler, user_id)
+            .await?
+            .unwrap_or_else(|| "a user".to_string());
+        Self::announce(
+            dms,
+            &dm,
+            format!("{} renamed the group to {}", renamer, name),
+        )
+        .await?;
+        Ok(Response::Success)
+    }
+
+    ///removes the user from the group conversation, if the user owns the group the ownership
+    ///passes to the participant that joined first. the group and its messages are deleted when
+    ///the last participant leaves
+    pub async fn leave_group_dm(
+        dms: &Database,
+        user_handler: &UserHandler,
+        dm_id: &ID,
+        user_id: &ID,
+    ) -> Result<Response> {
+        let dm = match Self::load_group(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        let opts = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let left = conversations
+            .find_one_and_update(
+                doc! {"_id": dm._id, "participants.id": &user_id.id},
+                doc! {"$pull": {"participants": {"id": &user_id.id}}},
+                opts,
+            )
+            .await?;
+        let left = match left {
+            Some(left) => left,
+            //the user left concurrently
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+
+        if left.participants.is_empty() {
+            conversations.delete_one(doc! {"_id": dm._id}, None).await?;
+            dm.store(dms).drop().await?;
+            return Ok(Response::Success);
+        }
+        if left.owner.as_ref() == Some(user_id) {
+            conversations
+                .update_one(
+                    doc! {"_id": dm._id, "owner.id": &user_id.id},
+                    doc! {"$set": {"owner": to_bson(&left.participants[0])?}},
+                    None,
+                )
+                .await?;
+        }
+        let leaver = Self::username_of(user_handler, user_id)
+            .await?
+            .unwrap_or_else(|| "a user".to_string());
+        Self::announce(dms, &dm, format!("{} left the group", leaver)).await?;
+        Ok(Response::Success)
+    }
+
+    ///appends a message of the user to the conversation, a message of the conversation can be
+    ///referenced as the message that is replied to. returns invalid message if the content
+    ///violates the default message limits and permission denied if one of the users of a
+    ///conversation between two users blocked the other
+    pub async fn send_dm(
+        dms: &Database,
+        user_handler: &UserHandler,
+        dm_id: &ID,
+        user_id: &ID,
+        content: String,
+        reply_to: Option<ID>,
+    ) -> Result<Response> {
+        let dm = match Self::load_dm(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if let Err(e) = MessageLimits::default().validate(&content) {
+            return Ok(Response::Error(ServerError::InvalidMessage(e)));
+        }
+        if dm.pair.is_some() {
+            let relationships = Relationships::new(dms);
+            for other in dm.participants.iter().filter(|id| *id != user_id) {
+                if relationships.is_blocked_between(user_id, other).await? {
+                    return Ok(Response::Error(ServerError::PermissionDenied));
+                }
+            }
+        }
+        let oid = ObjectId::parse_str(&user_id.id)?;
+        let author = match user_handler.get_user(oid).await? {
+            Some(user) => user.username,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+
+        let store = dm.store(dms);
+        let mut message = Message::from_user(content, author, user_id.clone());
+        if let Some(parent_id) = reply_to {
+            if store.find_message(&parent_id).await?.is_none() {
+                return Ok(Response::Error(ServerError::BadRequest));
+            }
+            message.reply_to = Some(Reply::new(parent_id));
+        }
+        let message = store.append(message).await?;
+
+        let conversations: Collection<DmConfig> = dms.collection("conversations");
+        conversations
+            .update_one(
+                doc! {"_id": dm._id},
+                doc! {"$max": {"last_activity": to_bson(&message.created_at)?}},
+                None,
+            )
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///returns the part of the message history of the conversation matching the query, returns bad
+    ///request if the cursor points to an unknown message
+    pub async fn get_dm_history(
+        dms: &Database,
+        dm_id: &ID,
+        user_id: &ID,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Response> {
+        let dm = match Self::load_dm(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let store = dm.store(dms);
+        match store.query_history(query, order).await? {
+            Some(mut history) => {
+                store.attach_replies(&mut history.messages).await?;
+                store
+                    .attach_reactions(&mut history.messages, user_id)
+                    .await?;
+                Ok(Response::History(history))
+            }
+            None => Ok(Response::Error(ServerError::BadRequest)),
+        }
+    }
+
+    ///replaces the content of the message if the user is its author
+    ///returns bad request if the message does not exist and invalid message if the content
+    ///violates the default message limits
+    pub async fn edit_dm_message(
+        dms: &Database,
+        dm_id: &ID,
+        user_id: &ID,
+        message_id: &ID,
+        content: String,
+    ) -> Result<Response> {
+        let dm = match Self::load_dm(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if let Err(e) = MessageLimits::default().validate(&content) {
+            return Ok(Response::Error(ServerError::InvalidMessage(e)));
+        }
+
+        let store = dm.store(dms);
+        let message = match Self::load_own_message(&store, user_id, message_id).await? {
+            FindError::Ok(message) => message,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        let edited = Message {
+            content,
+            ..message.clone()
+        };
+        if !store.edit_message(&message, &edited).await? {
+            //the message was deleted or edited concurrently
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        Ok(Response::Success)
+    }
+
+    ///deletes the message if the user is its author, there are no moderators in conversations
+    ///returns bad request if the message does not exist
+    pub async fn delete_dm_message(
+        dms: &Database,
+        dm_id: &ID,
+        user_id: &ID,
+        message_id: &ID,
+    ) -> Result<Response> {
+        let dm = match Self::load_dm(dms, dm_id, user_id).await? {
+            FindError::Ok(dm) => dm,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let store = dm.store(dms);
+        if let FindError::Err(e) = Self::load_own_message(&store, user_id, message_id).await? {
+            return Ok(Response::Error(e));
+        }
+        if !store.delete_message(message_id).await? {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        Ok(Response::Success)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::friend_handler::FriendHandler;
+    use crate::mongodb::connect_mongo;
+    use crate::user::create_users;
+    use tokio::test;
+
+    use super::*;
+
+    fn opened(response: Response) -> ID {
+        match response {
+            Response::DmOpened(id) => id,
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    fn history(response: Response) -> Vec<Message> {
+        match response {
+            Response::History(history) => history.messages,
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    async fn test_open_dm() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_DMS1");
+        db.drop(None).await.unwrap();
+        DmHandler::create_indexes(&db).await.unwrap();
+        let (users, ids) = create_users(&client, &db, &["Max", "Moritz", "Malte"]).await;
+
+        let dm = opened(
+            DmHandler::open_dm(&db, &users, &ids[0], &ids[1])
+                .await
+                .unwrap(),
+        );
+        //opening the conversation again or from the other side returns the same conversation
+        let again = opened(
+            DmHandler::open_dm(&db, &users, &ids[1], &ids[0])
+                .await
+                .unwrap(),
+        );
+        let other = opened(
+            DmHandler::open_dm(&db, &users, &ids[0], &ids[2])
+                .await
+                .unwrap(),
+        );
+        let with_self = DmHandler::open_dm(&db, &users, &ids[0], &ids[0])
+            .await
+            .unwrap();
+        let unknown = ID::new(ObjectId::new().to_hex()).unwrap();
+        let with_unknown = DmHandler::open_dm(&db, &users, &ids[0], &unknown)
+            .await
+            .unwrap();
+
+        let listed = match DmHandler::list_dms(&db, &users, &ids[0]).await.unwrap() {
+            Response::DmList(dms) => dms,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let malte = match DmHandler::list_dms(&db, &users, &ids[2]).await.unwrap() {
+            Response::DmList(dms) => dms,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        db.drop(None).await.unwrap();
+        assert_eq!(dm, again);
+        assert_ne!(dm, other);
+        assert!(matches!(
+            with_self,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert!(matches!(
+            with_unknown,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert_eq!(listed.len(), 2);
+        assert_eq!(malte.len(), 1);
+        assert_eq!(malte[0].id, other);
+        assert!(malte[0]
+            .participants
+            .iter()
+            .any(|user| user.username == "Max"));
+    }
+
+    #[test]
+    async fn test_dm_messages() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_DMS2");
+        db.drop(None).await.unwrap();
+        let (users, ids) = create_users(&client, &db, &["Max", "Moritz", "Malte"]).await;
+        let dm = opened(
+            DmHandler::open_dm(&db, &users, &ids[0], &ids[1])
+                .await
+                .unwrap(),
+        );
+        let query = || HistoryQuery::Latest(10);
+
+        assert!(
+            DmHandler::send_dm(&db, &users, &dm, &ids[0], "hi".to_string(), None)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let first = history(
+            DmHandler::get_dm_history(&db, &dm, &ids[1], query(), HistoryOrder::OldestFirst)
+                .await
+                .unwrap(),
+        );
+        assert!(DmHandler::send_dm(
+            &db,
+            &users,
+            &dm,
+            &ids[1],
+            "hey".to_string(),
+            Some(first[0].id.clone())
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let outsider = DmHandler::send_dm(&db, &users, &dm, &ids[2], "me too".to_string(), None)
+            .await
+            .unwrap();
+        let outsider_history =
+            DmHandler::get_dm_history(&db, &dm, &ids[2], query(), HistoryOrder::OldestFirst)
+                .await
+                .unwrap();
+        let empty = DmHandler::send_dm(&db, &users, &dm, &ids[0], " ".to_string(), None)
+            .await
+            .unwrap();
+
+        //only the author can edit or delete a message
+        let foreign_edit =
+            DmHandler::edit_dm_message(&db, &dm, &ids[1], &first[0].id, "bye".to_string())
+                .await
+                .unwrap();
+        assert!(
+            DmHandler::edit_dm_message(&db, &dm, &ids[0], &first[0].id, "hello".to_string())
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let messages = history(
+            DmHandler::get_dm_history(&db, &dm, &ids[0], query(), HistoryOrder::OldestFirst)
+                .await
+                .unwrap(),
+        );
+        let foreign_delete = DmHandler::delete_dm_message(&db, &dm, &ids[0], &messages[1].id)
+            .await
+            .unwrap();
+        assert!(
+            DmHandler::delete_dm_message(&db, &dm, &ids[1], &messages[1].id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let remaining = history(
+            DmHandler::get_dm_history(&db, &dm, &ids[0], query(), HistoryOrder::OldestFirst)
+                .await
+                .unwrap(),
+        );
+        db.drop(None).await.unwrap();
+
+        assert!(matches!(
+            outsider,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            outsider_history,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            empty,
+            Response::Error(ServerError::InvalidMessage(_))
+        ));
+        assert!(matches!(
+            foreign_edit,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            foreign_delete,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "hello");
+        assert!(messages[0].edited_at.is_some());
+        assert_eq!(messages[1].author, "Moritz");
+        assert_eq!(
+            messages[1].reply_to.as_ref().unwrap().message_id,
+            first[0].id
+        );
+        assert_eq!(remaining.len(), 1);
+    }
+
+    #[test]
+    async fn test_group_dm() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_DMS3");
+        db.drop(None).await.unwrap();
+        let names: Vec<String> = (0..MAX_GROUP_DM_MEMBERS + 1)
+            .map(|i| format!("User{}", i))
+            .collect();
+        let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        let (users, ids) = create_users(&client, &db, &names).await;
+        let (owner, member, outsider) = (&ids[0], &ids[1], &ids[2]);
+
+        let alone = DmHandler::create_group_dm(
+            &db,
+            &users,
+            owner,
+            "group".to_string(),
+            vec![owner.clone()],
+        )
+        .await
+        .unwrap();
+        let too_many =
+            DmHandler::create_group_dm(&db, &users, owner, "group".to_string(), ids.clone())
+                .await
+                .unwrap();
+        let group = opened(
+            DmHandler::create_group_dm(
+                &db,
+                &users,
+                owner,
+                "group".to_string(),
+                vec![member.clone()],
+            )
+            .await
+            .unwrap(),
+        );
+        let one_to_one = opened(
+            DmHandler::open_dm(&db, &users, owner, member)
+                .await
+                .unwrap(),
+        );
+        let rename_pair =
+            DmHandler::rename_group_dm(&db, &users, &one_to_one, owner, "pair".to_string())
+                .await
+                .unwrap();
+
+        //any participant can add users and rename the group
+        assert!(
+            DmHandler::add_dm_participant(&db, &users, &group, member, outsider)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let added_twice = DmHandler::add_dm_participant(&db, &users, &group, owner, outsider)
+            .await
+            .unwrap();
+        for id in &ids[3..MAX_GROUP_DM_MEMBERS] {
+            assert!(
+                DmHandler::add_dm_participant(&db, &users, &group, owner, id)
+                    .await
+                    .unwrap()
+                    .succeeded()
+            );
+        }
+        let full =
+            DmHandler::add_dm_participant(&db, &users, &group, owner, &ids[MAX_GROUP_DM_MEMBERS])
+                .await
+                .unwrap();
+        assert!(
+            DmHandler::rename_group_dm(&db, &users, &group, member, "renamed".to_string())
+                .await
+                .unwrap()
+                .succeeded()
+        );
+
+        //only the owner can remove participants
+        let removed_by_member =
+            DmHandler::remove_dm_participant(&db, &users, &group, member, outsider)
+                .await
+                .unwrap();
+        assert!(
+            DmHandler::remove_dm_participant(&db, &users, &group, owner, outsider)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let removed_send =
+            DmHandler::send_dm(&db, &users, &group, outsider, "hi".to_string(), None)
+                .await
+                .unwrap();
+
+        //the ownership passes on when the owner leaves
+        assert!(DmHandler::leave_group_dm(&db, &users, &group, owner)
+            .await
+            .unwrap()
+            .succeeded());
+        let listed = match DmHandler::list_dms(&db, &users, member).await.unwrap() {
+            Response::DmList(dms) => dms,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let messages = history(
+            DmHandler::get_dm_history(
+                &db,
+                &group,
+                member,
+                HistoryQuery::Latest(10),
+                HistoryOrder::OldestFirst,
+            )
+            .await
+            .unwrap(),
+        );
+
+        //the group is deleted when the last participant leaves
+        for id in &ids[1..MAX_GROUP_DM_MEMBERS] {
+            if id != outsider {
+                assert!(DmHandler::leave_group_dm(&db, &users, &group, id)
+                    .await
+                    .unwrap()
+                    .succeeded());
+            }
+        }
+        let conversations: Collection<DmConfig> = db.collection("conversations");
+        let deleted = conversations
+            .find_one(doc! {"_id": ObjectId::parse_str(&group.id).unwrap()}, None)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+
+        assert!(matches!(alone, Response::Error(ServerError::BadRequest)));
+        assert!(matches!(
+            too_many,
+            Response::Error(ServerError::LimitExceeded)
+        ));
+        assert!(matches!(
+            rename_pair,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert!(matches!(
+            added_twice,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert!(matches!(full, Response::Error(ServerError::LimitExceeded)));
+        assert!(matches!(
+            removed_by_member,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            removed_send,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        let info = listed.iter().find(|dm| dm.id == group).unwrap();
+        assert_eq!(info.name.as_deref(), Some("renamed"));
+        assert_eq!(info.owner.as_ref(), Some(member));
+        assert_eq!(info.participants.len(), MAX_GROUP_DM_MEMBERS - 2);
+        assert!(messages.iter().all(|message| message.author_id.is_none()));
+        assert_eq!(messages.last().unwrap().content, "User0 left the group");
+        assert!(deleted.is_none());
+    }
+
+    #[test]
+    async fn test_blocked_dm() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_DMS4");
+        db.drop(None).await.unwrap();
+        let (users, ids) = create_users(&client, &db, &["Max", "Moritz", "Malte"]).await;
+        let (max, moritz, malte) = (&ids[0], &ids[1], &ids[2]);
+        let dm = opened(DmHandler::open_dm(&db, &users, max, moritz).await.unwrap());
+        let group = opened(
+            DmHandler::create_group_dm(&db, &users, max, "group".to_string(), vec![malte.clone()])
+                .await
+                .unwrap(),
+        );
+
+        assert!(FriendHandler::block_user(&db, &users, max, moritz)
+            .await
+            .unwrap()
+            .succeeded());
+        let send = DmHandler::send_dm(&db, &users, &dm, moritz, "hi".to_string(), None)
+            .await
+            .unwrap();
+        let reopen = DmHandler::open_dm(&db, &users, moritz, max).await.unwrap();
+        let add = DmHandler::add_dm_participant(&db, &users, &group, max, moritz)
+            .await
+            .unwrap();
+        let create =
+            DmHandler::create_group_dm(&db, &users, moritz, "group".to_string(), vec![max.clone()])
+                .await
+                .unwrap();
+        db.drop(None).await.unwrap();
+
+        assert!(matches!(
+            send,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            reopen,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            add,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            create,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use common::{
+    events::{Event, EventKind, MAX_EVENTS_PER_REQUEST},
+    id::ID,
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    options::{FindOptions, IndexOptions},
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+///time after which events are removed from the feed, clients that don't ask for events for
+///longer miss them
+pub const EVENT_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+///an event for a user, stored in the events collection of the users database
+#[derive(Serialize, Deserialize, Debug)]
+struct EventEntry {
+    _id: ObjectId,
+    user_id: ID,
+    created_at: SystemTime,
+    ///the entry is removed by the ttl index after this time
+    expires: DateTime,
+    kind: EventKind,
+}
+
+///the events of all users, oldest first, clients ask for the events after the last event they
+///received
+#[derive(Clone)]
+pub struct EventFeed {
+    events: Collection<EventEntry>,
+}
+
+impl EventFeed {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            events: database.collection("events"),
+        }
+    }
+
+    ///creates the ttl index that removes events after EVENT_RETENTION
+    pub async fn create_indexes(&self) -> Result<()> {
+        //creating an index that already exists has no effect
+        let index = IndexModel::builder()
+            .keys(doc! {"expires": 1})
+            .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+            .build();
+        self.events.create_index(index, None).await?;
+        Ok(())
+    }
+
+    ///adds the event to the feed of all users
+    pub async fn push(&self, users: &[ID], kind: &EventKind) -> Result<()> {
+        if users.is_empty() {
+            return Ok(());
+        }
+        let now = SystemTime::now();
+        let expires = DateTime::from_system_time(now + EVENT_RETENTION);
+        let entries = users.iter().map(|user_id| EventEntry {
+            _id: ObjectId::new(),
+            user_id: user_id.clone(),
+            created_at: now,
+            expires,
+            kind: kind.clone(),
+        });
+        self.events.insert_many(entries, None).await?;
+        Ok(())
+    }
+
+    ///returns up to MAX_EVENTS_PER_REQUEST events of the user that happened after the event, all
+    ///events of the user if after is None
+    pub async fn after(&self, user_id: &ID, after: Option<ObjectId>) -> Result<Vec<Event>> {
+        let mut filter = doc! {"user_id.id": &user_id.id};
+        if let Some(after) = after {
+            filter.insert("_id", doc! {"$gt": after});
+        }
+        //object ids start with their creation time
+        let opts = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .limit(MAX_EVENTS_PER_REQUEST as i64)
+            .build();
+        let mut cursor = self.events.find(filter, opts).await?;
+        let mut events = Vec::new();
+        while cursor.advance().await? {
+            let entry = cursor.deserialize_current()?;
+            events.push(Event {
+                id: ID::new(entry._id.to_hex()).ok_or(anyhow!("invalid event id"))?,
+                created_at: entry.created_at,
+                kind: entry.kind,
+            });
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use common::user::User;
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_event_feed() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_EVENTS1");
+        db.drop(None).await.unwrap();
+        let feed = EventFeed::new(&db);
+        feed.create_indexes().await.unwrap();
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let moritz = ID::new("123123123123123123123124".to_string()).unwrap();
+        let changed =
+            |name: &str| EventKind::PresenceChanged(User::new(max.clone(), name.to_string(), true));
+
+        feed.push(&[max.clone(), moritz.clone()], &changed("first"))
+            .await
+            .unwrap();
+        feed.push(std::slice::from_ref(&moritz), &changed("second"))
+            .await
+            .unwrap();
+        let all = feed.after(&moritz, None).await.unwrap();
+        let last = ObjectId::parse_str(&all[0].id.id).unwrap();
+        let newer = feed.after(&moritz, Some(last)).await.unwrap();
+        let max_events = feed.after(&max, None).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(all.len(), 2);
+        assert!(
+            matches!(&all[0].kind, EventKind::PresenceChanged(user) if user.username == "first")
+        );
+        assert_eq!(newer.len(), 1);
+        assert!(
+            matches!(&newer[0].kind, EventKind::PresenceChanged(user) if user.username == "second")
+        );
+        assert_eq!(max_events.len(), 1);
+    }
+}
@@ -0,0 +1,489 @@
+use anyhow::Result;
+use common::{
+    error::ServerError,
+    id::ID,
+    messages::Response,
+    user::{Relationship, RelationshipKind},
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson},
+    options::{IndexOptions, UpdateOptions},
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+use crate::user::UserHandler;
+
+///the relationship of a user to another user as seen by the user, a friendship or a pending
+///request is stored once for each side, a block only for the user that blocked
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RelationshipEntry {
+    pub user_id: ID,
+    pub other_id: ID,
+    pub kind: RelationshipKind,
+    ///time the relationship was last changed
+    pub since: SystemTime,
+}
+
+///the relationships between users, stored in the relationships collection of the dm database
+#[derive(Clone)]
+pub struct Relationships {
+    entries: Collection<RelationshipEntry>,
+}
+
+pub struct FriendHandler;
+
+impl Relationships {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            entries: database.collection("relationships"),
+        }
+    }
+
+    ///creates the index that keeps one relationship per user and other user
+    pub async fn create_indexes(&self) -> Result<()> {
+        //creating an index that already exists has no effect
+        let index = IndexModel::builder()
+            .keys(doc! {"user_id.id": 1, "other_id.id": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.entries.create_index(index, None).await?;
+        Ok(())
+    }
+
+    ///returns the relationship of the user to the other user
+    pub async fn kind(&self, user_id: &ID, other_id: &ID) -> Result<Option<RelationshipKind>> {
+        let entry = self
+            .entries
+            .find_one(
+                doc! {"user_id.id": &user_id.id, "other_id.id": &other_id.id},
+                None,
+            )
+            .await?;
+        Ok(entry.map(|entry| entry.kind))
+    }
+
+    ///returns true if one of the users blocked the other
+    pub async fn is_blocked_between(&self, user_id: &ID, other_id: &ID) -> Result<bool> {
+        Ok(
+            self.kind(user_id, other_id).await? == Some(RelationshipKind::Blocked)
+                || self.kind(other_id, user_id).await? == Some(RelationshipKind::Blocked),
+        )
+    }
+
+    ///sets the relationship of the user to the other user
+    async fn set(&self, user_id: &ID, other_id: &ID, kind: RelationshipKind) -> Result<()> {
+        let opts = UpdateOptions::builder().upsert(true).build();
+        self.entries
+            .update_one(
+                doc! {"user_id.id": &user_id.id, "other_id.id": &other_id.id},
+                doc! {
+                    "$set": {
+                        "user_id": to_bson(user_id)?,
+                        "other_id": to_bson(other_id)?,
+                        "kind": to_bson(&kind)?,
+                        "since": to_bson(&SystemTime::now())?,
+                    }
+                },
+                opts,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///removes the relationship of the user to the other user if it is of the kind
+    async fn remove(&self, user_id: &ID, other_id: &ID, kind: RelationshipKind) -> Result<bool> {
+        let result = self
+            .entries
+            .delete_one(
+                doc! {
+                    "user_id.id": &user_id.id,
+                    "other_id.id": &other_id.id,
+                    "kind": to_bson(&kind)?,
+                },
+                None,
+            )
+            .await?;
+        Ok(result.deleted_count == 1)
+    }
+
+    ///returns all relationships of the user
+    pub async fn list(&self, user_id: &ID) -> Result<Vec<RelationshipEntry>> {
+        let mut cursor = self
+            .entries
+            .find(doc! {"user_id.id": &user_id.id}, None)
+            .await?;
+        let mut entries = Vec::new();
+        while cursor.advance().await? {
+            entries.push(cursor.deserialize_current()?);
+        }
+        Ok(entries)
+    }
+}
+
+impl FriendHandler {
+    ///sends a friend request to the other user, if the other user already sent a request to the
+    ///user the request is accepted instead. returns bad request if the other user does not exist,
+    ///is the user or a request or friendship exists already and permission denied if one of the
+    ///users blocked the other
+    pub async fn add_friend(
+        dms: &Database,
+        user_handler: &UserHandler,
+        user_id: &ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if user_id == other_id {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let oid = match ObjectId::parse_str(&other_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if user_handler.get_user(oid).await?.is_none() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let relationships = Relationships::new(dms);
+        if relationships.is_blocked_between(user_id, other_id).await? {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+        match relationships.kind(user_id, other_id).await? {
+            Some(RelationshipKind::IncomingRequest) => {
+                Self::accept_friend(dms, user_id, other_id).await
+            }
+            Some(_) => Ok(Response::Error(ServerError::BadRequest)),
+            None => {
+                relationships
+                    .set(user_id, other_id, RelationshipKind::OutgoingRequest)
+                    .await?;
+                relationships
+                    .set(other_id, user_id, RelationshipKind::IncomingRequest)
+                    .await?;
+                Ok(Response::Success)
+            }
+        }
+    }
+
+    ///accepts the friend request the other user sent to the user
+    ///returns bad request if there is no such request
+    pub async fn accept_friend(dms: &Database, user_id: &ID, other_id: &ID) -> Result<Response> {
+        let relationships = Relationships::new(dms);
+        if relationships.kind(user_id, other_id).await? != Some(RelationshipKind::IncomingRequest) {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        relationships
+            .set(user_id, other_id, RelationshipKind::Friend)
+            .await?;
+        relationships
+            .set(other_id, user_id, RelationshipKind::Friend)
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///declines the friend request the other user sent to the user
+    ///returns bad request if there is no such request
+    pub async fn decline_friend(dms: &Database, user_id: &ID, other_id: &ID) -> Result<Response> {
+        let relationships = Relationships::new(dms);
+        if !relationships
+            .remove(user_id, other_id, RelationshipKind::IncomingRequest)
+            .await?
+        {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        relationships
+            .remove(other_id, user_id, RelationshipKind::OutgoingRequest)
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///cancels the friend request the user sent to the other user
+    ///returns bad request if there is no such request
+    pub async fn cancel_friend_request(
+        dms: &Database,
+        user_id: &ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        let relationships = Relationships::new(dms);
+        if !relationships
+            .remove(user_id, other_id, RelationshipKind::OutgoingRequest)
+            .await?
+        {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        relationships
+            .remove(other_id, user_id, RelationshipKind::IncomingRequest)
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///ends the friendship of the users, returns bad request if they are not friends
+    pub async fn remove_friend(dms: &Database, user_id: &ID, other_id: &ID) -> Result<Response> {
+        let relationships = Relationships::new(dms);
+        if !relationships
+            .remove(user_id, other_id, RelationshipKind::Friend)
+            .await?
+        {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        relationships
+            .remove(other_id, user_id, RelationshipKind::Friend)
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///blocks the other user, a friendship or pending requests between the users are removed
+    ///blocked users can't send friend requests or direct messages to the user
+    pub async fn block_user(
+        dms: &Database,
+        user_handler: &UserHandler,
+        user_id: &ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if user_id == other_id {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let oid = match ObjectId::parse_str(&other_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if user_handler.get_user(oid).await?.is_none() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let relationships = Relationships::new(dms);
+        relationships
+            .set(user_id, other_id, RelationshipKind::Blocked)
+            .await?;
+        //a block of the other user stays in place
+        for kind in [
+            RelationshipKind::Friend,
+            RelationshipKind::IncomingRequest,
+            RelationshipKind::OutgoingRequest,
+        ] {
+            relationships.remove(other_id, user_id, kind).await?;
+        }
+        Ok(Response::Success)
+    }
+
+    ///removes the block of the other user, returns bad request if the user did not block them
+    pub async fn unblock_user(dms: &Database, user_id: &ID, other_id: &ID) -> Result<Response> {
+        let relationships = Relationships::new(dms);
+        if !relationships
+            .remove(user_id, other_id, RelationshipKind::Blocked)
+            .await?
+        {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        Ok(Response::Success)
+    }
+
+    ///returns the friends, pending requests and blocked users of the user ordered by the kind of
+    ///the relationship, online users first and then by their username
+    pub async fn get_friends(
+        dms: &Database,
+        user_handler: &UserHandler,
+        user_id: &ID,
+    ) -> Result<Response> {
+        let entries = Relationships::new(dms).list(user_id).await?;
+        let oids = entries
+            .iter()
+            .map(|entry| ObjectId::parse_str(&entry.other_id.id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut relationships: Vec<Relationship> = user_handler
+            .get_users(&oids)
+            .await?
+            .into_iter()
+            .filter_map(|user| {
+                let entry = entries.iter().find(|entry| entry.other_id == user.id)?;
+                Some(Relationship::new(user, entry.kind))
+            })
+            .collect();
+        relationships.sort_by(|a, b| {
+            a.kind
+                .cmp(&b.kind)
+                .then_with(|| b.user.is_online.cmp(&a.user.is_online))
+                .then_with(|| {
+                    a.user
+                        .username
+                        .to_lowercase()
+                        .cmp(&b.user.username.to_lowercase())
+                })
+        });
+        Ok(Response::Relationships(relationships))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use crate::user::create_users;
+    use tokio::test;
+
+    use super::*;
+
+    fn relationships(response: Response) -> Vec<(String, RelationshipKind)> {
+        match response {
+            Response::Relationships(relationships) => relationships
+                .into_iter()
+                .map(|r| (r.user.username, r.kind))
+                .collect(),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    async fn test_friend_requests() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_FRIENDS1");
+        db.drop(None).await.unwrap();
+        Relationships::new(&db).create_indexes().await.unwrap();
+        let (users, ids) = create_users(&client, &db, &["Max", "Moritz", "Malte"]).await;
+        let (max, moritz, malte) = (&ids[0], &ids[1], &ids[2]);
+
+        assert!(FriendHandler::add_friend(&db, &users, max, moritz)
+            .await
+            .unwrap()
+            .succeeded());
+        let twice = FriendHandler::add_friend(&db, &users, max, moritz)
+            .await
+            .unwrap();
+        let moritz_pending = relationships(
+            FriendHandler::get_friends(&db, &users, moritz)
+                .await
+                .unwrap(),
+        );
+        assert!(FriendHandler::accept_friend(&db, moritz, max)
+            .await
+            .unwrap()
+            .succeeded());
+        let accepted_twice = FriendHandler::accept_friend(&db, moritz, max)
+            .await
+            .unwrap();
+
+        //a request to a user that sent a request is accepted
+        assert!(FriendHandler::add_friend(&db, &users, malte, max)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::add_friend(&db, &users, max, malte)
+            .await
+            .unwrap()
+            .succeeded());
+        let max_friends =
+            relationships(FriendHandler::get_friends(&db, &users, max).await.unwrap());
+
+        assert!(FriendHandler::remove_friend(&db, max, malte)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::add_friend(&db, &users, malte, max)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::decline_friend(&db, max, malte)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::add_friend(&db, &users, malte, max)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::cancel_friend_request(&db, malte, max)
+            .await
+            .unwrap()
+            .succeeded());
+        let declined = FriendHandler::decline_friend(&db, max, malte)
+            .await
+            .unwrap();
+        let malte_friends = relationships(
+            FriendHandler::get_friends(&db, &users, malte)
+                .await
+                .unwrap(),
+        );
+        db.drop(None).await.unwrap();
+
+        assert!(matches!(twice, Response::Error(ServerError::BadRequest)));
+        assert_eq!(
+            moritz_pending,
+            vec![("Max".to_string(), RelationshipKind::IncomingRequest)]
+        );
+        assert!(matches!(
+            accepted_twice,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert_eq!(
+            max_friends,
+            vec![
+                ("Malte".to_string(), RelationshipKind::Friend),
+                ("Moritz".to_string(), RelationshipKind::Friend)
+            ]
+        );
+        assert!(matches!(declined, Response::Error(ServerError::BadRequest)));
+        assert!(malte_friends.is_empty());
+    }
+
+    #[test]
+    async fn test_block_user() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_FRIENDS2");
+        db.drop(None).await.unwrap();
+        Relationships::new(&db).create_indexes().await.unwrap();
+        let (users, ids) = create_users(&client, &db, &["Max", "Moritz"]).await;
+        let (max, moritz) = (&ids[0], &ids[1]);
+
+        assert!(FriendHandler::add_friend(&db, &users, max, moritz)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::accept_friend(&db, moritz, max)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::block_user(&db, &users, max, moritz)
+            .await
+            .unwrap()
+            .succeeded());
+        let max_view = relationships(FriendHandler::get_friends(&db, &users, max).await.unwrap());
+        let moritz_view = relationships(
+            FriendHandler::get_friends(&db, &users, moritz)
+                .await
+                .unwrap(),
+        );
+        let blocked_request = FriendHandler::add_friend(&db, &users, moritz, max)
+            .await
+            .unwrap();
+        let blocker_request = FriendHandler::add_friend(&db, &users, max, moritz)
+            .await
+            .unwrap();
+
+        assert!(FriendHandler::unblock_user(&db, max, moritz)
+            .await
+            .unwrap()
+            .succeeded());
+        let unblocked_request = FriendHandler::add_friend(&db, &users, moritz, max)
+            .await
+            .unwrap();
+        let unblocked_twice = FriendHandler::unblock_user(&db, max, moritz).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(
+            max_view,
+            vec![("Moritz".to_string(), RelationshipKind::Blocked)]
+        );
+        assert!(moritz_view.is_empty());
+        assert!(matches!(
+            blocked_request,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            blocker_request,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(unblocked_request.succeeded());
+        assert!(matches!(
+            unblocked_twice,
+            Response::Error(ServerError::BadRequest)
+        ));
+    }
+}
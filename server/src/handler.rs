@@ -1,21 +1,47 @@
 use anyhow::Result;
-use common::{id::ID, messages::Response};
+use common::{
+    channel::ChannelUpdate,
+    id::ID,
+    messages::{HistoryOrder, HistoryQuery, Response, SearchFilters},
+    server::ServerUpdate,
+    user::{CustomStatus, Member, Presence, ProfileImage, ProfileUpdate},
+};
 use mongodb::{bson::oid::ObjectId, Client};
+use tokio::sync::broadcast;
 
-use crate::{server_handler::ServerHandler, session::SessionHandler, user::UserHandler};
+use crate::{
+    attachments::{AttachmentHandler, ATTACHMENT_DATABASE},
+    blob_store::BlobStore,
+    dm_handler::{DmHandler, DM_DATABASE},
+    friend_handler::FriendHandler,
+    presence::PresenceHandler,
+    profile::ProfileHandler,
+    server_handler::ServerHandler,
+    session::SessionHandler,
+    typing::{TypingChange, TypingTracker},
+    user::{FindError, UserHandler},
+};
 
 #[derive(Clone)]
 pub struct Handler {
     pub session_handler: SessionHandler,
     pub user_handler: UserHandler,
+    pub typing: TypingTracker,
+    pub blobs: BlobStore,
 }
 
 //authentication
 impl Handler {
-    pub fn new(session_handler: SessionHandler, user_handler: UserHandler) -> Self {
+    pub fn new(
+        session_handler: SessionHandler,
+        user_handler: UserHandler,
+        blobs: BlobStore,
+    ) -> Self {
         Self {
             session_handler,
             user_handler,
+            typing: TypingTracker::new(),
+            blobs,
         }
     }
 
@@ -57,13 +83,18 @@ impl Handler {
         Ok(Response::Success)
     }
 
+    ///every request of an authenticated user counts as activity for the idle detection
     async fn is_authenticated(&self, user_id: ID) -> Result<bool> {
         let oid = ObjectId::parse_str(user_id.id)?;
-        Ok(self
+        let active = self
             .session_handler
             .check_session_active(oid)
             .await?
-            .succeeded())
+            .succeeded();
+        if active {
+            self.user_handler.touch(oid).await?;
+        }
+        Ok(active)
     }
 }
 
@@ -80,10 +111,10 @@ impl Handler {
             let oid = ObjectId::parse_str(user_id.id)?;
             return self.session_handler.check_session_active(oid).await;
         }
-        ServerHandler::new_server(user_id, mongo_client, name).await
+        ServerHandler::new_server(user_id, mongo_client, &self.user_handler, name).await
     }
 
-    ///checks authentication and deletes a nicord server if the user has the required priviledges
+    ///checks authentication and deletes a nicord server if the user is the owner of the server
     pub async fn delete_server(
         &self,
         mongo_client: &Client,
@@ -98,13 +129,179 @@ impl Handler {
         ServerHandler::delete_server(&user_id, mongo_client, server_id).await
     }
 
+    ///checks authentication and returns the settings of the server if the user is a user of the
+    ///server
+    pub async fn get_server(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_server(mongo_client, server_id, &user_id).await
+    }
+
+    ///checks authentication and updates the server settings if the user has the required
+    ///priviledges
+    pub async fn update_server(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        update: ServerUpdate,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::update_server(&user_id, mongo_client, server_id, update).await
+    }
+
+    ///checks authentication and transfers the ownership of the server if the user is its owner
+    pub async fn transfer_ownership(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        new_owner: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::transfer_ownership(&user_id, mongo_client, server_id, new_owner).await
+    }
+
+    ///checks authentication and returns a page of the members of the server if the user is a user
+    ///of the server. Members are sorted by their highest role and then by their name
+    pub async fn get_members(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        page: u32,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let (entries, has_more) =
+            match ServerHandler::get_members(mongo_client, server_id, &user_id, page).await? {
+                FindError::Ok(page) => page,
+                FindError::Err(e) => return Ok(Response::Error(e)),
+            };
+
+        //only the users of the page are loaded, the entries are already in the order of the list
+        let oids = entries
+            .iter()
+            .map(|entry| ObjectId::parse_str(&entry.user_id.id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let users = self.user_handler.get_users(&oids).await?;
+        let members = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let user = users.iter().find(|user| user.id == entry.user_id)?;
+                Some(Member::new(user.clone(), entry.roles, entry.nickname))
+            })
+            .collect();
+        Ok(Response::MemberList(members, has_more))
+    }
+
+    ///checks authentication and sets the nickname of the user on the server
+    pub async fn set_nickname(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        nickname: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::set_nickname(
+            &user_id,
+            mongo_client,
+            &self.user_handler,
+            server_id,
+            nickname,
+        )
+        .await
+    }
+
+    ///returns the custom emoji of the server if the user is authenticated and a user of the
+    ///server
+    pub async fn list_emoji(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::list_emoji(mongo_client, server_id, &user_id).await
+    }
+
+    ///adds a custom emoji to the server if the user is authenticated and may manage emoji
+    pub async fn create_emoji(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        name: String,
+        image: Vec<u8>,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::create_emoji(mongo_client, &self.blobs, server_id, &user_id, name, image)
+            .await
+    }
+
+    ///removes a custom emoji from the server if the user is authenticated and may manage emoji
+    pub async fn delete_emoji(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        emoji_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::delete_emoji(mongo_client, server_id, &user_id, emoji_id).await
+    }
+
+    ///returns the image of a custom emoji if the user is authenticated and a user of the server
+    pub async fn get_emoji_image(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        emoji_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_emoji_image(mongo_client, &self.blobs, server_id, &user_id, emoji_id)
+            .await
+    }
+
     ///creates a new channel(Collection) on a server if the user is authenticated and has the
     ///required priviledges
     pub async fn new_channel(
         &self,
         mongo_client: &Client,
         user_id: ID,
-        name: &String,
+        name: &str,
         server_id: &ID,
     ) -> Result<Response> {
         if !self.is_authenticated(user_id.clone()).await? {
@@ -120,7 +317,7 @@ impl Handler {
         &self,
         mongo_client: &Client,
         user_id: ID,
-        name: &String,
+        name: &str,
         server_id: &ID,
     ) -> Result<Response> {
         if !self.is_authenticated(user_id.clone()).await? {
@@ -145,6 +342,42 @@ impl Handler {
         ServerHandler::get_channels(mongo_client, server_id, &user_id).await
     }
 
+    ///renames the channel or changes its topic if the user is authenticated and has the required
+    ///priviledges
+    pub async fn update_channel(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        name: &str,
+        server_id: &ID,
+        update: ChannelUpdate,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::update_channel(&user_id, mongo_client, name, server_id, update).await
+    }
+
+    ///moves the channel to a new position and category if the user is authenticated and has the
+    ///required priviledges
+    pub async fn move_channel(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        name: &String,
+        server_id: &ID,
+        position: u32,
+        category: Option<String>,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id)?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::move_channel(&user_id, mongo_client, name, server_id, position, category)
+            .await
+    }
+
     ///send a message to the channel if the user is authenticated and has the required priviledges
     pub async fn send_message(
         &self,
@@ -153,30 +386,1001 @@ impl Handler {
         server_id: &ID,
         channel_name: String,
         message_content: String,
+        reply_to: Option<ID>,
     ) -> Result<Response> {
-        let oid = ObjectId::parse_str(user_id.id.clone())?;
         if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
             return self.session_handler.check_session_active(oid).await;
         }
-        let username = self.user_handler.get_user(oid).await?.expect("checked above").username;
-        ServerHandler::send_message(mongo_client,  server_id, &channel_name, &user_id, message_content, username).await
+        ServerHandler::send_message(
+            mongo_client,
+            &self.user_handler,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_content,
+            reply_to,
+        )
+        .await
     }
 
-    ///get a block of messages from a channel if the user is authenticated and has the required
-    ///priviledges. The block is uniquely identified by its id
-    pub async fn get_message_block(
-        &self, 
+    ///send a message with uploaded attachments to the channel if the user is authenticated and
+    ///has the required priviledges
+    pub async fn send_attachments(
+        &self,
         mongo_client: &Client,
         user_id: ID,
         server_id: &ID,
         channel_name: String,
-        block_id: u32,
+        message_content: String,
+        attachment_ids: Vec<ID>,
     ) -> Result<Response> {
         if !self.is_authenticated(user_id.clone()).await? {
             let oid = ObjectId::parse_str(user_id.id.clone())?;
             return self.session_handler.check_session_active(oid).await;
         }
-        ServerHandler::get_block_content(mongo_client, server_id, &channel_name, &user_id, block_id).await
+        ServerHandler::send_attachments(
+            mongo_client,
+            &self.user_handler,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_content,
+            attachment_ids,
+        )
+        .await
+    }
+
+    ///download a chunk of an attachment of a message or of its thumbnail if the user is
+    ///authenticated and can read the channel
+    #[allow(clippy::too_many_arguments)]
+    pub async fn download_attachment(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: &str,
+        message_id: &ID,
+        attachment_id: &ID,
+        thumbnail: Option<u32>,
+        offset: u64,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::download_attachment(
+            mongo_client,
+            &self.blobs,
+            server_id,
+            channel_name,
+            &user_id,
+            message_id,
+            attachment_id,
+            thumbnail,
+            offset,
+        )
+        .await
+    }
+
+    ///create a thread that hangs off a message if the user is authenticated and can read the
+    ///channel
+    pub async fn create_thread(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+        name: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::create_thread(
+            mongo_client,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_id,
+            name,
+        )
+        .await
+    }
+
+    ///get the threads of a channel if the user is authenticated and can read the channel
+    pub async fn get_threads(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_threads(mongo_client, server_id, &channel_name, &user_id).await
+    }
+
+    ///send a message to a thread if the user is authenticated and has the required priviledges
+    pub async fn send_thread_message(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        thread_id: &ID,
+        message_content: String,
+        reply_to: Option<ID>,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::send_thread_message(
+            mongo_client,
+            &self.user_handler,
+            server_id,
+            thread_id,
+            &user_id,
+            message_content,
+            reply_to,
+        )
+        .await
+    }
+
+    ///pin a message if the user is authenticated and has the required priviledges
+    pub async fn pin_message(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::pin_message(
+            mongo_client,
+            &self.user_handler,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_id,
+        )
+        .await
+    }
+
+    ///unpin a message if the user is authenticated and has the required priviledges
+    pub async fn unpin_message(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::unpin_message(mongo_client, server_id, &channel_name, &user_id, message_id)
+            .await
+    }
+
+    ///get the pinned messages of a channel if the user is authenticated and can read the channel
+    pub async fn get_pins(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_pins(mongo_client, server_id, &channel_name, &user_id).await
+    }
+
+    ///search the messages of a server if the user is authenticated and is on the server
+    pub async fn search_messages(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        query: String,
+        filters: SearchFilters,
+        page: u32,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::search_messages(mongo_client, server_id, &user_id, &query, filters, page)
+            .await
+    }
+
+    ///get the latest messages the user was mentioned in on a server if the user is authenticated
+    pub async fn get_mentions(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        limit: u32,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_mentions(mongo_client, server_id, &user_id, limit).await
+    }
+
+    ///mark the messages of a channel up to the message as read if the user is authenticated and
+    ///can read the channel
+    pub async fn ack(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: &str,
+        message_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::ack(mongo_client, server_id, channel_name, &user_id, message_id).await
+    }
+
+    ///get the unread messages and mentions of every channel of a server if the user is
+    ///authenticated
+    pub async fn get_unread(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_unread(mongo_client, server_id, &user_id).await
+    }
+
+    ///get a part of the message history of a thread if the user is authenticated and has the
+    ///required priviledges
+    pub async fn get_thread_history(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        thread_id: &ID,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_thread_history(
+            mongo_client,
+            server_id,
+            thread_id,
+            &user_id,
+            query,
+            order,
+        )
+        .await
+    }
+
+    ///archive or unarchive a thread if the user is authenticated and has the required priviledges
+    pub async fn archive_thread(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        thread_id: &ID,
+        archived: bool,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::archive_thread(mongo_client, server_id, thread_id, &user_id, archived).await
+    }
+
+    ///get a part of the message history of a channel if the user is authenticated and has the
+    ///required priviledges
+    pub async fn get_history(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_history(
+            mongo_client,
+            server_id,
+            &channel_name,
+            &user_id,
+            query,
+            order,
+        )
+        .await
+    }
+
+    ///edit a message if the user is authenticated and is the author of the message
+    pub async fn edit_message(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+        content: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::edit_message(
+            mongo_client,
+            &self.user_handler,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_id,
+            content,
+        )
+        .await
+    }
+
+    ///delete a message if the user is authenticated and is the author of the message or has the
+    ///required priviledges
+    pub async fn delete_message(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::delete_message(mongo_client, server_id, &channel_name, &user_id, message_id)
+            .await
+    }
+
+    ///get the previous versions of a message if the user is authenticated and has the required
+    ///priviledges
+    pub async fn get_edit_history(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_edit_history(
+            mongo_client,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_id,
+        )
+        .await
+    }
+
+    ///add a reaction to a message if the user is authenticated and can read the channel
+    pub async fn add_reaction(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+        emoji: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::add_reaction(
+            mongo_client,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_id,
+            &emoji,
+        )
+        .await
+    }
+
+    ///remove a reaction of the user from a message if the user is authenticated
+    pub async fn remove_reaction(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        message_id: &ID,
+        emoji: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::remove_reaction(
+            mongo_client,
+            server_id,
+            &channel_name,
+            &user_id,
+            message_id,
+            &emoji,
+        )
+        .await
+    }
+
+    ///get a block of messages from a channel if the user is authenticated and has the required
+    ///priviledges. The block is uniquely identified by its id
+    pub async fn get_message_block(
+        &self, 
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+        block_id: u32,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ServerHandler::get_block_content(mongo_client, server_id, &channel_name, &user_id, block_id).await
+    }
+}
+
+//direct messages
+impl Handler {
+    ///open the conversation with the other user if the user is authenticated
+    pub async fn open_dm(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::open_dm(&dms, &self.user_handler, &user_id, other_id).await
+    }
+
+    ///get the conversations of the user if the user is authenticated
+    pub async fn list_dms(&self, mongo_client: &Client, user_id: ID) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::list_dms(&dms, &self.user_handler, &user_id).await
+    }
+
+    ///append a message to a conversation if the user is authenticated and takes part in it
+    pub async fn send_dm(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+        message_content: String,
+        reply_to: Option<ID>,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::send_dm(
+            &dms,
+            &self.user_handler,
+            dm_id,
+            &user_id,
+            message_content,
+            reply_to,
+        )
+        .await
+    }
+
+    ///get a part of the message history of a conversation if the user is authenticated and takes
+    ///part in it
+    pub async fn get_dm_history(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::get_dm_history(&dms, dm_id, &user_id, query, order).await
+    }
+
+    ///edit a message of a conversation if the user is authenticated and is the author of the
+    ///message
+    pub async fn edit_dm_message(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+        message_id: &ID,
+        content: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::edit_dm_message(&dms, dm_id, &user_id, message_id, content).await
+    }
+
+    ///delete a message of a conversation if the user is authenticated and is the author of the
+    ///message
+    pub async fn delete_dm_message(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+        message_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::delete_dm_message(&dms, dm_id, &user_id, message_id).await
+    }
+
+    ///create a group conversation with the other users if the user is authenticated
+    pub async fn create_group_dm(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        name: String,
+        others: Vec<ID>,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::create_group_dm(&dms, &self.user_handler, &user_id, name, others).await
+    }
+
+    ///add a user to a group conversation if the user is authenticated and takes part in it
+    pub async fn add_dm_participant(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+        added_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::add_dm_participant(&dms, &self.user_handler, dm_id, &user_id, added_id).await
+    }
+
+    ///remove a user from a group conversation if the user is authenticated and owns the group
+    pub async fn remove_dm_participant(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+        removed_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::remove_dm_participant(&dms, &self.user_handler, dm_id, &user_id, removed_id)
+            .await
+    }
+
+    ///rename a group conversation if the user is authenticated and takes part in it
+    pub async fn rename_group_dm(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+        name: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::rename_group_dm(&dms, &self.user_handler, dm_id, &user_id, name).await
+    }
+
+    ///leave a group conversation if the user is authenticated and takes part in it
+    pub async fn leave_group_dm(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        dm_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        DmHandler::leave_group_dm(&dms, &self.user_handler, dm_id, &user_id).await
+    }
+}
+
+//friends
+impl Handler {
+    ///get the friends, pending friend requests and blocked users of the user if the user is
+    ///authenticated
+    pub async fn get_friends(&self, mongo_client: &Client, user_id: ID) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::get_friends(&dms, &self.user_handler, &user_id).await
+    }
+
+    ///send a friend request to a user if the user is authenticated
+    pub async fn add_friend(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::add_friend(&dms, &self.user_handler, &user_id, other_id).await
+    }
+
+    ///accept a friend request if the user is authenticated
+    pub async fn accept_friend(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::accept_friend(&dms, &user_id, other_id).await
+    }
+
+    ///decline a friend request if the user is authenticated
+    pub async fn decline_friend(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::decline_friend(&dms, &user_id, other_id).await
+    }
+
+    ///cancel a friend request the user sent if the user is authenticated
+    pub async fn cancel_friend_request(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::cancel_friend_request(&dms, &user_id, other_id).await
+    }
+
+    ///end a friendship if the user is authenticated
+    pub async fn remove_friend(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::remove_friend(&dms, &user_id, other_id).await
+    }
+
+    ///block a user if the user is authenticated
+    pub async fn block_user(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::block_user(&dms, &self.user_handler, &user_id, other_id).await
+    }
+
+    ///unblock a user if the user is authenticated
+    pub async fn unblock_user(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        other_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        FriendHandler::unblock_user(&dms, &user_id, other_id).await
+    }
+}
+
+//presence
+impl Handler {
+    ///set the presence and the custom status of the user if the user is authenticated
+    pub async fn set_presence(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        presence: Presence,
+        custom_status: Option<CustomStatus>,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let dms = mongo_client.database(DM_DATABASE);
+        PresenceHandler::set_presence(
+            mongo_client,
+            &dms,
+            &self.user_handler,
+            &user_id,
+            presence,
+            custom_status,
+        )
+        .await
+    }
+
+    ///get the presence of users if the user is authenticated
+    pub async fn get_presence(&self, user_id: ID, user_ids: Vec<ID>) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        PresenceHandler::get_presence(&self.user_handler, user_ids).await
+    }
+
+    ///get the events of the user after the last received event if the user is authenticated
+    pub async fn get_events(&self, user_id: ID, after: Option<ID>) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        PresenceHandler::get_events(&self.user_handler, &user_id, after).await
+    }
+}
+
+//uploads
+impl Handler {
+    ///start an upload if the user is authenticated
+    pub async fn start_upload(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        filename: String,
+        size: u64,
+        mime_type: String,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let uploads = mongo_client.database(ATTACHMENT_DATABASE);
+        AttachmentHandler::start_upload(&uploads, &user_id, filename, size, mime_type).await
+    }
+
+    ///append a chunk to an upload of the user if the user is authenticated
+    pub async fn upload_chunk(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        upload_id: &ID,
+        offset: u64,
+        bytes: Vec<u8>,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let uploads = mongo_client.database(ATTACHMENT_DATABASE);
+        AttachmentHandler::upload_chunk(&uploads, &user_id, upload_id, offset, bytes).await
+    }
+
+    ///turn a complete upload of the user into an attachment if the user is authenticated
+    pub async fn finish_upload(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        upload_id: &ID,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        let uploads = mongo_client.database(ATTACHMENT_DATABASE);
+        AttachmentHandler::finish_upload(&uploads, &self.blobs, &user_id, upload_id).await
+    }
+}
+
+//profiles
+impl Handler {
+    ///get the profile of a user if the user is authenticated
+    pub async fn get_profile(&self, user_id: ID, profile_user_id: &ID) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ProfileHandler::get_profile(&self.user_handler, profile_user_id).await
+    }
+
+    ///change the profile of the user if the user is authenticated
+    pub async fn update_profile(&self, user_id: ID, update: ProfileUpdate) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ProfileHandler::update_profile(&self.user_handler, &self.blobs, &user_id, update).await
+    }
+
+    ///get the avatar or banner of a user if the user is authenticated
+    pub async fn get_profile_image(
+        &self,
+        user_id: ID,
+        profile_user_id: &ID,
+        image: ProfileImage,
+    ) -> Result<Response> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return self.session_handler.check_session_active(oid).await;
+        }
+        ProfileHandler::get_profile_image(&self.user_handler, &self.blobs, profile_user_id, image)
+            .await
+    }
+}
+
+//typing indicators
+impl Handler {
+    ///returns the key of the channel for the typing tracker and the name the user is displayed
+    ///with if the user is authenticated and can read the channel, else the error response
+    async fn typing_channel(
+        &self,
+        mongo_client: &Client,
+        user_id: &ID,
+        server_id: &ID,
+        channel_name: &str,
+    ) -> Result<Result<(String, String), Response>> {
+        if !self.is_authenticated(user_id.clone()).await? {
+            let oid = ObjectId::parse_str(user_id.id.clone())?;
+            return Ok(Err(self.session_handler.check_session_active(oid).await?));
+        }
+        match ServerHandler::get_channel_identity(
+            mongo_client,
+            &self.user_handler,
+            server_id,
+            channel_name,
+            user_id,
+        )
+        .await?
+        {
+            FindError::Ok((channel_id, name)) => {
+                Ok(Ok((TypingTracker::key(server_id, &channel_id), name)))
+            }
+            FindError::Err(e) => Ok(Err(Response::Error(e))),
+        }
+    }
+
+    ///mark the user as typing in a channel if the user is authenticated and can read the channel
+    pub async fn start_typing(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+    ) -> Result<Response> {
+        let (channel, name) = match self
+            .typing_channel(mongo_client, &user_id, server_id, &channel_name)
+            .await?
+        {
+            Ok(identity) => identity,
+            Err(response) => return Ok(response),
+        };
+        self.typing.start(&channel, &user_id, name);
+        Ok(Response::Success)
+    }
+
+    ///get the other users typing in a channel if the user is authenticated and can read the
+    ///channel
+    pub async fn get_typing(
+        &self,
+        mongo_client: &Client,
+        user_id: ID,
+        server_id: &ID,
+        channel_name: String,
+    ) -> Result<Response> {
+        let (channel, _) = match self
+            .typing_channel(mongo_client, &user_id, server_id, &channel_name)
+            .await?
+        {
+            Ok(identity) => identity,
+            Err(response) => return Ok(response),
+        };
+        let mut typing = self.typing.typing(&channel);
+        typing.retain(|entry| entry.user_id != user_id);
+        Ok(Response::Typing(typing))
+    }
+
+    ///subscribe to the changes of the typing users if the user is authenticated and can read the
+    ///channel, returns the key of the channel the changes have to be filtered by
+    pub async fn subscribe_typing(
+        &self,
+        mongo_client: &Client,
+        user_id: &ID,
+        server_id: &ID,
+        channel_name: &str,
+    ) -> Result<Result<(String, broadcast::Receiver<TypingChange>), Response>> {
+        let (channel, _) = match self
+            .typing_channel(mongo_client, user_id, server_id, channel_name)
+            .await?
+        {
+            Ok(identity) => identity,
+            Err(response) => return Ok(Err(response)),
+        };
+        Ok(Ok((channel, self.typing.subscribe())))
     }
 }
 
@@ -192,7 +1396,11 @@ mod test {
         let client = connect_mongo(None).await.unwrap();
         let uhandler = UserHandler::from_names(&client, "TESTAUTH", "users");
         let shandler = SessionHandler::from_names(&client, "TESTAUTH", "sessions");
-        let handler = Handler::new(shandler, uhandler);
+        let handler = Handler::new(
+            shandler,
+            uhandler,
+            BlobStore::new(std::env::temp_dir().join("TESTAUTH")),
+        );
 
         let resp = handler
             .signup("TUser".to_string(), "Password123".to_string())
@@ -0,0 +1,252 @@
+use common::file::{ImageFormat, THUMBNAIL_SIZES};
+use image::{
+    io::{Limits, Reader},
+    GenericImageView, ImageOutputFormat,
+};
+use std::io::Cursor;
+
+///images with a larger width or height are not decoded
+const MAX_IMAGE_DIMENSION: u32 = 16384;
+///maximum memory in bytes decoding an image may allocate
+const MAX_DECODE_ALLOCATION: u64 = 256 * 1024 * 1024;
+
+///a thumbnail of an image encoded as png
+pub struct ScaledImage {
+    pub max_size: u32,
+    pub width: u32,
+    pub height: u32,
+    pub png: Vec<u8>,
+}
+
+///an image attachment prepared for storing
+pub struct PreparedImage {
+    pub width: u32,
+    pub height: u32,
+    pub thumbnails: Vec<ScaledImage>,
+}
+
+///removes metadata like exif and xmp, which may contain the location a photo was taken at, from
+///png, jpeg and webp images without decoding them. gifs carry no such metadata and are returned
+///unchanged. returns None if the bytes are no image of these formats or malformed. the exif
+///orientation is removed as well, so rotated photos are shown as they were recorded
+pub fn strip_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    match ImageFormat::detect(bytes)? {
+        ImageFormat::Png => strip_png(bytes),
+        ImageFormat::Jpeg => strip_jpeg(bytes),
+        ImageFormat::Webp => strip_webp(bytes),
+        ImageFormat::Gif => Some(bytes.to_vec()),
+    }
+}
+
+///drops the text, time and exif chunks
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = bytes[..8].to_vec();
+    let mut rest = &bytes[8..];
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        //length, type, data and crc
+        let chunk = rest.get(..length.checked_add(12)?)?;
+        if !matches!(
+            &chunk[4..8],
+            b"tEXt" | b"zTXt" | b"iTXt" | b"eXIf" | b"tIME"
+        ) {
+            stripped.extend_from_slice(chunk);
+        }
+        rest = &rest[chunk.len()..];
+    }
+    Some(stripped)
+}
+
+///drops the application segments that hold exif, xmp and iptc data and comments, the segments
+///needed to show the image correctly are kept
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = bytes[..2].to_vec();
+    let mut position = 2;
+    loop {
+        if *bytes.get(position)? != 0xff {
+            return None;
+        }
+        let marker = *bytes.get(position + 1)?;
+        match marker {
+            //fill bytes
+            0xff => {
+                position += 1;
+                continue;
+            }
+            //markers without a segment
+            0x01 | 0xd0..=0xd7 => {
+                stripped.extend_from_slice(&bytes[position..position + 2]);
+                position += 2;
+                continue;
+            }
+            //start of scan, the compressed image data follows until the end of the file
+            0xda => {
+                stripped.extend_from_slice(&bytes[position..]);
+                return Some(stripped);
+            }
+            _ => {}
+        }
+        let length = u16::from_be_bytes(bytes.get(position + 2..position + 4)?.try_into().ok()?);
+        let segment = bytes.get(position..position + 2 + length as usize)?;
+        //app1 holds exif and xmp, app13 iptc, fe is a comment
+        if !matches!(marker, 0xe1 | 0xed | 0xfe) {
+            stripped.extend_from_slice(segment);
+        }
+        position += segment.len();
+    }
+}
+
+///drops the exif and xmp chunks and clears their flags in the extended header
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = bytes[..12].to_vec();
+    let mut rest = &bytes[12..];
+    while !rest.is_empty() {
+        let length = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?) as usize;
+        //chunks are padded to an even length
+        let padded = length.checked_add(length % 2)?.checked_add(8)?;
+        let chunk = rest.get(..padded.min(rest.len()))?;
+        if chunk.len() < length + 8 {
+            return None;
+        }
+        match &chunk[..4] {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" if length > 0 => {
+                let flags_position = stripped.len() + 8;
+                stripped.extend_from_slice(chunk);
+                stripped[flags_position] &= !(0x04 | 0x08);
+            }
+            _ => stripped.extend_from_slice(chunk),
+        }
+        rest = &rest[chunk.len()..];
+    }
+    let riff_size = u32::try_from(stripped.len() - 8).ok()?;
+    stripped[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(stripped)
+}
+
+///decodes the image and scales it down to every thumbnail size smaller than the image, returns
+///None if the image can't be decoded. animated images are represented by their first frame
+pub fn prepare_image(bytes: &[u8]) -> Option<PreparedImage> {
+    let mut reader = Reader::new(Cursor::new(bytes)).with_guessed_format().ok()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOCATION);
+    reader.limits(limits);
+    let image = reader.decode().ok()?;
+
+    let (width, height) = image.dimensions();
+    let mut thumbnails = Vec::new();
+    for max_size in THUMBNAIL_SIZES {
+        if width.max(height) <= max_size {
+            break;
+        }
+        let thumbnail = image.thumbnail(max_size, max_size);
+        let mut png = Vec::new();
+        thumbnail
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .ok()?;
+        thumbnails.push(ScaledImage {
+            max_size,
+            width: thumbnail.width(),
+            height: thumbnail.height(),
+            png,
+        });
+    }
+    Some(PreparedImage {
+        width,
+        height,
+        thumbnails,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+
+    fn encode(width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(width, height));
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    fn contains(bytes: &[u8], part: &[u8]) -> bool {
+        bytes.windows(part.len()).any(|window| window == part)
+    }
+
+    #[test]
+    fn test_strip_jpeg() {
+        let jpeg = encode(16, 16, ImageOutputFormat::Jpeg(80));
+        let exif = b"Exif\0\0GPSLatitude";
+        let mut tagged = jpeg[..2].to_vec();
+        tagged.extend_from_slice(&[0xff, 0xe1]);
+        tagged.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        tagged.extend_from_slice(exif);
+        tagged.extend_from_slice(&jpeg[2..]);
+
+        let stripped = strip_metadata(&tagged).unwrap();
+        assert!(!contains(&stripped, b"GPSLatitude"));
+        assert_eq!(stripped, jpeg);
+        assert_eq!(strip_metadata(&tagged[..20]), None);
+    }
+
+    #[test]
+    fn test_strip_png() {
+        let png = encode(16, 16, ImageOutputFormat::Png);
+        //the text chunk is inserted after the header chunk
+        let text = b"Location\0Berlin";
+        let mut tagged = png[..33].to_vec();
+        tagged.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        tagged.extend_from_slice(b"tEXt");
+        tagged.extend_from_slice(text);
+        tagged.extend_from_slice(&[0; 4]);
+        tagged.extend_from_slice(&png[33..]);
+
+        let stripped = strip_metadata(&tagged).unwrap();
+        assert!(!contains(&stripped, b"Berlin"));
+        assert_eq!(stripped, png);
+    }
+
+    #[test]
+    fn test_strip_webp() {
+        let mut webp = b"RIFF\0\0\0\0WEBP".to_vec();
+        webp.extend_from_slice(b"VP8X\x0a\0\0\0\x0c\0\0\0\0\0\0\0\0\0");
+        webp.extend_from_slice(b"EXIF\x03\0\0\0GPS\0");
+        webp.extend_from_slice(b"XMP \x02\0\0\0ab");
+
+        let stripped = strip_metadata(&webp).unwrap();
+        assert_eq!(stripped.len(), 12 + 18);
+        assert_eq!(&stripped[4..8], &22u32.to_le_bytes());
+        assert_eq!(stripped[20], 0);
+        assert!(!contains(&stripped, b"GPS"));
+    }
+
+    #[test]
+    fn test_prepare_image() {
+        let png = encode(600, 300, ImageOutputFormat::Png);
+        let prepared = prepare_image(&png).unwrap();
+        assert_eq!((prepared.width, prepared.height), (600, 300));
+        let sizes: Vec<(u32, u32, u32)> = prepared
+            .thumbnails
+            .iter()
+            .map(|thumbnail| (thumbnail.max_size, thumbnail.width, thumbnail.height))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![(128, 128, 64), (256, 256, 128), (512, 512, 256)]
+        );
+        assert_eq!(
+            ImageFormat::detect(&prepared.thumbnails[0].png),
+            Some(ImageFormat::Png)
+        );
+
+        let small = encode(100, 50, ImageOutputFormat::Png);
+        assert!(prepare_image(&small).unwrap().thumbnails.is_empty());
+        assert!(prepare_image(b"GIF89a broken").is_none());
+    }
+}
@@ -1,12 +1,33 @@
+mod attachments;
+mod blob_store;
 mod core;
+mod dm_handler;
+mod events;
+mod friend_handler;
+mod handler;
+mod images;
+mod memberships;
+mod mentions;
+mod message_store;
 mod mongodb;
-mod user;
-mod session;
+mod presence;
+mod profile;
+mod read_states;
+mod search;
 mod server_handler;
-mod handler;
+mod session;
+mod typing;
+mod user;
 
+use attachments::{AttachmentHandler, ATTACHMENT_DATABASE};
+use blob_store::{BlobStore, BLOB_DIRECTORY};
+use dm_handler::{DmHandler, DM_DATABASE};
+use events::EventFeed;
+use friend_handler::Relationships;
 use handler::Handler;
 use log::{error, info};
+use presence::PresenceHandler;
+use server_handler::ServerHandler;
 use session::SessionHandler;
 use user::UserHandler;
 
@@ -27,7 +48,38 @@ async fn main() {
 
     let ufrom_names = SessionHandler::from_names(&client, "SESSIONS", "sessions");
     let sfrom_names = UserHandler::from_names(&client, "USERS", "users");
-    let auth_handler = Handler::new(ufrom_names, sfrom_names);
+    if let Err(err) = ServerHandler::prepare_servers(&client, &sfrom_names).await {
+        error!("Can't prepare the servers {:?}", err);
+        panic!();
+    }
+    if let Err(err) = EventFeed::new(sfrom_names.database())
+        .create_indexes()
+        .await
+    {
+        error!("Can't create the event indexes {:?}", err);
+        panic!();
+    }
+    if let Err(err) = DmHandler::create_indexes(&client.database(DM_DATABASE)).await {
+        error!("Can't create the dm indexes {:?}", err);
+        panic!();
+    }
+    if let Err(err) = Relationships::new(&client.database(DM_DATABASE))
+        .create_indexes()
+        .await
+    {
+        error!("Can't create the relationship indexes {:?}", err);
+        panic!();
+    }
+    if let Err(err) = AttachmentHandler::create_indexes(&client.database(ATTACHMENT_DATABASE)).await
+    {
+        error!("Can't create the attachment indexes {:?}", err);
+        panic!();
+    }
+    tokio::spawn(PresenceHandler::run_presence_updates(
+        client.clone(),
+        sfrom_names.clone(),
+    ));
+    let auth_handler = Handler::new(ufrom_names, sfrom_names, BlobStore::new(BLOB_DIRECTORY));
 
     match core::accept_new_connections(client, auth_handler).await {
         Ok(_) => {
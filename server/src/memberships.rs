@@ -0,0 +1,293 @@
+use anyhow::Result;
+use common::{id::ID, user::Role};
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{FindOptions, IndexOptions, UpdateOptions},
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+pub const MEMBERSHIP_DATABASE: &str = "MEMBERSHIPS";
+
+///a user being a member of a server, stored in the members collection of the membership database.
+///mirrors the users of the server configs so the servers of a user can be found without reading
+///every server
+#[derive(Serialize, Deserialize, Debug)]
+struct Membership {
+    server_id: ID,
+    user_id: ID,
+    ///position of the highest role of the member in the role hierarchy, the member list of a
+    ///server is sorted by it and then by the name
+    #[serde(default)]
+    rank: i32,
+    ///the nickname of the member on the server or the username, in lowercase
+    #[serde(default)]
+    name: String,
+}
+
+///a member of a server together with what the member list of the server is sorted by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberKey {
+    pub user_id: ID,
+    ///the highest role of the member
+    pub role: Role,
+    ///the nickname of the member on the server or the username
+    pub name: String,
+}
+
+///the members of all servers
+#[derive(Clone)]
+pub struct Memberships {
+    members: Collection<Membership>,
+}
+
+impl Memberships {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            members: database.collection("members"),
+        }
+    }
+
+    ///creates the indexes used to look up the members of a server, to list them in order and to
+    ///look up the servers of a user
+    pub async fn create_indexes(&self) -> Result<()> {
+        //creating an index that already exists has no effect
+        let members = IndexModel::builder()
+            .keys(doc! {"server_id.id": 1, "user_id.id": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        let list = IndexModel::builder()
+            .keys(doc! {"server_id.id": 1, "rank": 1, "name": 1, "user_id.id": 1})
+            .build();
+        let servers = IndexModel::builder().keys(doc! {"user_id.id": 1}).build();
+        self.members
+            .create_indexes([members, list, servers], None)
+            .await?;
+        Ok(())
+    }
+
+    ///adds the member to the members of the server, adding a member twice only updates the role
+    ///and the name of the member
+    pub async fn add(&self, server_id: &ID, member: &MemberKey) -> Result<()> {
+        let opts = UpdateOptions::builder().upsert(true).build();
+        self.members
+            .update_one(
+                doc! {"server_id.id": &server_id.id, "user_id.id": &member.user_id.id},
+                doc! {
+                    "$set": {"rank": member.role as i32, "name": member.name.to_lowercase()},
+                    "$setOnInsert": {
+                        "server_id": to_bson(server_id)?,
+                        "user_id": to_bson(&member.user_id)?,
+                    },
+                },
+                opts,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///replaces the members of the server with the members
+    pub async fn set_members(&self, server_id: &ID, members: &[MemberKey]) -> Result<()> {
+        let ids: Vec<&String> = members.iter().map(|member| &member.user_id.id).collect();
+        self.members
+            .delete_many(
+                doc! {"server_id.id": &server_id.id, "user_id.id": {"$nin": ids}},
+                None,
+            )
+            .await?;
+        for member in members {
+            self.add(server_id, member).await?;
+        }
+        Ok(())
+    }
+
+    ///sets the highest role of the member of the server
+    pub async fn set_role(&self, server_id: &ID, user_id: &ID, role: Role) -> Result<()> {
+        self.members
+            .update_one(
+                doc! {"server_id.id": &server_id.id, "user_id.id": &user_id.id},
+                doc! {"$set": {"rank": role as i32}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///sets the name the member of the server is listed by, the nickname or the username
+    pub async fn set_name(&self, server_id: &ID, user_id: &ID, name: &str) -> Result<()> {
+        self.members
+            .update_one(
+                doc! {"server_id.id": &server_id.id, "user_id.id": &user_id.id},
+                doc! {"$set": {"name": name.to_lowercase()}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///returns up to limit members of the server after skipping the first skip members, members
+    ///are sorted by their highest role and then by their name
+    pub async fn page(&self, server_id: &ID, skip: u64, limit: i64) -> Result<Vec<ID>> {
+        let opts = FindOptions::builder()
+            .sort(doc! {"rank": 1, "name": 1, "user_id.id": 1})
+            .skip(skip)
+            .limit(limit)
+            .build();
+        let mut cursor = self
+            .members
+            .find(doc! {"server_id.id": &server_id.id}, opts)
+            .await?;
+        let mut members = Vec::new();
+        while cursor.advance().await? {
+            members.push(cursor.deserialize_current()?.user_id);
+        }
+        Ok(members)
+    }
+
+    ///removes all members of the server
+    pub async fn remove_server(&self, server_id: &ID) -> Result<()> {
+        self.members
+            .delete_many(doc! {"server_id.id": &server_id.id}, None)
+            .await?;
+        Ok(())
+    }
+
+    ///returns the users that share at least one server with the user, excluding the user
+    pub async fn co_members(&self, user_id: &ID) -> Result<Vec<ID>> {
+        let mut cursor = self
+            .members
+            .find(doc! {"user_id.id": &user_id.id}, None)
+            .await?;
+        let mut servers = Vec::new();
+        while cursor.advance().await? {
+            servers.push(cursor.deserialize_current()?.server_id.id);
+        }
+        if servers.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut cursor = self
+            .members
+            .find(
+                doc! {"server_id.id": {"$in": servers}, "user_id.id": {"$ne": &user_id.id}},
+                None,
+            )
+            .await?;
+        let mut members: Vec<ID> = Vec::new();
+        while cursor.advance().await? {
+            let member = cursor.deserialize_current()?.user_id;
+            if !members.contains(&member) {
+                members.push(member);
+            }
+        }
+        Ok(members)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use tokio::test;
+
+    use super::*;
+
+    fn member(user_id: &ID, role: Role, name: &str) -> MemberKey {
+        MemberKey {
+            user_id: user_id.clone(),
+            role,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    async fn test_co_members() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MEMBERSHIPS1");
+        db.drop(None).await.unwrap();
+        let memberships = Memberships::new(&db);
+        memberships.create_indexes().await.unwrap();
+        let first = ID::new("120129184124124127777100".to_string()).unwrap();
+        let second = ID::new("120129184124124127777101".to_string()).unwrap();
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let moritz = ID::new("123123123123123123123124".to_string()).unwrap();
+        let malte = ID::new("123123123123123123123125".to_string()).unwrap();
+
+        let (max_key, moritz_key, malte_key) = (
+            member(&max, Role::Owner, "Max"),
+            member(&moritz, Role::Member, "Moritz"),
+            member(&malte, Role::Member, "Malte"),
+        );
+        memberships.add(&first, &max_key).await.unwrap();
+        memberships.add(&first, &max_key).await.unwrap();
+        memberships.add(&first, &moritz_key).await.unwrap();
+        memberships
+            .set_members(&second, &[max_key.clone(), moritz_key, malte_key])
+            .await
+            .unwrap();
+        let max_members = memberships.co_members(&max).await.unwrap();
+        memberships
+            .set_members(&second, std::slice::from_ref(&max_key))
+            .await
+            .unwrap();
+        let malte_members = memberships.co_members(&malte).await.unwrap();
+        memberships.remove_server(&first).await.unwrap();
+        let moritz_members = memberships.co_members(&moritz).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(max_members, vec![moritz, malte]);
+        assert!(malte_members.is_empty());
+        assert!(moritz_members.is_empty());
+    }
+
+    #[test]
+    async fn test_member_page() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MEMBERSHIPS2");
+        db.drop(None).await.unwrap();
+        let memberships = Memberships::new(&db);
+        memberships.create_indexes().await.unwrap();
+        let server = ID::new("120129184124124127777100".to_string()).unwrap();
+        let ids: Vec<ID> = (0..5)
+            .map(|i| ID::new(format!("12312312312312312312312{}", i)).unwrap())
+            .collect();
+        memberships
+            .set_members(
+                &server,
+                &[
+                    member(&ids[0], Role::Member, "bob"),
+                    member(&ids[1], Role::Admin, "zoe"),
+                    member(&ids[2], Role::Member, "Anton"),
+                    member(&ids[3], Role::Owner, "yves"),
+                    member(&ids[4], Role::Admin, "Alice"),
+                ],
+            )
+            .await
+            .unwrap();
+        let first = memberships.page(&server, 0, 3).await.unwrap();
+        let second = memberships.page(&server, 3, 3).await.unwrap();
+        //the nickname changes the position of the member, so does a new role
+        memberships
+            .set_name(&server, &ids[0], "Aaron")
+            .await
+            .unwrap();
+        memberships
+            .set_role(&server, &ids[1], Role::Member)
+            .await
+            .unwrap();
+        let changed = memberships.page(&server, 0, 5).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(first, vec![ids[3].clone(), ids[4].clone(), ids[1].clone()]);
+        assert_eq!(second, vec![ids[2].clone(), ids[0].clone()]);
+        assert_eq!(
+            changed,
+            vec![
+                ids[3].clone(),
+                ids[4].clone(),
+                ids[0].clone(),
+                ids[2].clone(),
+                ids[1].clone()
+            ]
+        );
+    }
+}
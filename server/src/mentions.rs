@@ -0,0 +1,304 @@
+use anyhow::Result;
+use common::{id::ID, messages::Message};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    options::FindOptions,
+    Collection, Database,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+///returns the names that are mentioned in the content, a mention is an @ at the start of the
+///content or after whitespace followed by the name, names consist of alphanumeric characters,
+///underscores, dashes and dots, trailing dots are not part of the name
+pub fn parse_mentions(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for word in content.split_whitespace() {
+        let name = match word.strip_prefix('@') {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let end = name
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.'))
+            .unwrap_or(name.len());
+        let name = name[..end].trim_end_matches('.');
+        if !name.is_empty() && !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+///a mention of a user, stored in the mentions collection of the server
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MentionEntry {
+    _id: ObjectId,
+    ///None for a mention of everyone, which is stored once and belongs to all users of the server
+    pub user_id: Option<ID>,
+    ///author of the message, mentions of everyone don't belong to the author
+    #[serde(default)]
+    pub author_id: Option<ID>,
+    pub message_id: ID,
+    ///name of the collection the message is stored in, the id of the channel or thread
+    pub storage: String,
+    ///sequence number of the message in its storage
+    #[serde(default)]
+    pub seq: u64,
+}
+
+///the mentions of all users of a server, newest first
+#[derive(Clone)]
+pub struct MentionInbox {
+    mentions: Collection<MentionEntry>,
+}
+
+impl MentionInbox {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            mentions: database.collection("mentions"),
+        }
+    }
+
+    ///filter matching the mentions of the user, including the mentions of everyone
+    fn of_user(user_id: &ID) -> Document {
+        doc! {"$or": [
+            {"user_id.id": &user_id.id},
+            {"user_id": null, "author_id.id": {"$ne": &user_id.id}},
+        ]}
+    }
+
+    ///adds mentions of the message stored in the storage for the users and one for everyone
+    async fn insert(
+        &self,
+        users: &[ID],
+        everyone: bool,
+        message: &Message,
+        storage: &str,
+    ) -> Result<()> {
+        let mentioned = users
+            .iter()
+            .map(|user_id| Some(user_id.clone()))
+            .chain(everyone.then_some(None));
+        let entries: Vec<MentionEntry> = mentioned
+            .map(|user_id| MentionEntry {
+                _id: ObjectId::new(),
+                user_id,
+                author_id: message.author_id.clone(),
+                message_id: message.id.clone(),
+                storage: storage.to_string(),
+                seq: message.seq,
+            })
+            .collect();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.mentions.insert_many(entries, None).await?;
+        Ok(())
+    }
+
+    ///adds the message stored in the storage to the inbox of all users and to the inbox of
+    ///everyone if the message mentions everyone
+    pub async fn add(&self, users: &[ID], message: &Message, storage: &str) -> Result<()> {
+        self.insert(users, message.mentions_everyone, message, storage)
+            .await
+    }
+
+    ///replaces the mentions of the edited message with the mentions of the users and of everyone
+    ///if the message mentions everyone, mentions that stay keep their place in the inbox
+    pub async fn update(&self, users: &[ID], message: &Message, storage: &str) -> Result<()> {
+        let mut cursor = self
+            .mentions
+            .find(doc! {"message_id.id": &message.id.id}, None)
+            .await?;
+        let mut stale = Vec::new();
+        let mut kept = Vec::new();
+        let mut everyone_kept = false;
+        while cursor.advance().await? {
+            let entry = cursor.deserialize_current()?;
+            match entry.user_id {
+                Some(user_id) if users.contains(&user_id) && !kept.contains(&user_id) => {
+                    kept.push(user_id)
+                }
+                None if message.mentions_everyone && !everyone_kept => everyone_kept = true,
+                _ => stale.push(entry._id),
+            }
+        }
+        if !stale.is_empty() {
+            self.mentions
+                .delete_many(doc! {"_id": {"$in": stale}}, None)
+                .await?;
+        }
+
+        let added: Vec<ID> = users
+            .iter()
+            .filter(|user_id| !kept.contains(user_id))
+            .cloned()
+            .collect();
+        let everyone = message.mentions_everyone && !everyone_kept;
+        self.insert(&added, everyone, message, storage).await
+    }
+
+    ///returns up to limit of the latest mentions of the user
+    pub async fn latest(&self, user_id: &ID, limit: u32) -> Result<Vec<MentionEntry>> {
+        //object ids start with their creation time
+        let opts = FindOptions::builder()
+            .sort(doc! {"_id": -1})
+            .limit(limit as i64)
+            .build();
+        let mut cursor = self.mentions.find(Self::of_user(user_id), opts).await?;
+        let mut entries = Vec::new();
+        while cursor.advance().await? {
+            entries.push(cursor.deserialize_current()?);
+        }
+        Ok(entries)
+    }
+
+    ///returns the number of mentions of the user per storage that are newer than the sequence
+    ///number given for the storage, only the given storages are counted
+    pub async fn count_after(
+        &self,
+        user_id: &ID,
+        after: &HashMap<String, u64>,
+    ) -> Result<HashMap<String, u64>> {
+        if after.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let newer: Vec<Document> = after
+            .iter()
+            .map(|(storage, seq)| doc! {"storage": storage, "seq": {"$gt": *seq as i64}})
+            .collect();
+        let pipeline = vec![
+            doc! {"$match": {"$and": [Self::of_user(user_id), {"$or": newer}]}},
+            doc! {"$group": {"_id": "$storage", "count": {"$sum": 1_i64}}},
+        ];
+        let mut cursor = self.mentions.aggregate(pipeline, None).await?;
+        let mut counts = HashMap::new();
+        while cursor.advance().await? {
+            let group = cursor.deserialize_current()?;
+            counts.insert(
+                group.get_str("_id")?.to_string(),
+                group.get_i64("count")? as u64,
+            );
+        }
+        Ok(counts)
+    }
+
+    ///removes all mentions of messages stored in the storage
+    pub async fn forget(&self, storage: &str) -> Result<()> {
+        self.mentions
+            .delete_many(doc! {"storage": storage}, None)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_parse_mentions() {
+        assert_eq!(
+            parse_mentions("@Max hi @everyone, mail me at max@nicord.de or ask @admin."),
+            vec!["Max", "everyone", "admin"]
+        );
+        assert_eq!(parse_mentions("@Max @Max @ @@"), vec!["Max"]);
+        assert!(parse_mentions("no mentions").is_empty());
+    }
+
+    #[test]
+    async fn test_mention_inbox() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MENTIONS1");
+        db.drop(None).await.unwrap();
+        let inbox = MentionInbox::new(&db);
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let moritz = ID::new("123123123123123123123124".to_string()).unwrap();
+        let malte = ID::new("123123123123123123123125".to_string()).unwrap();
+        let mut first = Message::from_user(
+            "@Max @Moritz".to_string(),
+            "Malte".to_string(),
+            malte.clone(),
+        );
+        first.seq = 1;
+        let mut second = Message::from_user("@Max".to_string(), "Malte".to_string(), malte.clone());
+        second.seq = 2;
+
+        inbox
+            .add(&[max.clone(), moritz.clone()], &first, "CHANNEL1")
+            .await
+            .unwrap();
+        inbox
+            .add(std::slice::from_ref(&max), &second, "CHANNEL1")
+            .await
+            .unwrap();
+        inbox
+            .add(std::slice::from_ref(&max), &second, "CHANNEL2")
+            .await
+            .unwrap();
+        let latest = inbox.latest(&max, 10).await.unwrap();
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[0].message_id, second.id);
+        assert_eq!(latest[0].storage, "CHANNEL2");
+        assert_eq!(inbox.latest(&max, 1).await.unwrap().len(), 1);
+
+        let read = HashMap::from([("CHANNEL1".to_string(), 1), ("CHANNEL2".to_string(), 2)]);
+        let counts = inbox.count_after(&max, &read).await.unwrap();
+        assert_eq!(counts.get("CHANNEL1"), Some(&1));
+        assert_eq!(counts.get("CHANNEL2"), None);
+
+        inbox.forget("CHANNEL1").await.unwrap();
+        let moritz_mentions = inbox.latest(&moritz, 10).await.unwrap();
+        db.drop(None).await.unwrap();
+        assert!(moritz_mentions.is_empty());
+    }
+
+    #[test]
+    async fn test_mention_everyone() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MENTIONS2");
+        db.drop(None).await.unwrap();
+        let inbox = MentionInbox::new(&db);
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let moritz = ID::new("123123123123123123123124".to_string()).unwrap();
+        let mut message =
+            Message::from_user("@everyone".to_string(), "Max".to_string(), max.clone());
+        message.seq = 1;
+        message.mentions_everyone = true;
+
+        inbox.add(&[], &message, "CHANNEL1").await.unwrap();
+        let moritz_mentions = inbox.latest(&moritz, 10).await.unwrap();
+        //the author is not notified about mentioning everyone
+        let max_mentions = inbox.latest(&max, 10).await.unwrap();
+        let read = HashMap::from([("CHANNEL1".to_string(), 0)]);
+        let counts = inbox.count_after(&moritz, &read).await.unwrap();
+
+        //the edit mentions moritz only, the mention of everyone is removed
+        message.content = "@Moritz".to_string();
+        message.mentions_everyone = false;
+        inbox
+            .update(std::slice::from_ref(&moritz), &message, "CHANNEL1")
+            .await
+            .unwrap();
+        let edited = inbox.latest(&moritz, 10).await.unwrap();
+        let max_edited = inbox.latest(&max, 10).await.unwrap();
+        inbox
+            .update(std::slice::from_ref(&moritz), &message, "CHANNEL1")
+            .await
+            .unwrap();
+        let unchanged = inbox.latest(&moritz, 10).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(moritz_mentions.len(), 1);
+        assert_eq!(moritz_mentions[0].user_id, None);
+        assert!(max_mentions.is_empty());
+        assert_eq!(counts.get("CHANNEL1"), Some(&1));
+        assert_eq!(edited.len(), 1);
+        assert_eq!(edited[0].user_id, Some(moritz));
+        assert!(max_edited.is_empty());
+        assert_eq!(unchanged[0]._id, edited[0]._id);
+    }
+}
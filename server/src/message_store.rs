@@ -0,0 +1,1078 @@
+use anyhow::{anyhow, Result};
+use common::{
+    id::ID,
+    messages::{
+        Cursor, HistoryOrder, HistoryQuery, Message, MessageHistory, MessageRevision, Reaction,
+        MAX_HISTORY_LIMIT, MAX_REACTIONS_PER_MESSAGE,
+    },
+};
+use mongodb::{
+    bson::{doc, to_bson, Document},
+    error::{CommandError, Error, ErrorKind, WriteError, WriteFailure},
+    options::{
+        FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReturnDocument, UpdateOptions,
+    },
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::HashMap, time::SystemTime};
+
+use crate::search::SearchIndex;
+
+///number of messages that fit into one block
+pub const BLOCK_SIZE: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub _id: u32,
+    pub messages: Vec<Message>,
+    pub time_stamp: SystemTime,
+    pub filled: bool,
+    ///range of the sequence numbers and creation times of all messages that were added to the
+    ///block, deleted messages are not taken out. used to read only the blocks that can hold a
+    ///part of the history
+    #[serde(default)]
+    pub min_seq: u64,
+    #[serde(default)]
+    pub max_seq: u64,
+    #[serde(default)]
+    pub min_created_at: Option<SystemTime>,
+    #[serde(default)]
+    pub max_created_at: Option<SystemTime>,
+}
+
+impl Block {
+    pub fn new(id: u32) -> Self {
+        Self {
+            _id: id,
+            messages: Vec::new(),
+            time_stamp: SystemTime::now(),
+            filled: false,
+            min_seq: 0,
+            max_seq: 0,
+            min_created_at: None,
+            max_created_at: None,
+        }
+    }
+
+    ///add message to a block, if reaches BLOCK_SIZE sets filled flag, if len >= BLOCK_SIZE and add
+    ///message is called false is returned to signalize an invalid operation
+    pub fn add_message(&mut self, message: Message) -> bool {
+        if self.messages.len() >= BLOCK_SIZE {
+            return false;
+        }
+        if self.messages.is_empty() {
+            self.min_seq = message.seq;
+            self.max_seq = message.seq;
+        } else {
+            self.min_seq = self.min_seq.min(message.seq);
+            self.max_seq = self.max_seq.max(message.seq);
+        }
+        let created_at = Some(message.created_at);
+        self.min_created_at = self.min_created_at.min(created_at).or(created_at);
+        self.max_created_at = self.max_created_at.max(created_at);
+        self.messages.push(message);
+        if self.messages.len() == BLOCK_SIZE {
+            self.filled = true
+        };
+        true
+    }
+}
+
+///only the id of a block, used to find the last block without loading its messages
+#[derive(Deserialize)]
+struct BlockId {
+    _id: u32,
+}
+
+///a position in the history, messages are ordered by their sequence number
+enum Bound {
+    Seq(u64),
+    Time(SystemTime),
+}
+
+///the messages on one side of a bound
+#[derive(Clone, Copy)]
+enum Side {
+    Before,
+    AtOrBefore,
+    After,
+    AtOrAfter,
+}
+
+impl Bound {
+    ///returns whether the message is on the side of the bound
+    fn contains(&self, side: Side, message: &Message) -> bool {
+        let ordering = match self {
+            Bound::Seq(seq) => message.seq.cmp(seq),
+            Bound::Time(time) => message.created_at.cmp(time),
+        };
+        match side {
+            Side::Before => ordering.is_lt(),
+            Side::AtOrBefore => ordering.is_le(),
+            Side::After => ordering.is_gt(),
+            Side::AtOrAfter => ordering.is_ge(),
+        }
+    }
+
+    ///filter matching all blocks that can hold messages on the side of the bound
+    fn block_filter(&self, side: Side) -> Result<Document> {
+        let (end, operator) = match side {
+            Side::Before => ("min", "$lt"),
+            Side::AtOrBefore => ("min", "$lte"),
+            Side::After => ("max", "$gt"),
+            Side::AtOrAfter => ("max", "$gte"),
+        };
+        Ok(match self {
+            Bound::Seq(seq) => doc! {format!("{}_seq", end): {operator: *seq as i64}},
+            Bound::Time(time) => {
+                doc! {format!("{}_created_at", end): {operator: to_bson(time)?}}
+            }
+        })
+    }
+}
+
+///returns whether the operation failed because a document with the same unique key exists
+pub fn is_duplicate_key(error: &Error) -> bool {
+    const DUPLICATE_KEY: i32 = 11000;
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(WriteError { code, .. })) => {
+            *code == DUPLICATE_KEY
+        }
+        ErrorKind::Command(CommandError { code, .. }) => *code == DUPLICATE_KEY,
+        _other => false,
+    }
+}
+
+///a previous version of a message, stored in the edit_history collection
+#[derive(Serialize, Deserialize, Debug)]
+struct Revision {
+    message_id: ID,
+    ///name of the collection the message is stored in
+    storage: String,
+    content: String,
+    replaced_at: SystemTime,
+}
+
+///the users that reacted to a message with the same emoji
+#[derive(Serialize, Deserialize, Debug)]
+struct EmojiReaction {
+    emoji: String,
+    users: Vec<ID>,
+}
+
+///all reactions to a message, stored in the reactions collection
+#[derive(Serialize, Deserialize, Debug)]
+struct MessageReactions {
+    ///id of the message
+    _id: String,
+    ///name of the collection the message is stored in
+    storage: String,
+    ///in the order the emojis were first used
+    reactions: Vec<EmojiReaction>,
+}
+
+///stores the messages of a channel in blocks of BLOCK_SIZE messages, previous versions of edited
+///messages are kept in the edit_history collection, reactions in the reactions collection and the
+///sequence counters of all stores in the sequences collection of the same database. the search
+///index of the database is kept up to date with the messages of the store
+#[derive(Clone)]
+pub struct MessageStore {
+    storage: String,
+    blocks: Collection<Block>,
+    history: Collection<Revision>,
+    reactions: Collection<MessageReactions>,
+    sequences: Collection<Document>,
+    search: SearchIndex,
+}
+
+impl MessageStore {
+    ///creates a message store that keeps its blocks in the collection named storage
+    pub fn new(database: &Database, storage: &str) -> Self {
+        Self {
+            storage: storage.to_string(),
+            blocks: database.collection(storage),
+            history: database.collection("edit_history"),
+            reactions: database.collection("reactions"),
+            sequences: database.collection("sequences"),
+            search: SearchIndex::new(database),
+        }
+    }
+
+    ///returns the next sequence number of the store, the first message gets 1
+    async fn next_seq(&self) -> Result<u64> {
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+        loop {
+            let counter = match self
+                .sequences
+                .find_one_and_update(
+                    doc! {"_id": &self.storage},
+                    doc! {"$inc": {"seq": 1_i64}},
+                    options.clone(),
+                )
+                .await
+            {
+                Ok(counter) => counter,
+                //two concurrent upserts of a missing counter, the counter exists now
+                Err(e) if is_duplicate_key(&e) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let counter = counter.ok_or_else(|| anyhow!("upsert returned no document"))?;
+            return Ok(counter.get_i64("seq")? as u64);
+        }
+    }
+
+    ///creates the indexes used to find messages by their id and blocks by their range of
+    ///sequence numbers, called once when the first block of the store is created
+    async fn create_indexes(&self) -> Result<()> {
+        let indexes = ["messages.id.id", "min_seq", "max_seq"]
+            .into_iter()
+            .map(|key| IndexModel::builder().keys(doc! {key: 1}).build());
+        self.blocks.create_indexes(indexes, None).await?;
+        Ok(())
+    }
+
+    ///returns the id of the block with the highest id, None if there are no blocks
+    async fn last_block_id(&self) -> Result<Option<u32>> {
+        let options = FindOneOptions::builder()
+            .sort(doc! {"_id": -1})
+            .projection(doc! {"_id": 1})
+            .build();
+        let last = self
+            .blocks
+            .clone_with_type::<BlockId>()
+            .find_one(None, options)
+            .await?;
+        Ok(last.map(|block| block._id))
+    }
+
+    ///appends the message to the last block or creates a new block if the last block is full and
+    ///returns the message as it was stored. the message gets the next sequence number of the store,
+    ///it is safe to append concurrently: the message is pushed only if the block still has room
+    ///and new blocks are inserted with the next id, so a sender that loses the race retries with
+    ///the block that was created by the other sender
+    pub async fn append(&self, mut message: Message) -> Result<Message> {
+        message.seq = self.next_seq().await?;
+        let entry = to_bson(&message)?;
+        let seq = message.seq as i64;
+        let created_at = to_bson(&message.created_at)?;
+        let last_slot = format!("messages.{}", BLOCK_SIZE - 1);
+
+        loop {
+            let next_id = match self.last_block_id().await? {
+                Some(id) => {
+                    let pushed = self
+                        .blocks
+                        .update_one(
+                            doc! {"_id": id, &last_slot: {"$exists": false}},
+                            doc! {
+                                "$push": {"messages": &entry},
+                                "$min": {"min_seq": seq, "min_created_at": &created_at},
+                                "$max": {"max_seq": seq, "max_created_at": &created_at},
+                            },
+                            None,
+                        )
+                        .await?;
+                    if pushed.matched_count == 1 {
+                        break;
+                    }
+                    self.blocks
+                        .update_one(doc! {"_id": id}, doc! {"$set": {"filled": true}}, None)
+                        .await?;
+                    id + 1
+                }
+                None => 0,
+            };
+
+            let mut block = Block::new(next_id);
+            block.add_message(message.clone());
+            match self.blocks.insert_one(block, None).await {
+                Ok(_) if next_id == 0 => {
+                    self.create_indexes().await?;
+                    break;
+                }
+                Ok(_) => break,
+                //another sender created the block first
+                Err(e) if is_duplicate_key(&e) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.search.add(&message, &self.storage).await?;
+        Ok(message)
+    }
+
+    ///name of the collection the blocks are stored in
+    pub fn storage(&self) -> &str {
+        &self.storage
+    }
+
+    ///returns the block with the given id if it exists
+    pub async fn get_block(&self, block_id: u32) -> Result<Option<Block>> {
+        Ok(self.blocks.find_one(doc! {"_id": block_id}, None).await?)
+    }
+
+    ///returns the message with the given id if it exists
+    pub async fn find_message(&self, message_id: &ID) -> Result<Option<Message>> {
+        let block = self
+            .blocks
+            .find_one(doc! {"messages.id.id": &message_id.id}, None)
+            .await?;
+        Ok(block.and_then(|block| {
+            block
+                .messages
+                .into_iter()
+                .find(|message| message.id == *message_id)
+        }))
+    }
+
+    ///replaces the content, the mentions and the emojis of the message with the ones of the edited
+    ///message and stores the previous content in the edit history
+    ///returns false if the message does not exist anymore or was changed since it was read
+    pub async fn edit_message(&self, message: &Message, edited: &Message) -> Result<bool> {
+        let now = SystemTime::now();
+        let result = self
+            .blocks
+            .update_one(
+                doc! {"messages": {"$elemMatch": {
+                    "id.id": &message.id.id,
+                    "content": &message.content,
+                }}},
+                doc! {"$set": {
+                    "messages.$.content": &edited.content,
+                    "messages.$.edited_at": to_bson(&now)?,
+                    "messages.$.mentions": to_bson(&edited.mentions)?,
+                    "messages.$.mentions_everyone": edited.mentions_everyone,
+                    "messages.$.emojis": to_bson(&edited.emojis)?,
+                }},
+                None,
+            )
+            .await?;
+        if result.modified_count == 0 {
+            return Ok(false);
+        }
+
+        let revision = Revision {
+            message_id: message.id.clone(),
+            storage: self.storage.clone(),
+            content: message.content.clone(),
+            replaced_at: now,
+        };
+        self.history.insert_one(revision, None).await?;
+        self.search.update(edited).await?;
+        Ok(true)
+    }
+
+    ///removes the message from its block, returns false if the message does not exist
+    ///the edit history of the message is kept, its reactions are removed
+    pub async fn delete_message(&self, message_id: &ID) -> Result<bool> {
+        let result = self
+            .blocks
+            .update_one(
+                doc! {"messages.id.id": &message_id.id},
+                doc! {"$pull": {"messages": {"id.id": &message_id.id}}},
+                None,
+            )
+            .await?;
+        self.reactions
+            .delete_one(doc! {"_id": &message_id.id}, None)
+            .await?;
+        self.search.remove(message_id).await?;
+        Ok(result.modified_count > 0)
+    }
+
+    ///returns the previous versions of the message, the oldest version first
+    pub async fn get_edit_history(&self, message_id: &ID) -> Result<Vec<MessageRevision>> {
+        let opts = FindOptions::builder().sort(doc! {"replaced_at": 1}).build();
+        let mut cursor = self
+            .history
+            .find(
+                doc! {"message_id.id": &message_id.id, "storage": &self.storage},
+                opts,
+            )
+            .await?;
+        let mut revisions = Vec::new();
+        while cursor.advance().await? {
+            let revision = cursor.deserialize_current()?;
+            revisions.push(MessageRevision::new(revision.content, revision.replaced_at));
+        }
+        Ok(revisions)
+    }
+
+    ///adds the reaction of the user with the emoji to the message, returns false if the message
+    ///already has reactions with MAX_REACTIONS_PER_MESSAGE other emojis
+    pub async fn add_reaction(&self, message_id: &ID, emoji: &str, user_id: &ID) -> Result<bool> {
+        //a concurrent insert of the same document is fine, it exists either way
+        let options = UpdateOptions::builder().upsert(true).build();
+        match self
+            .reactions
+            .update_one(
+                doc! {"_id": &message_id.id},
+                doc! {"$setOnInsert": {"storage": &self.storage, "reactions": []}},
+                options,
+            )
+            .await
+        {
+            Ok(_) => {}
+            Err(e) if is_duplicate_key(&e) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let user = to_bson(user_id)?;
+        let last_slot = format!("reactions.{}", MAX_REACTIONS_PER_MESSAGE - 1);
+        loop {
+            let added = self
+                .reactions
+                .update_one(
+                    doc! {"_id": &message_id.id, "reactions.emoji": emoji},
+                    doc! {"$addToSet": {"reactions.$.users": &user}},
+                    None,
+                )
+                .await?;
+            if added.matched_count == 1 {
+                return Ok(true);
+            }
+            let pushed = self
+                .reactions
+                .update_one(
+                    doc! {
+                        "_id": &message_id.id,
+                        "reactions.emoji": {"$ne": emoji},
+                        &last_slot: {"$exists": false},
+                    },
+                    doc! {"$push": {"reactions": {"emoji": emoji, "users": [&user]}}},
+                    None,
+                )
+                .await?;
+            if pushed.matched_count == 1 {
+                return Ok(true);
+            }
+            //either the limit is reached or another user added the emoji in the meantime
+            let added_concurrently = self
+                .reactions
+                .count_documents(doc! {"_id": &message_id.id, "reactions.emoji": emoji}, None)
+                .await?;
+            if added_concurrently == 0 {
+                return Ok(false);
+            }
+        }
+    }
+
+    ///removes the reaction of the user with the emoji from the message, returns false if the user
+    ///did not react with the emoji
+    pub async fn remove_reaction(
+        &self,
+        message_id: &ID,
+        emoji: &str,
+        user_id: &ID,
+    ) -> Result<bool> {
+        let user = to_bson(user_id)?;
+        let removed = self
+            .reactions
+            .update_one(
+                doc! {
+                    "_id": &message_id.id,
+                    "reactions": {"$elemMatch": {"emoji": emoji, "users": &user}},
+                },
+                doc! {"$pull": {"reactions.$.users": &user}},
+                None,
+            )
+            .await?;
+        //emojis without users are removed, so they don't count towards the limit
+        self.reactions
+            .update_one(
+                doc! {"_id": &message_id.id},
+                doc! {"$pull": {"reactions": {"users": {"$size": 0}}}},
+                None,
+            )
+            .await?;
+        Ok(removed.modified_count == 1)
+    }
+
+    ///attaches the reactions to the messages, me is set for the reactions of the viewer
+    pub async fn attach_reactions(&self, messages: &mut [Message], viewer: &ID) -> Result<()> {
+        let ids: Vec<String> = messages
+            .iter()
+            .map(|message| message.id.id.clone())
+            .collect();
+        let mut cursor = self
+            .reactions
+            .find(doc! {"_id": {"$in": ids}}, None)
+            .await?;
+        let mut found = HashMap::new();
+        while cursor.advance().await? {
+            let reactions = cursor.deserialize_current()?;
+            found.insert(reactions._id, reactions.reactions);
+        }
+
+        for message in messages.iter_mut() {
+            if let Some(reactions) = found.remove(&message.id.id) {
+                message.reactions = reactions
+                    .into_iter()
+                    .map(|reaction| {
+                        let me = reaction.users.contains(viewer);
+                        Reaction::new(reaction.emoji, reaction.users.len() as u32, me)
+                    })
+                    .collect();
+            }
+        }
+        Ok(())
+    }
+
+    ///fills in the author and a snippet of the replied message for all replies, replies to
+    ///messages that were deleted are left as they are
+    pub async fn attach_replies(&self, messages: &mut [Message]) -> Result<()> {
+        let ids: Vec<String> = messages
+            .iter()
+            .filter_map(|message| message.reply_to.as_ref())
+            .map(|reply| reply.message_id.id.clone())
+            .collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let parents: HashMap<String, Message> = self
+            .find_messages(&ids)
+            .await?
+            .into_iter()
+            .map(|parent| (parent.id.id.clone(), parent))
+            .collect();
+
+        for reply in messages
+            .iter_mut()
+            .filter_map(|message| message.reply_to.as_mut())
+        {
+            if let Some(parent) = parents.get(&reply.message_id.id) {
+                reply.resolve(parent);
+            }
+        }
+        Ok(())
+    }
+
+    ///returns the messages with one of the ids that exist in the store
+    async fn find_messages(&self, ids: &[String]) -> Result<Vec<Message>> {
+        let mut cursor = self
+            .blocks
+            .find(doc! {"messages.id.id": {"$in": ids}}, None)
+            .await?;
+        let mut messages = Vec::new();
+        while cursor.advance().await? {
+            let block = cursor.deserialize_current()?;
+            messages.extend(
+                block
+                    .messages
+                    .into_iter()
+                    .filter(|message| ids.contains(&message.id.id)),
+            );
+        }
+        Ok(messages)
+    }
+
+    ///returns up to limit messages on the side of the bound, all messages if there is no bound,
+    ///ordered by their sequence number. blocks are read in the order of their sequence numbers
+    ///and only until no further block can hold one of the first limit messages, so a query reads
+    ///a few blocks independent of the size of the history
+    async fn fetch(
+        &self,
+        range: Option<(&Bound, Side)>,
+        ascending: bool,
+        limit: u32,
+    ) -> Result<Vec<Message>> {
+        let limit = limit as usize;
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let filter = match range {
+            Some((bound, side)) => bound.block_filter(side)?,
+            None => doc! {},
+        };
+        let sort = if ascending {
+            doc! {"min_seq": 1}
+        } else {
+            doc! {"max_seq": -1}
+        };
+        let options = FindOptions::builder()
+            .sort(sort)
+            .batch_size((limit / BLOCK_SIZE + 2) as u32)
+            .build();
+        let mut cursor = self.blocks.find(filter, options).await?;
+
+        let mut messages: Vec<Message> = Vec::new();
+        while cursor.advance().await? {
+            let block = cursor.deserialize_current()?;
+            if let Some(last) = messages.get(limit - 1) {
+                //the remaining blocks only hold messages that come after the ones found
+                let done = if ascending {
+                    block.min_seq > last.seq
+                } else {
+                    block.max_seq < last.seq
+                };
+                if done {
+                    break;
+                }
+            }
+            let in_range =
+                |message: &Message| range.is_none_or(|(bound, side)| bound.contains(side, message));
+            messages.extend(block.messages.into_iter().filter(in_range));
+            if ascending {
+                messages.sort_by_key(|message| message.seq);
+            } else {
+                messages.sort_by_key(|message| Reverse(message.seq));
+            }
+            messages.truncate(limit);
+        }
+        Ok(messages)
+    }
+
+    ///returns whether there is at least one message on the side of the bound
+    async fn exists(&self, bound: &Bound, side: Side) -> Result<bool> {
+        Ok(!self.fetch(Some((bound, side)), true, 1).await?.is_empty())
+    }
+
+    ///returns the position of the cursor, None if the cursor points to a message that does not
+    ///exist in this store
+    async fn resolve(&self, cursor: &Cursor) -> Result<Option<Bound>> {
+        Ok(match cursor {
+            Cursor::Message(id) => self
+                .find_message(id)
+                .await?
+                .map(|message| Bound::Seq(message.seq)),
+            Cursor::Time(time) => Some(Bound::Time(*time)),
+        })
+    }
+
+    ///returns the part of the history matching the query independent of the blocks the messages
+    ///are stored in, None if the cursor of the query points to a message that does not exist
+    pub async fn query_history(
+        &self,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Option<MessageHistory>> {
+        //older messages are fetched newest first and reversed afterwards
+        let (mut older, newer, has_more_before, has_more_after) = match query {
+            HistoryQuery::Latest(limit) => {
+                let limit = limit.min(MAX_HISTORY_LIMIT);
+                let mut older = self.fetch(None, false, limit + 1).await?;
+                let has_more = older.len() > limit as usize;
+                older.truncate(limit as usize);
+                (older, Vec::new(), has_more, false)
+            }
+            HistoryQuery::Before(cursor, limit) => {
+                let limit = limit.min(MAX_HISTORY_LIMIT);
+                let bound = match self.resolve(&cursor).await? {
+                    Some(bound) => bound,
+                    None => return Ok(None),
+                };
+                let mut older = self
+                    .fetch(Some((&bound, Side::Before)), false, limit + 1)
+                    .await?;
+                let has_more = older.len() > limit as usize;
+                older.truncate(limit as usize);
+                let has_more_after = self.exists(&bound, Side::AtOrAfter).await?;
+                (older, Vec::new(), has_more, has_more_after)
+            }
+            HistoryQuery::After(cursor, limit) => {
+                let limit = limit.min(MAX_HISTORY_LIMIT);
+                let bound = match self.resolve(&cursor).await? {
+                    Some(bound) => bound,
+                    None => return Ok(None),
+                };
+                let mut newer = self
+                    .fetch(Some((&bound, Side::After)), true, limit + 1)
+                    .await?;
+                let has_more = newer.len() > limit as usize;
+                newer.truncate(limit as usize);
+                let has_more_before = self.exists(&bound, Side::AtOrBefore).await?;
+                (Vec::new(), newer, has_more_before, has_more)
+            }
+            HistoryQuery::Around(cursor, limit) => {
+                let limit = limit.min(MAX_HISTORY_LIMIT);
+                let bound = match self.resolve(&cursor).await? {
+                    Some(bound) => bound,
+                    None => return Ok(None),
+                };
+                //the message the cursor points to is the first of the newer messages
+                let before = limit / 2;
+                let after = limit - before;
+                let mut older = self
+                    .fetch(Some((&bound, Side::Before)), false, before + 1)
+                    .await?;
+                let mut newer = self
+                    .fetch(Some((&bound, Side::AtOrAfter)), true, after + 1)
+                    .await?;
+                let has_more_before = older.len() > before as usize;
+                let has_more_after = newer.len() > after as usize;
+                older.truncate(before as usize);
+                newer.truncate(after as usize);
+                (older, newer, has_more_before, has_more_after)
+            }
+        };
+
+        older.reverse();
+        older.extend(newer);
+        let mut messages = older;
+        if order == HistoryOrder::NewestFirst {
+            messages.reverse();
+        }
+        Ok(Some(MessageHistory {
+            messages,
+            has_more_before,
+            has_more_after,
+        }))
+    }
+
+    ///returns the number of messages sent by users per storage of the database that are newer
+    ///than the sequence number given for the storage, deleted messages and messages sent by the
+    ///server are not counted. all storages are counted in one aggregation, storages without such
+    ///messages are missing from the result
+    pub async fn count_after(
+        database: &Database,
+        after: &HashMap<String, u64>,
+    ) -> Result<HashMap<String, u64>> {
+        let mut pipelines = Vec::new();
+        for (storage, seq) in after {
+            let pipeline = vec![
+                doc! {"$match": Bound::Seq(*seq).block_filter(Side::After)?},
+                doc! {"$unwind": "$messages"},
+                doc! {"$match": {
+                    "messages.seq": {"$gt": *seq as i64},
+                    "messages.author_id": {"$ne": null},
+                }},
+                doc! {"$group": {"_id": {"$literal": storage}, "count": {"$sum": 1_i64}}},
+            ];
+            pipelines.push((storage, pipeline));
+        }
+        let (first, mut pipeline) = match pipelines.pop() {
+            Some(first) => first,
+            None => return Ok(HashMap::new()),
+        };
+        for (storage, other) in pipelines {
+            pipeline.push(doc! {"$unionWith": {"coll": storage, "pipeline": other}});
+        }
+
+        let blocks: Collection<Block> = database.collection(first);
+        let mut cursor = blocks.aggregate(pipeline, None).await?;
+        let mut counts = HashMap::new();
+        while cursor.advance().await? {
+            let group = cursor.deserialize_current()?;
+            counts.insert(
+                group.get_str("_id")?.to_string(),
+                group.get_i64("count")? as u64,
+            );
+        }
+        Ok(counts)
+    }
+
+    ///drops all blocks, the edit history, the reactions and the search entries of the store
+    pub async fn drop(&self) -> Result<()> {
+        self.blocks.drop(None).await?;
+        self.sequences
+            .delete_one(doc! {"_id": &self.storage}, None)
+            .await?;
+        self.reactions
+            .delete_many(doc! {"storage": &self.storage}, None)
+            .await?;
+        self.search.forget(&self.storage).await?;
+        self.history
+            .delete_many(doc! {"storage": &self.storage}, None)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use common::messages::Reply;
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_edit_message() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MESSAGE_STORE1");
+        db.drop(None).await.unwrap();
+        let store = MessageStore::new(&db, "TEST_CHANNEL");
+
+        let message = Message::new("typo".to_string(), "SERVER".to_string());
+        store.append(message.clone()).await.unwrap();
+        let mentioned = ID::new("123123123123123123123123".to_string()).unwrap();
+        let first_edit = Message {
+            content: "first edit @Max".to_string(),
+            mentions: vec![mentioned.clone()],
+            ..message.clone()
+        };
+        assert!(store.edit_message(&message, &first_edit).await.unwrap());
+        //the content was changed in the meantime
+        let second_edit = Message {
+            content: "second edit".to_string(),
+            ..message.clone()
+        };
+        assert!(!store.edit_message(&message, &second_edit).await.unwrap());
+
+        let edited = store.find_message(&message.id).await.unwrap().unwrap();
+        let history = store.get_edit_history(&message.id).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(edited.content, "first edit @Max");
+        assert_eq!(edited.mentions, vec![mentioned]);
+        assert!(edited.edited_at.is_some());
+        assert_eq!(edited.created_at, message.created_at);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "typo");
+    }
+
+    #[test]
+    async fn test_delete_message() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MESSAGE_STORE2");
+        db.drop(None).await.unwrap();
+        let store = MessageStore::new(&db, "TEST_CHANNEL");
+
+        let first = store
+            .append(Message::new("first".to_string(), "SERVER".to_string()))
+            .await
+            .unwrap();
+        let second = store
+            .append(Message::new("second".to_string(), "SERVER".to_string()))
+            .await
+            .unwrap();
+
+        assert!(store.delete_message(&first.id).await.unwrap());
+        assert!(!store.delete_message(&first.id).await.unwrap());
+
+        let block = store.get_block(0).await.unwrap().unwrap();
+        db.drop(None).await.unwrap();
+        assert_eq!(block.messages, vec![second]);
+    }
+
+    #[test]
+    async fn test_query_history() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MESSAGE_STORE3");
+        db.drop(None).await.unwrap();
+        let store = MessageStore::new(&db, "TEST_CHANNEL");
+
+        //spans multiple blocks
+        let mut messages = Vec::new();
+        for i in 0..120 {
+            let message = Message::new(i.to_string(), "SERVER".to_string());
+            store.append(message.clone()).await.unwrap();
+            messages.push(message);
+        }
+        let contents = |history: &MessageHistory| -> Vec<String> {
+            history.messages.iter().map(|m| m.content.clone()).collect()
+        };
+        let range =
+            |from: u32, to: u32| -> Vec<String> { (from..to).map(|i| i.to_string()).collect() };
+
+        let latest = store
+            .query_history(HistoryQuery::Latest(10), HistoryOrder::OldestFirst)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(contents(&latest), range(110, 120));
+        assert!(latest.has_more_before);
+        assert!(!latest.has_more_after);
+
+        let cursor = Cursor::Message(messages[55].id.clone());
+        let before = store
+            .query_history(
+                HistoryQuery::Before(cursor.clone(), 10),
+                HistoryOrder::NewestFirst,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let mut expected = range(45, 55);
+        expected.reverse();
+        assert_eq!(contents(&before), expected);
+        assert!(before.has_more_before);
+        assert!(before.has_more_after);
+
+        let after = store
+            .query_history(
+                HistoryQuery::After(cursor.clone(), 100),
+                HistoryOrder::OldestFirst,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(contents(&after), range(56, 120));
+        assert!(after.has_more_before);
+        assert!(!after.has_more_after);
+
+        let around = store
+            .query_history(HistoryQuery::Around(cursor, 4), HistoryOrder::OldestFirst)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(contents(&around), range(53, 57));
+
+        let time = Cursor::Time(messages[0].created_at - std::time::Duration::from_secs(1));
+        let first = store
+            .query_history(HistoryQuery::After(time, 3), HistoryOrder::OldestFirst)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(contents(&first), range(0, 3));
+        assert!(!first.has_more_before);
+
+        let unknown = Cursor::Message(ID::new("123123123123123123123123".to_string()).unwrap());
+        assert!(store
+            .query_history(HistoryQuery::Before(unknown, 10), HistoryOrder::OldestFirst)
+            .await
+            .unwrap()
+            .is_none());
+        db.drop(None).await.unwrap();
+    }
+
+    #[test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_append() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MESSAGE_STORE4");
+        db.drop(None).await.unwrap();
+        let store = MessageStore::new(&db, "TEST_CHANNEL");
+
+        let count = 2000;
+        let mut handles = Vec::new();
+        for i in 0..count {
+            let store = store.clone();
+            handles.push(tokio::spawn(async move {
+                let message = Message::new(i.to_string(), "SERVER".to_string());
+                store.append(message).await.unwrap()
+            }));
+        }
+        let mut seqs = Vec::new();
+        for handle in handles {
+            seqs.push(handle.await.unwrap().seq);
+        }
+
+        //block ids have no gaps and no block holds more than BLOCK_SIZE messages
+        let mut contents = Vec::new();
+        let mut block_id = 0;
+        while let Some(block) = store.get_block(block_id).await.unwrap() {
+            assert!(block.messages.len() <= BLOCK_SIZE);
+            contents.extend(block.messages.into_iter().map(|m| m.content));
+            block_id += 1;
+        }
+        let blocks = store.blocks.count_documents(None, None).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(blocks, block_id as u64);
+        seqs.sort();
+        assert_eq!(seqs, (1..=count as u64).collect::<Vec<u64>>());
+        contents.sort();
+        let mut expected: Vec<String> = (0..count).map(|i| i.to_string()).collect();
+        expected.sort();
+        assert_eq!(contents, expected);
+    }
+
+    #[test]
+    async fn test_reactions() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MESSAGE_STORE5");
+        db.drop(None).await.unwrap();
+        let store = MessageStore::new(&db, "TEST_CHANNEL");
+        let alice = ID::new("123123123123123123123123".to_string()).unwrap();
+        let bob = ID::new("123123123123123123123124".to_string()).unwrap();
+
+        let message = store
+            .append(Message::new(
+                "react to me".to_string(),
+                "SERVER".to_string(),
+            ))
+            .await
+            .unwrap();
+        assert!(store.add_reaction(&message.id, "👍", &alice).await.unwrap());
+        assert!(store.add_reaction(&message.id, "👍", &bob).await.unwrap());
+        //reacting twice with the same emoji has no effect
+        assert!(store.add_reaction(&message.id, "👍", &bob).await.unwrap());
+        assert!(store.add_reaction(&message.id, "🎉", &bob).await.unwrap());
+
+        let mut messages = vec![message.clone()];
+        store.attach_reactions(&mut messages, &alice).await.unwrap();
+        assert_eq!(
+            messages[0].reactions,
+            vec![
+                Reaction::new("👍".to_string(), 2, true),
+                Reaction::new("🎉".to_string(), 1, false),
+            ]
+        );
+
+        assert!(store
+            .remove_reaction(&message.id, "🎉", &bob)
+            .await
+            .unwrap());
+        assert!(!store
+            .remove_reaction(&message.id, "🎉", &bob)
+            .await
+            .unwrap());
+
+        //the removed emoji no longer counts towards the limit
+        for i in 1..MAX_REACTIONS_PER_MESSAGE {
+            assert!(store
+                .add_reaction(&message.id, &i.to_string(), &alice)
+                .await
+                .unwrap());
+        }
+        assert!(!store.add_reaction(&message.id, "🎉", &bob).await.unwrap());
+        assert!(store.add_reaction(&message.id, "1", &bob).await.unwrap());
+
+        let mut messages = vec![message];
+        store.attach_reactions(&mut messages, &bob).await.unwrap();
+        db.drop(None).await.unwrap();
+        assert_eq!(messages[0].reactions.len(), MAX_REACTIONS_PER_MESSAGE);
+        assert_eq!(
+            messages[0].reactions[1],
+            Reaction::new("1".to_string(), 2, true)
+        );
+    }
+
+    #[test]
+    async fn test_block_ranges() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_MESSAGE_STORE6");
+        db.drop(None).await.unwrap();
+        let store = MessageStore::new(&db, "TEST_CHANNEL");
+
+        let mut messages = Vec::new();
+        for i in 0..(3 * BLOCK_SIZE) {
+            let message = Message::new(i.to_string(), "SERVER".to_string());
+            messages.push(store.append(message).await.unwrap());
+        }
+        let block = store.get_block(1).await.unwrap().unwrap();
+        assert_eq!(block.min_seq, BLOCK_SIZE as u64 + 1);
+        assert_eq!(block.max_seq, 2 * BLOCK_SIZE as u64);
+        assert_eq!(block.min_created_at, Some(messages[BLOCK_SIZE].created_at));
+
+        //the emptied block is passed without ending the query early
+        for message in &messages[BLOCK_SIZE..2 * BLOCK_SIZE] {
+            assert!(store.delete_message(&message.id).await.unwrap());
+        }
+        let cursor = Cursor::Message(messages[2 * BLOCK_SIZE].id.clone());
+        let before = store
+            .query_history(HistoryQuery::Before(cursor, 3), HistoryOrder::OldestFirst)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut replies = vec![messages[2 * BLOCK_SIZE].clone(), messages[0].clone()];
+        replies[0].reply_to = Some(Reply::new(messages[0].id.clone()));
+        store.attach_replies(&mut replies).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        let contents: Vec<&str> = before.messages.iter().map(|m| m.content.as_str()).collect();
+        let expected: Vec<String> = (BLOCK_SIZE - 3..BLOCK_SIZE)
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(contents, expected);
+        assert!(before.has_more_before);
+        assert!(before.has_more_after);
+        assert_eq!(
+            replies[0].reply_to.as_ref().unwrap().author,
+            Some("SERVER".to_string())
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+use common::{
+    error::ServerError,
+    events::EventKind,
+    id::ID,
+    messages::Response,
+    user::{
+        validate_custom_status, CustomStatus, Presence, RelationshipKind, User, MAX_PRESENCE_QUERY,
+    },
+};
+use log::error;
+use mongodb::{bson::oid::ObjectId, Client, Database};
+use std::time::Duration;
+
+use crate::dm_handler::DM_DATABASE;
+use crate::events::EventFeed;
+use crate::friend_handler::Relationships;
+use crate::server_handler::ServerHandler;
+use crate::user::UserHandler;
+
+///interval in which presence changes that happen without a request, like becoming idle, are
+///announced
+const PRESENCE_UPDATE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct PresenceHandler;
+
+impl PresenceHandler {
+    ///returns the users that are notified about presence changes of the user: the friends of the
+    ///user and the members of all servers the user is a member of
+    async fn audience(client: &Client, dms: &Database, user_id: &ID) -> Result<Vec<ID>> {
+        let mut audience = ServerHandler::get_co_members(client, user_id).await?;
+        for entry in Relationships::new(dms).list(user_id).await? {
+            if entry.kind == RelationshipKind::Friend && !audience.contains(&entry.other_id) {
+                audience.push(entry.other_id);
+            }
+        }
+        Ok(audience)
+    }
+
+    ///notifies the audience of the user about the presence of the user
+    async fn announce(
+        client: &Client,
+        dms: &Database,
+        user_handler: &UserHandler,
+        user: User,
+    ) -> Result<()> {
+        let oid = ObjectId::parse_str(&user.id.id)?;
+        let presence = user.presence;
+        let audience = Self::audience(client, dms, &user.id).await?;
+        EventFeed::new(user_handler.database())
+            .push(&audience, &EventKind::PresenceChanged(user))
+            .await?;
+        user_handler.set_shown_presence(oid, presence).await
+    }
+
+    ///sets the presence and the custom status of the user and notifies the friends of the user
+    ///and the members of shared servers. returns bad request if the presence is offline and
+    ///invalid name if the custom status text is invalid
+    pub async fn set_presence(
+        client: &Client,
+        dms: &Database,
+        user_handler: &UserHandler,
+        user_id: &ID,
+        presence: Presence,
+        custom_status: Option<CustomStatus>,
+    ) -> Result<Response> {
+        if presence == Presence::Offline {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        if let Some(status) = &custom_status {
+            if let Err(e) = validate_custom_status(&status.text) {
+                return Ok(Response::Error(ServerError::InvalidName(e)));
+            }
+        }
+        let oid = ObjectId::parse_str(&user_id.id)?;
+        user_handler
+            .set_presence(oid, presence, custom_status)
+            .await?;
+        let user = match user_handler.get_user(oid).await? {
+            Some(user) => user,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        Self::announce(client, dms, user_handler, user).await?;
+        Ok(Response::Success)
+    }
+
+    ///returns the presence of the users, unknown users are left out
+    ///returns limit exceeded if more than MAX_PRESENCE_QUERY users are requested
+    pub async fn get_presence(user_handler: &UserHandler, user_ids: Vec<ID>) -> Result<Response> {
+        if user_ids.len() > MAX_PRESENCE_QUERY {
+            return Ok(Response::Error(ServerError::LimitExceeded));
+        }
+        let oids = match user_ids
+            .iter()
+            .map(|id| ObjectId::parse_str(&id.id))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(oids) => oids,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        Ok(Response::Presences(user_handler.get_users(&oids).await?))
+    }
+
+    ///returns the events of the user after the event, returns bad request if the id of the event
+    ///is invalid
+    pub async fn get_events(
+        user_handler: &UserHandler,
+        user_id: &ID,
+        after: Option<ID>,
+    ) -> Result<Response> {
+        let after = match after.map(|id| ObjectId::parse_str(id.id)).transpose() {
+            Ok(after) => after,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let events = EventFeed::new(user_handler.database())
+            .after(user_id, after)
+            .await?;
+        Ok(Response::Events(events))
+    }
+
+    ///announces the presence of all users whose presence changed without a request since it was
+    ///announced last, e.g. because they became idle or signed out
+    pub async fn announce_presence_changes(
+        client: &Client,
+        dms: &Database,
+        user_handler: &UserHandler,
+    ) -> Result<()> {
+        for user in user_handler.get_unannounced_presence_changes().await? {
+            Self::announce(client, dms, user_handler, user).await?;
+        }
+        Ok(())
+    }
+
+    ///announces presence changes every PRESENCE_UPDATE_INTERVAL, never returns
+    pub async fn run_presence_updates(client: Client, user_handler: UserHandler) {
+        let dms = client.database(DM_DATABASE);
+        let mut interval = tokio::time::interval(PRESENCE_UPDATE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = Self::announce_presence_changes(&client, &dms, &user_handler).await {
+                error!("failed to announce presence changes: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::friend_handler::FriendHandler;
+    use crate::mongodb::connect_mongo;
+    use crate::user::create_users;
+    use common::events::Event;
+    use tokio::test;
+
+    use super::*;
+
+    async fn events(user_handler: &UserHandler, user_id: &ID) -> Vec<Event> {
+        match PresenceHandler::get_events(user_handler, user_id, None)
+            .await
+            .unwrap()
+        {
+            Response::Events(events) => events,
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    async fn test_set_presence() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_PRESENCE1");
+        db.drop(None).await.unwrap();
+        let (users, ids) = create_users(&client, &db, &["Max", "Moritz", "Malte"]).await;
+        for id in &ids {
+            users
+                .set_user_status(ObjectId::parse_str(&id.id).unwrap(), true)
+                .await
+                .unwrap();
+        }
+        let (max, moritz, malte) = (&ids[0], &ids[1], &ids[2]);
+        assert!(FriendHandler::add_friend(&db, &users, max, moritz)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(FriendHandler::accept_friend(&db, moritz, max)
+            .await
+            .unwrap()
+            .succeeded());
+
+        let status = CustomStatus::new("on vacation".to_string(), None);
+        assert!(PresenceHandler::set_presence(
+            &client,
+            &db,
+            &users,
+            max,
+            Presence::DoNotDisturb,
+            Some(status.clone())
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let offline =
+            PresenceHandler::set_presence(&client, &db, &users, max, Presence::Offline, None)
+                .await
+                .unwrap();
+        let empty_status = CustomStatus::new(" ".to_string(), None);
+        let invalid = PresenceHandler::set_presence(
+            &client,
+            &db,
+            &users,
+            max,
+            Presence::Online,
+            Some(empty_status),
+        )
+        .await
+        .unwrap();
+        let presences = match PresenceHandler::get_presence(&users, vec![max.clone()])
+            .await
+            .unwrap()
+        {
+            Response::Presences(presences) => presences,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let moritz_events = events(&users, moritz).await;
+        let malte_events = events(&users, malte).await;
+
+        //signing out is announced by the presence updates
+        users
+            .set_user_status(ObjectId::parse_str(&max.id).unwrap(), false)
+            .await
+            .unwrap();
+        PresenceHandler::announce_presence_changes(&client, &db, &users)
+            .await
+            .unwrap();
+        let after_signout =
+            match PresenceHandler::get_events(&users, moritz, Some(moritz_events[0].id.clone()))
+                .await
+                .unwrap()
+            {
+                Response::Events(events) => events,
+                other => panic!("unexpected response: {:?}", other),
+            };
+        db.drop(None).await.unwrap();
+
+        assert!(matches!(offline, Response::Error(ServerError::BadRequest)));
+        assert!(matches!(
+            invalid,
+            Response::Error(ServerError::InvalidName(_))
+        ));
+        assert_eq!(presences[0].presence, Presence::DoNotDisturb);
+        assert_eq!(presences[0].custom_status, Some(status));
+        assert_eq!(moritz_events.len(), 1);
+        assert!(matches!(
+            &moritz_events[0].kind,
+            EventKind::PresenceChanged(user) if user.presence == Presence::DoNotDisturb
+        ));
+        assert!(malte_events.is_empty());
+        assert_eq!(after_signout.len(), 1);
+        assert!(matches!(
+            &after_signout[0].kind,
+            EventKind::PresenceChanged(user) if user.presence == Presence::Offline
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::Result;
+use common::{
+    error::{NameError, ServerError},
+    file::validate_image,
+    id::ID,
+    messages::Response,
+    user::{
+        validate_bio, validate_display_name, validate_pronouns, ImageUpdate, ProfileImage,
+        ProfileUpdate,
+    },
+};
+use mongodb::bson::oid::ObjectId;
+
+use crate::blob_store::BlobStore;
+use crate::user::UserHandler;
+
+pub struct ProfileHandler;
+
+impl ProfileHandler {
+    ///applies the change of a text field of the profile, empty texts remove the field
+    fn update_text(
+        field: &mut Option<String>,
+        change: Option<String>,
+        validate: impl Fn(&str) -> Result<(), ServerError>,
+    ) -> Result<(), ServerError> {
+        match change {
+            None => {}
+            Some(text) if text.is_empty() => *field = None,
+            Some(text) => {
+                validate(&text)?;
+                *field = Some(text);
+            }
+        }
+        Ok(())
+    }
+
+    ///checks the change of an image of the profile, returns the new image if one is set
+    fn check_image(
+        kind: ProfileImage,
+        change: &Option<ImageUpdate>,
+    ) -> Result<Option<&[u8]>, ServerError> {
+        match change {
+            Some(ImageUpdate::Set(bytes)) => match validate_image(bytes, kind.max_size()) {
+                Ok(_) => Ok(Some(bytes)),
+                Err(e) => Err(ServerError::InvalidFile(e)),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    ///returns the profile of the user, returns bad request if the user doesn't exist
+    pub async fn get_profile(user_handler: &UserHandler, user_id: &ID) -> Result<Response> {
+        let oid = match ObjectId::parse_str(&user_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        match user_handler.get_profile(oid).await? {
+            Some(profile) => Ok(Response::Profile(profile)),
+            None => Ok(Response::Error(ServerError::BadRequest)),
+        }
+    }
+
+    ///applies the changes to the profile of the user, nothing is changed if one of them is
+    ///invalid. returns invalid name for invalid display names and pronouns, invalid message for
+    ///invalid bios and invalid file for images that are too large or of an unsupported format
+    pub async fn update_profile(
+        user_handler: &UserHandler,
+        blobs: &BlobStore,
+        user_id: &ID,
+        update: ProfileUpdate,
+    ) -> Result<Response> {
+        let oid = ObjectId::parse_str(&user_id.id)?;
+        let mut profile = match user_handler.get_stored_profile(oid).await? {
+            Some(profile) => profile,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+
+        let invalid_name = |e: NameError| ServerError::InvalidName(e);
+        let checked = Self::update_text(&mut profile.display_name, update.display_name, |name| {
+            validate_display_name(name).map_err(invalid_name)
+        })
+        .and_then(|_| {
+            Self::update_text(&mut profile.pronouns, update.pronouns, |pronouns| {
+                validate_pronouns(pronouns).map_err(invalid_name)
+            })
+        })
+        .and_then(|_| {
+            Self::update_text(&mut profile.bio, update.bio, |bio| {
+                validate_bio(bio).map_err(ServerError::InvalidMessage)
+            })
+        })
+        .and_then(|_| {
+            let avatar = Self::check_image(ProfileImage::Avatar, &update.avatar)?;
+            let banner = Self::check_image(ProfileImage::Banner, &update.banner)?;
+            Ok((avatar, banner))
+        });
+        let (avatar, banner) = match checked {
+            Ok(images) => images,
+            Err(e) => return Ok(Response::Error(e)),
+        };
+
+        //images are only stored once all changes are valid
+        match avatar {
+            Some(bytes) => profile.avatar = Some(blobs.put(bytes).await?),
+            None if matches!(update.avatar, Some(ImageUpdate::Remove)) => profile.avatar = None,
+            None => {}
+        }
+        match banner {
+            Some(bytes) => profile.banner = Some(blobs.put(bytes).await?),
+            None if matches!(update.banner, Some(ImageUpdate::Remove)) => profile.banner = None,
+            None => {}
+        }
+        user_handler.set_profile(oid, &profile).await?;
+        Ok(Response::Success)
+    }
+
+    ///returns the avatar or banner of the user, returns bad request if the user doesn't exist or
+    ///has no such image
+    pub async fn get_profile_image(
+        user_handler: &UserHandler,
+        blobs: &BlobStore,
+        user_id: &ID,
+        image: ProfileImage,
+    ) -> Result<Response> {
+        let oid = match ObjectId::parse_str(&user_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let profile = match user_handler.get_stored_profile(oid).await? {
+            Some(profile) => profile,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let hash = match image {
+            ProfileImage::Avatar => profile.avatar,
+            ProfileImage::Banner => profile.banner,
+        };
+        match hash {
+            Some(hash) => match blobs.get(&hash).await? {
+                Some(bytes) => Ok(Response::Image(bytes)),
+                None => Ok(Response::Error(ServerError::InternalServerError)),
+            },
+            None => Ok(Response::Error(ServerError::BadRequest)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use common::error::{ContentError, FileError};
+    use common::user::MAX_BIO_LENGTH;
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_update_profile() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_PROFILES1");
+        db.drop(None).await.unwrap();
+        let root = std::env::temp_dir().join("TEST_PROFILES1");
+        let blobs = BlobStore::new(&root);
+        let users = UserHandler::from_names(&client, db.name(), "users");
+        let oid = users
+            .create_new_user("Max".to_string(), "Passwort".to_string(), true)
+            .await
+            .unwrap();
+        let max = ID::new(oid.to_hex()).unwrap();
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+
+        let update = ProfileUpdate {
+            display_name: Some("Max M.".to_string()),
+            bio: Some("first line\nsecond line".to_string()),
+            pronouns: Some("he/him".to_string()),
+            avatar: Some(ImageUpdate::Set(png.clone())),
+            banner: None,
+        };
+        assert!(ProfileHandler::update_profile(&users, &blobs, &max, update)
+            .await
+            .unwrap()
+            .succeeded());
+        //invalid changes don't change anything
+        let update = ProfileUpdate {
+            pronouns: Some(String::new()),
+            bio: Some("a".repeat(MAX_BIO_LENGTH + 1)),
+            ..Default::default()
+        };
+        let long_bio = ProfileHandler::update_profile(&users, &blobs, &max, update)
+            .await
+            .unwrap();
+        let update = ProfileUpdate {
+            banner: Some(ImageUpdate::Set(b"<svg></svg>".to_vec())),
+            ..Default::default()
+        };
+        let svg = ProfileHandler::update_profile(&users, &blobs, &max, update)
+            .await
+            .unwrap();
+        let profile = match ProfileHandler::get_profile(&users, &max).await.unwrap() {
+            Response::Profile(profile) => profile,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let avatar = ProfileHandler::get_profile_image(&users, &blobs, &max, ProfileImage::Avatar)
+            .await
+            .unwrap();
+        let banner = ProfileHandler::get_profile_image(&users, &blobs, &max, ProfileImage::Banner)
+            .await
+            .unwrap();
+
+        let update = ProfileUpdate {
+            display_name: Some(String::new()),
+            avatar: Some(ImageUpdate::Remove),
+            ..Default::default()
+        };
+        assert!(ProfileHandler::update_profile(&users, &blobs, &max, update)
+            .await
+            .unwrap()
+            .succeeded());
+        let removed = match ProfileHandler::get_profile(&users, &max).await.unwrap() {
+            Response::Profile(profile) => profile,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        db.drop(None).await.unwrap();
+        let _ = tokio::fs::remove_dir_all(&root).await;
+
+        assert!(matches!(
+            long_bio,
+            Response::Error(ServerError::InvalidMessage(ContentError::TooLong(
+                MAX_BIO_LENGTH
+            )))
+        ));
+        assert!(matches!(
+            svg,
+            Response::Error(ServerError::InvalidFile(FileError::UnsupportedFormat))
+        ));
+        assert_eq!(profile.display_name, Some("Max M.".to_string()));
+        assert_eq!(profile.pronouns, Some("he/him".to_string()));
+        assert_eq!(profile.bio, Some("first line\nsecond line".to_string()));
+        assert_eq!(profile.avatar, Some(BlobStore::hash(&png)));
+        assert_eq!(profile.banner, None);
+        assert_eq!(profile.user.username, "Max");
+        assert!(matches!(avatar, Response::Image(bytes) if bytes == png));
+        assert!(matches!(banner, Response::Error(ServerError::BadRequest)));
+        assert_eq!(removed.display_name, None);
+        assert_eq!(removed.avatar, None);
+        assert_eq!(removed.pronouns, Some("he/him".to_string()));
+    }
+}
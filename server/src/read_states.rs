@@ -0,0 +1,124 @@
+use anyhow::Result;
+use common::id::ID;
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{IndexOptions, UpdateOptions},
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+///the last message a user read in a storage, stored in the read_states collection of the server
+#[derive(Serialize, Deserialize, Debug)]
+struct ReadState {
+    user_id: ID,
+    ///name of the collection the messages are stored in, the id of the channel or thread
+    storage: String,
+    ///sequence number of the last read message
+    last_read: u64,
+}
+
+///the read states of all users of a server
+#[derive(Clone)]
+pub struct ReadStates {
+    states: Collection<ReadState>,
+}
+
+impl ReadStates {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            states: database.collection("read_states"),
+        }
+    }
+
+    ///creates the index that keeps one read state per user and storage, has to be called once
+    ///for every server
+    pub async fn create_indexes(&self) -> Result<()> {
+        //creating an index that already exists has no effect
+        let index = IndexModel::builder()
+            .keys(doc! {"user_id.id": 1, "storage": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.states.create_index(index, None).await?;
+        Ok(())
+    }
+
+    ///marks all messages of the storage up to the sequence number as read by the user, the read
+    ///state never moves backwards
+    pub async fn ack(&self, user_id: &ID, storage: &str, seq: u64) -> Result<()> {
+        let opts = UpdateOptions::builder().upsert(true).build();
+        self.states
+            .update_one(
+                doc! {"user_id.id": &user_id.id, "storage": storage},
+                doc! {
+                    "$max": {"last_read": seq as i64},
+                    "$setOnInsert": {"user_id": to_bson(user_id)?},
+                },
+                opts,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///returns the sequence number of the last read message of the user for each of the storages,
+    ///storages the user never read are left out
+    pub async fn last_read(
+        &self,
+        user_id: &ID,
+        storages: &[String],
+    ) -> Result<HashMap<String, u64>> {
+        let mut cursor = self
+            .states
+            .find(
+                doc! {"user_id.id": &user_id.id, "storage": {"$in": storages}},
+                None,
+            )
+            .await?;
+        let mut read = HashMap::new();
+        while cursor.advance().await? {
+            let state = cursor.deserialize_current()?;
+            read.insert(state.storage, state.last_read);
+        }
+        Ok(read)
+    }
+
+    ///removes the read states of all users for the storage
+    pub async fn forget(&self, storage: &str) -> Result<()> {
+        self.states
+            .delete_many(doc! {"storage": storage}, None)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_read_states() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_READ_STATES1");
+        db.drop(None).await.unwrap();
+        let states = ReadStates::new(&db);
+        states.create_indexes().await.unwrap();
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let moritz = ID::new("123123123123123123123124".to_string()).unwrap();
+        let storages = vec!["CHANNEL1".to_string(), "CHANNEL2".to_string()];
+
+        states.ack(&max, "CHANNEL1", 5).await.unwrap();
+        //acknowledging an older message doesn't move the read state back
+        states.ack(&max, "CHANNEL1", 3).await.unwrap();
+        states.ack(&moritz, "CHANNEL2", 1).await.unwrap();
+        let read = states.last_read(&max, &storages).await.unwrap();
+        states.forget("CHANNEL2").await.unwrap();
+        let moritz_read = states.last_read(&moritz, &storages).await.unwrap();
+        db.drop(None).await.unwrap();
+
+        assert_eq!(read, HashMap::from([("CHANNEL1".to_string(), 5)]));
+        assert!(moritz_read.is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, Result};
+use common::{
+    id::ID,
+    messages::{Message, SearchFilters, SearchResult, SEARCH_PAGE_SIZE},
+};
+use mongodb::{
+    bson::{doc, to_bson},
+    options::FindOptions,
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+///maximum number of characters of the snippet returned with a search result
+const SNIPPET_LENGTH: usize = 100;
+
+///a message as it is stored in the search collection of a server
+#[derive(Serialize, Deserialize, Debug)]
+struct SearchEntry {
+    ///id of the message
+    _id: String,
+    ///name of the collection the message is stored in, the id of the channel or thread
+    storage: String,
+    author_id: Option<ID>,
+    content: String,
+    mentions: Vec<ID>,
+    #[serde(default)]
+    mentions_everyone: bool,
+    created_at: SystemTime,
+}
+
+///full text index over the messages of all channels and threads of a server, backed by a text
+///index on the search collection of the server, messages sent by the server are not indexed
+#[derive(Clone)]
+pub struct SearchIndex {
+    entries: Collection<SearchEntry>,
+}
+
+impl SearchIndex {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            entries: database.collection("search"),
+        }
+    }
+
+    ///creates the text index searches use, has to be called once for every server
+    pub async fn create_indexes(&self) -> Result<()> {
+        //creating an index that already exists has no effect
+        let index = IndexModel::builder().keys(doc! {"content": "text"}).build();
+        self.entries.create_index(index, None).await?;
+        Ok(())
+    }
+
+    ///adds the message stored in the storage to the index
+    pub async fn add(&self, message: &Message, storage: &str) -> Result<()> {
+        if message.author_id.is_none() {
+            return Ok(());
+        }
+        let entry = SearchEntry {
+            _id: message.id.id.clone(),
+            storage: storage.to_string(),
+            author_id: message.author_id.clone(),
+            content: message.content.clone(),
+            mentions: message.mentions.clone(),
+            mentions_everyone: message.mentions_everyone,
+            created_at: message.created_at,
+        };
+        self.entries.insert_one(entry, None).await?;
+        Ok(())
+    }
+
+    ///replaces the indexed content and mentions of the message with the ones of the edited
+    ///message
+    pub async fn update(&self, message: &Message) -> Result<()> {
+        self.entries
+            .update_one(
+                doc! {"_id": &message.id.id},
+                doc! {"$set": {
+                    "content": &message.content,
+                    "mentions": to_bson(&message.mentions)?,
+                    "mentions_everyone": message.mentions_everyone,
+                }},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///removes the message from the index
+    pub async fn remove(&self, message_id: &ID) -> Result<()> {
+        self.entries
+            .delete_one(doc! {"_id": &message_id.id}, None)
+            .await?;
+        Ok(())
+    }
+
+    ///removes all messages stored in the storage from the index
+    pub async fn forget(&self, storage: &str) -> Result<()> {
+        self.entries
+            .delete_many(doc! {"storage": storage}, None)
+            .await?;
+        Ok(())
+    }
+
+    ///returns the page of the messages matching the query and the filters, the best matches first,
+    ///and whether there are more pages. only messages stored in one of the storages are found,
+    ///if pinned is Some only the messages with these ids are found. the channel filter has to be
+    ///applied by the caller by passing only the storages of the channel
+    pub async fn search(
+        &self,
+        query: &str,
+        storages: Vec<String>,
+        filters: &SearchFilters,
+        pinned: Option<Vec<String>>,
+        page: u32,
+    ) -> Result<(Vec<SearchResult>, bool)> {
+        let mut filter = doc! {
+            "$text": {"$search": query},
+            "storage": {"$in": storages},
+        };
+        if let Some(author) = &filters.author {
+            filter.insert("author_id.id", &author.id);
+        }
+        let mut created_at = doc! {};
+        if let Some(after) = &filters.after {
+            created_at.insert("$gte", to_bson(after)?);
+        }
+        if let Some(before) = &filters.before {
+            created_at.insert("$lt", to_bson(before)?);
+        }
+        if !created_at.is_empty() {
+            filter.insert("created_at", created_at);
+        }
+        if filters.has_mention {
+            filter.insert(
+                "$or",
+                vec![
+                    doc! {"mentions.0": {"$exists": true}},
+                    doc! {"mentions_everyone": true},
+                ],
+            );
+        }
+        if let Some(ids) = pinned {
+            filter.insert("_id", doc! {"$in": ids});
+        }
+
+        let opts = FindOptions::builder()
+            .projection(doc! {"score": {"$meta": "textScore"}})
+            .sort(doc! {"score": {"$meta": "textScore"}, "_id": -1})
+            .skip(page as u64 * SEARCH_PAGE_SIZE as u64)
+            .limit(SEARCH_PAGE_SIZE as i64 + 1)
+            .build();
+        let mut cursor = self.entries.find(filter, opts).await?;
+        let mut results = Vec::new();
+        while cursor.advance().await? {
+            let entry = cursor.deserialize_current()?;
+            results.push(SearchResult {
+                message_id: ID::new(entry._id).ok_or(anyhow!("invalid message id"))?,
+                channel: ID::new(entry.storage).ok_or(anyhow!("invalid storage name"))?,
+                author_id: entry.author_id,
+                created_at: entry.created_at,
+                snippet: snippet(&entry.content, query),
+            });
+        }
+        let has_more = results.len() > SEARCH_PAGE_SIZE;
+        results.truncate(SEARCH_PAGE_SIZE);
+        Ok((results, has_more))
+    }
+}
+
+///returns up to SNIPPET_LENGTH characters of the content around the first word of the query that
+///occurs in the content, the start of the content if no word occurs
+fn snippet(content: &str, query: &str) -> String {
+    let fold = |text: &str| -> Vec<char> {
+        text.chars()
+            .map(|c| c.to_lowercase().next().unwrap_or(c))
+            .collect()
+    };
+    let text = fold(content);
+    let position = query
+        .split_whitespace()
+        .map(fold)
+        .filter(|word| !word.is_empty() && word.len() <= text.len())
+        .filter_map(|word| text.windows(word.len()).position(|window| window == word))
+        .min()
+        .unwrap_or(0);
+    //keep some context in front of the match
+    let start = position.saturating_sub(SNIPPET_LENGTH / 4);
+    content.chars().skip(start).take(SNIPPET_LENGTH).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mongodb::connect_mongo;
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_snippet() {
+        assert_eq!(snippet("Hello World", "world"), "Hello World");
+        let content = format!("{}needle{}", "a".repeat(200), "b".repeat(200));
+        let found = snippet(&content, "NEEDLE");
+        assert_eq!(found.chars().count(), SNIPPET_LENGTH);
+        assert!(found.starts_with(&"a".repeat(SNIPPET_LENGTH / 4)));
+        assert!(found[SNIPPET_LENGTH / 4..].starts_with("needle"));
+        assert_eq!(snippet("short", "missing"), "short");
+    }
+
+    #[test]
+    async fn test_search() {
+        let client = connect_mongo(None).await.unwrap();
+        let db = client.database("TEST_SEARCH1");
+        db.drop(None).await.unwrap();
+        let index = SearchIndex::new(&db);
+        index.create_indexes().await.unwrap();
+        let alice = ID::new("123123123123123123123123".to_string()).unwrap();
+        let bob = ID::new("123123123123123123123124".to_string()).unwrap();
+        let general = "aaaaaaaaaaaaaaaaaaaaaaaa".to_string();
+        let random = "bbbbbbbbbbbbbbbbbbbbbbbb".to_string();
+
+        let deploy = Message::from_user(
+            "the deploy failed".to_string(),
+            "alice".to_string(),
+            alice.clone(),
+        );
+        let mut mention = Message::from_user(
+            "bob please fix the deploy".to_string(),
+            "alice".to_string(),
+            alice.clone(),
+        );
+        mention.mentions = vec![bob.clone()];
+        let other = Message::from_user(
+            "deploy works again".to_string(),
+            "bob".to_string(),
+            bob.clone(),
+        );
+        let unrelated = Message::from_user("lunch?".to_string(), "bob".to_string(), bob.clone());
+        index.add(&deploy, &general).await.unwrap();
+        index.add(&mention, &general).await.unwrap();
+        index.add(&other, &random).await.unwrap();
+        index.add(&unrelated, &general).await.unwrap();
+        //server messages are not indexed
+        index
+            .add(
+                &Message::new("deploy".to_string(), "SERVER".to_string()),
+                &general,
+            )
+            .await
+            .unwrap();
+
+        let both = vec![general.clone(), random.clone()];
+        let ids = |results: &[SearchResult]| -> Vec<ID> {
+            let mut ids: Vec<ID> = results.iter().map(|r| r.message_id.clone()).collect();
+            ids.sort_by(|a, b| a.id.cmp(&b.id));
+            ids
+        };
+        let (all, has_more) = index
+            .search("deploy", both.clone(), &SearchFilters::default(), None, 0)
+            .await
+            .unwrap();
+        assert!(!has_more);
+        assert_eq!(all.len(), 3);
+
+        let by_bob = SearchFilters {
+            author: Some(bob.clone()),
+            ..Default::default()
+        };
+        let (found, _) = index
+            .search("deploy", both.clone(), &by_bob, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(ids(&found), vec![other.id.clone()]);
+
+        let (found, _) = index
+            .search(
+                "deploy",
+                vec![general.clone()],
+                &SearchFilters::default(),
+                None,
+                0,
+            )
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|r| r.channel.id == general));
+
+        let with_mention = SearchFilters {
+            has_mention: true,
+            ..Default::default()
+        };
+        let (found, _) = index
+            .search("deploy", both.clone(), &with_mention, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(ids(&found), vec![mention.id.clone()]);
+
+        let pinned = Some(vec![deploy.id.id.clone()]);
+        let (found, _) = index
+            .search("deploy", both.clone(), &SearchFilters::default(), pinned, 0)
+            .await
+            .unwrap();
+        assert_eq!(ids(&found), vec![deploy.id.clone()]);
+
+        let edited = Message {
+            content: "the build failed".to_string(),
+            ..deploy.clone()
+        };
+        index.update(&edited).await.unwrap();
+        index.remove(&other.id).await.unwrap();
+        let (found, _) = index
+            .search("deploy", both, &SearchFilters::default(), None, 0)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        assert_eq!(ids(&found), vec![mention.id]);
+    }
+}
@@ -1,56 +1,185 @@
 use anyhow::{anyhow, Result};
 use common::{
-    error::ServerError,
+    channel::{
+        validate_channel_name, validate_thread_name, ChannelInfo, ChannelUnread, ChannelUpdate,
+        ThreadInfo,
+    },
+    emoji::{
+        parse_emoji_names, parse_emoji_reference, validate_emoji_name, CustomEmoji,
+        MAX_EMOJI_IMAGE_SIZE, MAX_EMOJI_PER_SERVER,
+    },
+    error::{NameError, ServerError},
+    file::{validate_image, Attachment, MAX_ATTACHMENTS_PER_MESSAGE},
     id::ID,
-    messages::{Message, Response},
+    messages::{
+        is_valid_emoji, HistoryOrder, HistoryQuery, Mention, Message, MessageLimits, Pin, Reply,
+        Response, SearchFilters, MAX_MENTIONS_LIMIT, MAX_PINS_PER_CHANNEL,
+    },
+    server::{validate_server_name, ServerInfo, ServerUpdate, MAX_SERVER_DESCRIPTION_LENGTH},
+    user::{validate_nickname, Permission, Role, MEMBER_PAGE_SIZE},
 };
 use mongodb::{
-    bson::{doc, oid::ObjectId},
+    bson::{doc, oid::ObjectId, to_bson, Document},
+    options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReturnDocument},
     Client, Collection, Database,
 };
 use serde::{Deserialize, Serialize};
-use std::{time::SystemTime, borrow::Borrow};
+use std::{borrow::Borrow, collections::HashMap, time::SystemTime};
 
-#[derive(Default, Serialize, Deserialize)]
+use crate::attachments::{AttachmentHandler, ATTACHMENT_DATABASE};
+use crate::blob_store::BlobStore;
+use crate::images::strip_metadata;
+use crate::memberships::{MemberKey, Memberships, MEMBERSHIP_DATABASE};
+use crate::mentions::{parse_mentions, MentionInbox};
+use crate::message_store::{is_duplicate_key, MessageStore};
+use crate::read_states::ReadStates;
+use crate::search::SearchIndex;
+use crate::user::{FindError, UserHandler};
+
+#[derive(Serialize, Deserialize)]
 struct ServerConfig {
     name: String,
+    ///the owner is the only user allowed to delete the server or transfer the ownership, the
+    ///owner is always an admin as well
+    owner: ID,
     admins: Vec<ID>,
     users: Vec<ID>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+    ///id of the default channel
+    #[serde(default)]
+    default_channel: Option<ID>,
+    ///nicknames of the users on this server, keyed by the user id
+    #[serde(default)]
+    nicknames: HashMap<String, String>,
+    #[serde(default)]
+    message_limits: MessageLimits,
+    ///custom emoji of the server in the order they were created
+    #[serde(default)]
+    emoji: Vec<EmojiEntry>,
+}
+
+///a custom emoji of a server, stored in the config of the server, the image is stored in the
+///blob store
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct EmojiEntry {
+    id: ID,
+    name: String,
+    hash: String,
+    created_by: ID,
+    created_at: SystemTime,
+}
+
+///a user of a server together with the roles the user has on the server
+#[derive(Debug, Clone)]
+pub struct MemberEntry {
+    pub user_id: ID,
+    pub roles: Vec<Role>,
+    pub nickname: Option<String>,
+}
+
+///metadata of a channel, stored in the channels collection of the server
+///the messages of the channel are stored in a collection named after the id of the channel, so
+///the name of a channel can never collide with the internal collections of a server
+#[derive(Serialize, Deserialize, Debug)]
+struct ChannelConfig {
+    _id: ObjectId,
+    name: String,
+    topic: Option<String>,
+    position: u32,
+    category: Option<String>,
+    ///in the order the messages were pinned
+    #[serde(default)]
+    pins: Vec<PinEntry>,
+}
+
+///a pinned message of a channel, stored in the config of the channel
+#[derive(Serialize, Deserialize, Debug)]
+struct PinEntry {
+    message_id: ID,
+    pinned_by: ID,
+    pinned_at: SystemTime,
+}
+
+///metadata of a thread, stored in the threads collection of the server
+///the id of a thread is the id of the message it hangs off, so there is at most one thread per
+///message, the messages of the thread are stored in a collection named after the id of the thread
+#[derive(Serialize, Deserialize, Debug)]
+struct ThreadConfig {
+    _id: ObjectId,
+    name: String,
+    ///id of the channel the thread belongs to
+    channel: ObjectId,
+    creator: ID,
+    participants: Vec<ID>,
+    archived: bool,
+    created_at: SystemTime,
 }
 
 ///implements functions for dealing with the the core nicord server functionalities
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ServerHandler;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
-struct Block {
-    _id: u32,
-    messages: Vec<Message>,
-    time_stamp: SystemTime,
-    filled: bool,
+impl ChannelConfig {
+    fn new(name: String, position: u32) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            name,
+            topic: None,
+            position,
+            category: None,
+            pins: Vec::new(),
+        }
+    }
+
+    ///name of the collection that stores the messages of the channel
+    fn storage_name(&self) -> String {
+        self._id.to_hex()
+    }
+
+    fn store(&self, server: &Database) -> MessageStore {
+        MessageStore::new(server, &self.storage_name())
+    }
+
+    fn to_info(&self) -> ChannelInfo {
+        ChannelInfo::new(
+            ID::new(self._id.to_hex()).expect("is an object id"),
+            self.name.clone(),
+            self.topic.clone(),
+            self.position,
+            self.category.clone(),
+        )
+    }
 }
 
-impl Block {
-    fn new(id: u32) -> Self {
+impl ThreadConfig {
+    fn new(parent: ObjectId, name: String, channel: ObjectId, creator: ID) -> Self {
         Self {
-            _id: id,
-            messages: Vec::new(),
-            time_stamp: SystemTime::now(),
-            filled: false,
+            _id: parent,
+            name,
+            channel,
+            participants: vec![creator.clone()],
+            creator,
+            archived: false,
+            created_at: SystemTime::now(),
         }
     }
 
-    ///add message to a block, if reaches 50 sets filled flag, if len >= 50 and add message is
-    ///called false is returned to signalize an invalid operation
-    fn add_message(&mut self, message: Message) -> bool {
-        if self.messages.len() >= 50 {
-            return false;
+    fn store(&self, server: &Database) -> MessageStore {
+        MessageStore::new(server, &self._id.to_hex())
+    }
+
+    fn to_info(&self) -> ThreadInfo {
+        ThreadInfo {
+            id: ID::new(self._id.to_hex()).expect("is an object id"),
+            name: self.name.clone(),
+            channel: ID::new(self.channel.to_hex()).expect("is an object id"),
+            participants: self.participants.clone(),
+            archived: self.archived,
+            created_at: self.created_at,
         }
-        self.messages.push(message);
-        if self.messages.len() == 50 {
-            self.filled = true
-        };
-        true
     }
 }
 
@@ -59,12 +188,121 @@ impl ServerConfig {
         let mut admins = Vec::new();
         let mut users = Vec::new();
         admins.push(creator.clone());
-        users.push(creator);
+        users.push(creator.clone());
 
         Self {
             name,
+            owner: creator,
             admins,
             users,
+            description: None,
+            icon: None,
+            default_channel: None,
+            nicknames: HashMap::new(),
+            message_limits: MessageLimits::default(),
+            emoji: Vec::new(),
+        }
+    }
+
+    fn nickname_of(&self, user_id: &ID) -> Option<String> {
+        self.nicknames.get(&user_id.id).cloned()
+    }
+
+    ///returns what the member list sorts the users of the server by, users that don't exist are
+    ///skipped
+    async fn member_keys(
+        &self,
+        user_handler: &UserHandler,
+        users: &[ID],
+    ) -> Result<Vec<MemberKey>> {
+        let oids = users
+            .iter()
+            .map(|user_id| ObjectId::parse_str(&user_id.id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys = user_handler
+            .get_users(&oids)
+            .await?
+            .into_iter()
+            .map(|user| MemberKey {
+                //the roles are ordered by their hierarchy, the highest role first
+                role: self.roles_of(&user.id)[0],
+                name: self.nickname_of(&user.id).unwrap_or(user.username),
+                user_id: user.id,
+            })
+            .collect();
+        Ok(keys)
+    }
+
+    ///replaces the author of the message and of the message it replies to with the nickname of
+    ///the author if one is set
+    fn apply_nickname(&self, message: &mut Message) {
+        if let Some(nickname) = message
+            .author_id
+            .as_ref()
+            .and_then(|id| self.nickname_of(id))
+        {
+            message.author = nickname;
+        }
+        if let Some(reply) = message.reply_to.as_mut() {
+            if let Some(nickname) = reply.author_id.as_ref().and_then(|id| self.nickname_of(id)) {
+                reply.author = Some(nickname);
+            }
+        }
+    }
+
+    fn find_emoji(&self, name: &str) -> Option<&EmojiEntry> {
+        self.emoji.iter().find(|emoji| emoji.name == name)
+    }
+
+    ///fills in the ids of the custom emoji used in the content of the message, names that are no
+    ///emoji of the server are left as they are. called when the message is sent or edited
+    fn resolve_emojis(&self, message: &mut Message) {
+        message.emojis = parse_emoji_names(&message.content)
+            .into_iter()
+            .filter_map(|name| self.find_emoji(name))
+            .map(|emoji| emoji.id.clone())
+            .collect();
+    }
+
+    ///returns the roles the user has on the server, ordered by their hierarchy
+    fn roles_of(&self, user_id: &ID) -> Vec<Role> {
+        let mut roles = Vec::new();
+        if self.owner == *user_id {
+            roles.push(Role::Owner);
+        }
+        if self.admins.contains(user_id) {
+            roles.push(Role::Admin);
+        }
+        roles.push(Role::Member);
+        roles
+    }
+
+    fn has_permission(&self, user_id: &ID, permission: Permission) -> bool {
+        self.roles_of(user_id)
+            .iter()
+            .any(|role| role.has_permission(permission))
+    }
+
+    fn to_info(&self, id: ID) -> ServerInfo {
+        ServerInfo {
+            id,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            icon: self.icon.clone(),
+            default_channel: self.default_channel.clone(),
+            owner: self.owner.clone(),
+            message_limits: self.message_limits,
+        }
+    }
+}
+
+impl EmojiEntry {
+    fn to_emoji(&self) -> CustomEmoji {
+        CustomEmoji {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            created_by: self.created_by.clone(),
+            created_at: self.created_at,
         }
     }
 }
@@ -85,28 +323,351 @@ impl ServerHandler {
         Ok(Response::Success)
     }
 
+    ///checks whether the user is the owner of the server
+    async fn check_ownership(server: &Database, user_id: &ID) -> Result<Response> {
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let conf_opt = conf_coll.find_one(None, None).await?;
+
+        if conf_opt.is_none() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        if conf_opt.expect("checked above").owner != *user_id {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+        Ok(Response::Success)
+    }
+
+    ///returns the metadata of the channel with the given name if it exists
+    async fn find_channel(server: &Database, name: &str) -> Result<Option<ChannelConfig>> {
+        let channels: Collection<ChannelConfig> = server.collection("channels");
+        Ok(channels.find_one(doc! {"name": name}, None).await?)
+    }
+
+    ///returns the config of the server if the user is listed as user in the config document
+    async fn load_config(server: &Database, user_id: &ID) -> Result<FindError<ServerConfig>> {
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let conf = match conf_coll.find_one(None, None).await? {
+            Some(conf) => conf,
+            None => return Ok(FindError::Err(ServerError::BadRequest)),
+        };
+        if !conf.users.contains(user_id) {
+            return Ok(FindError::Err(ServerError::PermissionDenied));
+        }
+        Ok(FindError::Ok(conf))
+    }
+
+    ///returns the config of the server and the metadata of the channel if the user is listed as
+    ///user in the config document and the channel exists
+    async fn load_channel(
+        server: &Database,
+        channel_name: &str,
+        user_id: &ID,
+    ) -> Result<FindError<(ServerConfig, ChannelConfig)>> {
+        let conf = match Self::load_config(server, user_id).await? {
+            FindError::Ok(conf) => conf,
+            FindError::Err(e) => return Ok(FindError::Err(e)),
+        };
+        match Self::find_channel(server, channel_name).await? {
+            Some(channel) => Ok(FindError::Ok((conf, channel))),
+            None => Ok(FindError::Err(ServerError::BadRequest)),
+        }
+    }
+
+    ///returns the config of the server and the metadata of the thread if the user is listed as
+    ///user in the config document and the thread exists
+    async fn load_thread(
+        server: &Database,
+        thread_id: &ID,
+        user_id: &ID,
+    ) -> Result<FindError<(ServerConfig, ThreadConfig)>> {
+        let conf = match Self::load_config(server, user_id).await? {
+            FindError::Ok(conf) => conf,
+            FindError::Err(e) => return Ok(FindError::Err(e)),
+        };
+        let oid = match ObjectId::parse_str(&thread_id.id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(FindError::Err(ServerError::BadRequest)),
+        };
+        let threads: Collection<ThreadConfig> = server.collection("threads");
+        match threads.find_one(doc! {"_id": oid}, None).await? {
+            Some(thread) => Ok(FindError::Ok((conf, thread))),
+            None => Ok(FindError::Err(ServerError::BadRequest)),
+        }
+    }
+
+    ///prepares messages of the store to be returned to the viewer: replies, nicknames, custom
+    ///emoji and reactions are filled in
+    async fn prepare_messages(
+        conf: &ServerConfig,
+        store: &MessageStore,
+        messages: &mut [Message],
+        viewer: &ID,
+    ) -> Result<()> {
+        store.attach_replies(messages).await?;
+        messages
+            .iter_mut()
+            .for_each(|message| conf.apply_nickname(message));
+        store.attach_reactions(messages, viewer).await
+    }
+
+    ///resolves the mentions in the content against the members of the server and fills in the
+    ///ids of the mentioned users. names are matched against the usernames and nicknames of the
+    ///members, @owner and @admin mention all users with the role. @everyone is not resolved to
+    ///the users but sets mentions_everyone if the author has the permission to do so, otherwise
+    ///it is ignored
+    async fn resolve_mentions(
+        conf: &ServerConfig,
+        user_handler: &UserHandler,
+        message: &mut Message,
+    ) -> Result<()> {
+        let author_id = match &message.author_id {
+            Some(author_id) => author_id,
+            None => return Ok(()),
+        };
+        let names = parse_mentions(&message.content);
+        if names.is_empty() {
+            message.mentions = Vec::new();
+            message.mentions_everyone = false;
+            return Ok(());
+        }
+        let oids: Vec<ObjectId> = conf
+            .users
+            .iter()
+            .filter_map(|id| ObjectId::parse_str(&id.id).ok())
+            .collect();
+        let users = user_handler.get_users(&oids).await?;
+
+        let mut mentioned: Vec<ID> = Vec::new();
+        let mut everyone = false;
+        for name in names {
+            let matches: Vec<&ID> = if name.eq_ignore_ascii_case("everyone") {
+                everyone |= conf.has_permission(author_id, Permission::MentionEveryone);
+                continue;
+            } else {
+                conf.users
+                    .iter()
+                    .filter(|id| {
+                        conf.roles_of(id).iter().any(|role| {
+                            *role != Role::Member && role.name().eq_ignore_ascii_case(&name)
+                        }) || conf.nickname_of(id).as_deref() == Some(name.as_str())
+                            || users
+                                .iter()
+                                .any(|user| user.id == **id && user.username == name)
+                    })
+                    .collect()
+            };
+            for id in matches {
+                if !mentioned.contains(id) {
+                    mentioned.push(id.clone());
+                }
+            }
+        }
+        message.mentions = mentioned;
+        message.mentions_everyone = everyone;
+        Ok(())
+    }
+
+    ///returns the users that get the message into their mention inbox, mentions of everyone
+    ///reach all users without listing them and users don't get notified about mentioning
+    ///themselves
+    fn notified(message: &Message) -> Vec<ID> {
+        if message.mentions_everyone {
+            return Vec::new();
+        }
+        message
+            .mentions
+            .iter()
+            .filter(|id| *id != message.author_id.as_ref().expect("is sent by a user"))
+            .cloned()
+            .collect()
+    }
+
+    ///appends a message of the user to the store and adds it to the mention inbox of all mentioned
+    ///users, the message and all before it count as read by the user, returns bad request if the
+    ///message that is replied to does not exist in the store and invalid message if the content
+    ///violates the message limits of the server, messages with attachments may have no content
+    ///the username of the user is used as author
+    #[allow(clippy::too_many_arguments)]
+    async fn post_message(
+        conf: &ServerConfig,
+        user_handler: &UserHandler,
+        server: &Database,
+        store: &MessageStore,
+        user_id: &ID,
+        content: String,
+        reply_to: Option<ID>,
+        attachments: Vec<Attachment>,
+    ) -> Result<Response> {
+        let attachment_only = content.is_empty() && !attachments.is_empty();
+        if let (false, Err(e)) = (attachment_only, conf.message_limits.validate(&content)) {
+            return Ok(Response::Error(ServerError::InvalidMessage(e)));
+        }
+        let oid = ObjectId::parse_str(&user_id.id)?;
+        let author = match user_handler.get_user(oid).await? {
+            Some(user) => user.username,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let mut message = Message::from_user(content, author, user_id.clone());
+        Self::resolve_mentions(conf, user_handler, &mut message).await?;
+        conf.resolve_emojis(&mut message);
+        if let Some(parent_id) = reply_to {
+            if store.find_message(&parent_id).await?.is_none() {
+                return Ok(Response::Error(ServerError::BadRequest));
+            }
+            message.reply_to = Some(Reply::new(parent_id));
+        }
+        message.attachments = attachments;
+        let message = store.append(message).await?;
+
+        MentionInbox::new(server)
+            .add(&Self::notified(&message), &message, store.storage())
+            .await?;
+        ReadStates::new(server)
+            .ack(user_id, store.storage(), message.seq)
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///returns the part of the message history of the store matching the query prepared for the
+    ///viewer, returns bad request if the cursor points to an unknown message
+    async fn history_response(
+        conf: &ServerConfig,
+        store: &MessageStore,
+        viewer: &ID,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Response> {
+        match store.query_history(query, order).await? {
+            Some(mut history) => {
+                Self::prepare_messages(conf, store, &mut history.messages, viewer).await?;
+                Ok(Response::History(history))
+            }
+            None => Ok(Response::Error(ServerError::BadRequest)),
+        }
+    }
+
+    ///returns the metadata of all channels of the server ordered by their position
+    async fn list_channels(server: &Database) -> Result<Vec<ChannelConfig>> {
+        let channels: Collection<ChannelConfig> = server.collection("channels");
+        let opts = FindOptions::builder().sort(doc! {"position": 1}).build();
+        let mut cursor = channels.find(None, opts).await?;
+        let mut configs = Vec::new();
+        while cursor.advance().await? {
+            configs.push(cursor.deserialize_current()?);
+        }
+        Ok(configs)
+    }
+
+    ///returns a position after all channels of the server, the position is taken from a counter
+    ///in the sequences collection, so channels created concurrently or after a channel was deleted
+    ///never get the same position
+    async fn next_channel_position(server: &Database) -> Result<u32> {
+        let channels: Collection<ChannelConfig> = server.collection("channels");
+        let opts = FindOneOptions::builder()
+            .sort(doc! {"position": -1})
+            .build();
+        let after_last = match channels.find_one(None, opts).await? {
+            Some(last) => last.position as i64 + 1,
+            None => 0,
+        };
+
+        //the counter starts after the last channel of servers created before the counter existed
+        let sequences: Collection<Document> = server.collection("sequences");
+        let next = vec![doc! {"$set": {"seq": {"$max": [
+            {"$add": [{"$ifNull": ["$seq", -1_i64]}, 1_i64]},
+            after_last,
+        ]}}}];
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+        loop {
+            let counter = match sequences
+                .find_one_and_update(
+                    doc! {"_id": "channel_positions"},
+                    next.clone(),
+                    options.clone(),
+                )
+                .await
+            {
+                Ok(counter) => counter,
+                //two concurrent upserts of a missing counter, the counter exists now
+                Err(e) if is_duplicate_key(&e) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let counter = counter.ok_or_else(|| anyhow!("upsert returned no document"))?;
+            return Ok(counter.get_i64("seq")? as u32);
+        }
+    }
+
+    ///creates the indexes of the collections of the server
+    async fn create_indexes(server: &Database) -> Result<()> {
+        SearchIndex::new(server).create_indexes().await?;
+        ReadStates::new(server).create_indexes().await?;
+        Ok(())
+    }
+
+    ///creates the indexes of all servers and records the users of all servers in the membership
+    ///database, servers are databases named by an object id. called once at startup for servers
+    ///created before their indexes and memberships existed
+    pub async fn prepare_servers(client: &Client, user_handler: &UserHandler) -> Result<()> {
+        let memberships = Memberships::new(&client.database(MEMBERSHIP_DATABASE));
+        memberships.create_indexes().await?;
+        for name in client.list_database_names(None, None).await? {
+            let server_id = match ID::new(name) {
+                Some(server_id) => server_id,
+                None => continue,
+            };
+            let server = client.database(&server_id.id);
+            let conf_coll: Collection<ServerConfig> = server.collection("config");
+            if let Some(conf) = conf_coll.find_one(None, None).await? {
+                let members = conf.member_keys(user_handler, &conf.users).await?;
+                memberships.set_members(&server_id, &members).await?;
+            }
+            Self::create_indexes(&server).await?;
+        }
+        Ok(())
+    }
+
     ///creates a new server and server id, the server is stored with the id as the dbs name and the
     ///name in the config, the user is automatically assigned admin and user status
-    pub async fn new_server(user_id: ID, client: &Client, name: String) -> Result<Response> {
+    ///returns invalid name if the name can't be used as a server name
+    pub async fn new_server(
+        user_id: ID,
+        client: &Client,
+        user_handler: &UserHandler,
+        name: String,
+    ) -> Result<Response> {
+        if let Err(e) = validate_server_name(&name) {
+            return Ok(Response::Error(ServerError::InvalidName(e)));
+        }
         let id = ObjectId::new().to_hex();
         let db = client.database(&id);
 
-        let coll = db.collection("config");
-        let conf = ServerConfig::new(name, user_id);
+        let coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new(name, user_id.clone());
 
-        coll.insert_one(conf, None).await?;
-        Ok(Response::ServerCreated(
-            ID::new(id).expect("is an object id"),
-        ))
+        coll.insert_one(&conf, None).await?;
+        Self::create_indexes(&db).await?;
+        let id = ID::new(id).expect("is an object id");
+        let memberships = Memberships::new(&client.database(MEMBERSHIP_DATABASE));
+        for member in conf.member_keys(user_handler, &conf.users).await? {
+            memberships.add(&id, &member).await?;
+        }
+        Ok(Response::ServerCreated(id))
     }
 
-    /// deletes the server db if the user has the required priviledges
+    /// deletes the server db if the user is the owner of the server
     pub async fn delete_server(user_id: &ID, client: &Client, server_id: &ID) -> Result<Response> {
         let db = client.database(&server_id.id);
 
-        match Self::check_priviledge(&db, user_id).await? {
+        match Self::check_ownership(&db, user_id).await? {
             Response::Success => {
                 db.drop(None).await?;
+                Memberships::new(&client.database(MEMBERSHIP_DATABASE))
+                    .remove_server(server_id)
+                    .await?;
                 Ok(Response::Success)
             }
             Response::Error(e) => Ok(Response::Error(e)),
@@ -114,488 +675,3638 @@ impl ServerHandler {
         }
     }
 
-    ///returns the servername (String) that is written in the servers config
-    ///if the server is not inititalized an error is returned
-    ///should only be used when a single or very few names are required, else it might be more
-    ///appropriate to do a lookup in the servername table TODO
-    pub async fn get_server_name_by_id(mongo_client: &Client, server_id: &ID) -> Result<String> {
-        let db = mongo_client.database(&server_id.id);
-        let conf_coll: Collection<ServerConfig> = db.collection("config");
-        let conf_opt = conf_coll.find_one(None, None).await?;
-        if conf_opt.is_none() {
-            return Err(anyhow!("server not initialized"));
+    ///returns the settings of the server if the user is listed as user in the config document
+    pub async fn get_server(client: &Client, server_id: &ID, user_id: &ID) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let conf = match conf_coll.find_one(None, None).await? {
+            Some(conf) => conf,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if !conf.users.contains(user_id) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
         }
-        let name = conf_opt.expect("checked above").name;
-        Ok(name)
+        Ok(Response::ServerInfo(conf.to_info(server_id.clone())))
     }
 
-    ///creates a new channel in the given server if the user has the required priviledges
-    ///returns bad request if the server has no valid config or the channel name is already taken
-    pub async fn new_channel(
+    ///returns a page of the users of the server together with their roles if the user is listed
+    ///as user in the config document and whether there are more pages. the members are sorted by
+    ///their highest role and then by their name in the membership database
+    pub async fn get_members(
+        client: &Client,
+        server_id: &ID,
+        user_id: &ID,
+        page: u32,
+    ) -> Result<FindError<(Vec<MemberEntry>, bool)>> {
+        let server = client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let conf = match conf_coll.find_one(None, None).await? {
+            Some(conf) => conf,
+            None => return Ok(FindError::Err(ServerError::BadRequest)),
+        };
+        if !conf.users.contains(user_id) {
+            return Ok(FindError::Err(ServerError::PermissionDenied));
+        }
+        let skip = page as u64 * MEMBER_PAGE_SIZE as u64;
+        //one more member than fits on the page tells if there is a next page
+        let mut users = Memberships::new(&client.database(MEMBERSHIP_DATABASE))
+            .page(server_id, skip, MEMBER_PAGE_SIZE as i64 + 1)
+            .await?;
+        let has_more = users.len() > MEMBER_PAGE_SIZE;
+        users.truncate(MEMBER_PAGE_SIZE);
+        let members = users
+            .into_iter()
+            .map(|user| MemberEntry {
+                roles: conf.roles_of(&user),
+                nickname: conf.nickname_of(&user),
+                user_id: user,
+            })
+            .collect();
+        Ok(FindError::Ok((members, has_more)))
+    }
+
+    ///returns the users that share at least one server with the user, excluding the user. the
+    ///servers of the user are looked up in the membership database
+    pub async fn get_co_members(client: &Client, user_id: &ID) -> Result<Vec<ID>> {
+        Memberships::new(&client.database(MEMBERSHIP_DATABASE))
+            .co_members(user_id)
+            .await
+    }
+
+    ///sets the nickname of the user on the server if the user is listed as user in the config
+    ///document, an empty nickname removes the nickname
+    pub async fn set_nickname(
         user_id: &ID,
         client: &Client,
-        name: &String,
+        user_handler: &UserHandler,
         server_id: &ID,
+        nickname: String,
     ) -> Result<Response> {
-        let db = client.database(&server_id.to_string());
+        let server = client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let conf = match conf_coll.find_one(None, None).await? {
+            Some(conf) => conf,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if !conf.users.contains(user_id) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+
+        let key = format!("nicknames.{}", user_id.id);
+        let update = if nickname.is_empty() {
+            doc! {"$unset": {key: ""}}
+        } else {
+            if let Err(e) = validate_nickname(&nickname) {
+                return Ok(Response::Error(ServerError::InvalidName(e)));
+            }
+            doc! {"$set": {key: &nickname}}
+        };
+        conf_coll.update_one(doc! {}, update, None).await?;
+
+        //the member list is sorted by the nickname or by the username without a nickname
+        let name = if nickname.is_empty() {
+            let oid = ObjectId::parse_str(&user_id.id)?;
+            match user_handler.get_user(oid).await? {
+                Some(user) => user.username,
+                None => return Ok(Response::Success),
+            }
+        } else {
+            nickname
+        };
+        Memberships::new(&client.database(MEMBERSHIP_DATABASE))
+            .set_name(server_id, user_id, &name)
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///applies the changes to the server settings if the user has the required priviledges
+    ///returns bad request if the default channel does not exist or the description is too long
+    pub async fn update_server(
+        user_id: &ID,
+        client: &Client,
+        server_id: &ID,
+        update: ServerUpdate,
+    ) -> Result<Response> {
+        let db = client.database(&server_id.id);
         match Self::check_priviledge(&db, user_id).await? {
             Response::Success => {}
             Response::Error(e) => return Ok(Response::Error(e)),
             _other => return Ok(Response::Error(ServerError::InternalServerError)),
         }
 
-        let channles = db.list_collection_names(None).await?;
-        if channles.contains(name) {
-            //duplicate channel name
-            return Ok(Response::Error(ServerError::BadRequest));
+        let mut changes = doc! {};
+        if let Some(name) = update.name {
+            if let Err(e) = validate_server_name(&name) {
+                return Ok(Response::Error(ServerError::InvalidName(e)));
+            }
+            changes.insert("name", name);
+        }
+        if let Some(description) = update.description {
+            if description.chars().count() > MAX_SERVER_DESCRIPTION_LENGTH {
+                return Ok(Response::Error(ServerError::BadRequest));
+            }
+            changes.insert("description", Some(description).filter(|d| !d.is_empty()));
+        }
+        if let Some(icon) = update.icon {
+            changes.insert("icon", Some(icon).filter(|i| !i.is_empty()));
+        }
+        if let Some(channel_name) = update.default_channel {
+            if channel_name.is_empty() {
+                changes.insert("default_channel", None::<String>);
+            } else {
+                match Self::find_channel(&db, &channel_name).await? {
+                    Some(config) => {
+                        changes.insert("default_channel", doc! {"id": config.storage_name()})
+                    }
+                    None => return Ok(Response::Error(ServerError::BadRequest)),
+                };
+            }
+        }
+        if let Some(limits) = update.message_limits {
+            if !limits.is_valid() {
+                return Ok(Response::Error(ServerError::BadRequest));
+            }
+            changes.insert("message_limits", to_bson(&limits)?);
         }
 
-        //create the channel
-        let channel: Collection<Block> = db.collection(name);
-        let mut block = Block::new(0);
-        let init_message = Message::new("channel created...".to_string(), "SERVER".to_string());
-        block.add_message(init_message);
-        channel.insert_one(block, None).await?;
-        //insert the init message into the channel_response' collection in order to create the collection
-
+        if !changes.is_empty() {
+            let conf_coll: Collection<ServerConfig> = db.collection("config");
+            conf_coll
+                .update_one(doc! {}, doc! {"$set": changes}, None)
+                .await?;
+        }
         Ok(Response::Success)
     }
 
-    ///delete a channel by its name, returns bad request if channel doesn't exist or the server
-    ///does not exist
-    pub async fn delete_channel(
+    ///makes new_owner the owner of the server if the user is the current owner, the new owner
+    ///has to be a user of the server and is made admin, the previous owner stays admin
+    pub async fn transfer_ownership(
         user_id: &ID,
         client: &Client,
-        name: &String,
         server_id: &ID,
+        new_owner: &ID,
     ) -> Result<Response> {
-        let db = client.database(&server_id.to_string());
-        let channel: Collection<Message> = db.collection(name);
-        match Self::check_priviledge(&db, user_id).await? {
+        let db = client.database(&server_id.id);
+        match Self::check_ownership(&db, user_id).await? {
             Response::Success => {}
             Response::Error(e) => return Ok(Response::Error(e)),
-            other => panic!("unexpected enum variant: {:?}", other),
+            _other => return Ok(Response::Error(ServerError::InternalServerError)),
         }
-        let channles = db.list_collection_names(None).await?;
-        if !channles.contains(name) {
-            //channel does not exist
+
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let result = conf_coll
+            .update_one(
+                doc! {"users.id": &new_owner.id},
+                doc! {
+                    "$set": {"owner": {"id": &new_owner.id}},
+                    "$addToSet": {"admins": {"id": &new_owner.id}},
+                },
+                None,
+            )
+            .await?;
+        if result.matched_count == 0 {
+            //the new owner is not a user of the server
             return Ok(Response::Error(ServerError::BadRequest));
         }
-        channel.drop(None).await?;
+        let memberships = Memberships::new(&client.database(MEMBERSHIP_DATABASE));
+        memberships
+            .set_role(server_id, user_id, Role::Admin)
+            .await?;
+        memberships
+            .set_role(server_id, new_owner, Role::Owner)
+            .await?;
         Ok(Response::Success)
     }
 
-    ///returns a response containing a vector with all the channel names in the database if the
-    ///user is listed as user in the config document
-    pub async fn get_channels(client: &Client, server_id: &ID, user_id: &ID) -> Result<Response> {
+    ///returns the custom emoji of the server in the order they were created if the user is a
+    ///user of the server
+    pub async fn list_emoji(client: &Client, server_id: &ID, user_id: &ID) -> Result<Response> {
         let server = client.database(&server_id.id);
-
-        let conf_coll: Collection<ServerConfig> = server.collection("config");
-        let conf_opt = conf_coll.find_one(None, None).await?;
-
-        if conf_opt.is_none() {
-            return Ok(Response::Error(ServerError::BadRequest));
-        }
-
-        if !conf_opt.expect("checked above").users.contains(user_id) {
-            return Ok(Response::Error(ServerError::PermissionDenied));
+        match Self::load_config(&server, user_id).await? {
+            FindError::Ok(conf) => Ok(Response::EmojiList(
+                conf.emoji.iter().map(EmojiEntry::to_emoji).collect(),
+            )),
+            FindError::Err(e) => Ok(Response::Error(e)),
         }
-
-        let collections = server.list_collection_names(None).await?;
-        let channel_response = collections
-            .iter()
-            .filter(|channel| channel.as_str() != "config")
-            .cloned()
-            .collect();
-        Ok(Response::ChannelList(channel_response))
     }
 
-    ///add a message to a non filled block or create a new block in the channel, given that the
-    ///user has the required priviledges to write messages
-    pub async fn send_message(
+    ///adds a custom emoji with the name and image to the server if the user has the permission
+    ///to manage emoji, metadata is removed from the image before it is stored. returns invalid
+    ///name if the name is invalid or already used on the server, invalid file if the image is
+    ///too large or of an unsupported format and limit exceeded if the server already has
+    ///MAX_EMOJI_PER_SERVER emoji
+    pub async fn create_emoji(
         client: &Client,
+        blobs: &BlobStore,
         server_id: &ID,
-        channel_name: &String,
         user_id: &ID,
-        content: String,
-        author: String,
+        name: String,
+        image: Vec<u8>,
     ) -> Result<Response> {
         let server = client.database(&server_id.id);
-        let conf_coll: Collection<ServerConfig> = server.collection("config");
-        let conf_opt = conf_coll.find_one(None, None).await?;
-
-        if conf_opt.is_none() {
-            return Ok(Response::Error(ServerError::BadRequest));
-        }
-        if !conf_opt.expect("checked above").users.contains(user_id) {
+        let conf = match Self::load_config(&server, user_id).await? {
+            FindError::Ok(conf) => conf,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if !conf.has_permission(user_id, Permission::ManageEmoji) {
             return Ok(Response::Error(ServerError::PermissionDenied));
         }
-        if !server
-            .list_collection_names(None)
-            .await?
-            .contains(channel_name)
-        {
-            return Ok(Response::Error(ServerError::BadRequest));
+        if let Err(e) = validate_emoji_name(&name) {
+            return Ok(Response::Error(ServerError::InvalidName(e)));
         }
+        if let Err(e) = validate_image(&image, MAX_EMOJI_IMAGE_SIZE) {
+            return Ok(Response::Error(ServerError::InvalidFile(e)));
+        }
+        let image = match strip_metadata(&image) {
+            Some(image) => image,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
 
-        let channel: Collection<Block> = server.collection(channel_name);
-        let message = Message::new(content, author);
-
-        if let Some(mut block) = channel.find_one(doc! {"filled": false}, None).await? {
-            if !block.add_message(message) {
-                //full block not marked as full
-                return Ok(Response::Error(ServerError::InternalServerError));
+        let entry = EmojiEntry {
+            id: ID::new(ObjectId::new().to_hex()).expect("is an object id"),
+            name,
+            hash: blobs.put(&image).await?,
+            created_by: user_id.clone(),
+            created_at: SystemTime::now(),
+        };
+        let last_slot = format!("emoji.{}", MAX_EMOJI_PER_SERVER - 1);
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let created = conf_coll
+            .update_one(
+                doc! {
+                    "emoji.name": {"$ne": &entry.name},
+                    &last_slot: {"$exists": false},
+                },
+                doc! {"$push": {"emoji": to_bson(&entry)?}},
+                None,
+            )
+            .await?;
+        if created.matched_count == 0 {
+            if conf.find_emoji(&entry.name).is_some() {
+                return Ok(Response::Error(ServerError::InvalidName(
+                    NameError::AlreadyTaken,
+                )));
             }
-            channel
-                .find_one_and_replace(doc! {"filled": false}, block, None)
-                .await?;
-        } else {
-            let id = channel.count_documents(None, None).await?;
-            //first block gets 0, second 1, ..., k-ter block gets k-1
-            let mut block = Block::new(id as u32);
-            block.add_message(message);
-            channel.insert_one(block, None).await?;
+            return Ok(Response::Error(ServerError::LimitExceeded));
+        }
+        Ok(Response::EmojiCreated(entry.id))
+    }
+
+    ///removes the custom emoji from the server if the user has the permission to manage emoji,
+    ///reactions with the emoji are kept. returns bad request if the emoji doesn't exist
+    pub async fn delete_emoji(
+        client: &Client,
+        server_id: &ID,
+        user_id: &ID,
+        emoji_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let conf = match Self::load_config(&server, user_id).await? {
+            FindError::Ok(conf) => conf,
+            FindError::Err(e) => return Ok(Response::Error(e)),
         };
+        if !conf.has_permission(user_id, Permission::ManageEmoji) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
 
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let deleted = conf_coll
+            .update_one(
+                doc! {},
+                doc! {"$pull": {"emoji": {"id.id": &emoji_id.id}}},
+                None,
+            )
+            .await?;
+        if deleted.modified_count == 0 {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
         Ok(Response::Success)
     }
 
-    ///find a message block in the database and return it if the user has the required priviledges
-    pub async fn get_block_content(
+    ///returns the image of the custom emoji if the user is a user of the server, returns bad
+    ///request if the emoji doesn't exist
+    pub async fn get_emoji_image(
         client: &Client,
+        blobs: &BlobStore,
         server_id: &ID,
-        channel_name: &String,
         user_id: &ID,
-        block_id: u32,
+        emoji_id: &ID,
     ) -> Result<Response> {
         let server = client.database(&server_id.id);
-        let conf_coll: Collection<ServerConfig> = server.collection("config");
-        let conf_opt = conf_coll.find_one(None, None).await?;
+        let conf = match Self::load_config(&server, user_id).await? {
+            FindError::Ok(conf) => conf,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        let entry = match conf.emoji.iter().find(|entry| entry.id == *emoji_id) {
+            Some(entry) => entry,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        match blobs.get(&entry.hash).await? {
+            Some(bytes) => Ok(Response::Image(bytes)),
+            None => Ok(Response::Error(ServerError::InternalServerError)),
+        }
+    }
 
+    ///returns the servername (String) that is written in the servers config
+    ///if the server is not inititalized an error is returned
+    ///should only be used when a single or very few names are required, else it might be more
+    ///appropriate to do a lookup in the servername table TODO
+    pub async fn get_server_name_by_id(mongo_client: &Client, server_id: &ID) -> Result<String> {
+        let db = mongo_client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf_opt = conf_coll.find_one(None, None).await?;
         if conf_opt.is_none() {
-            return Ok(Response::Error(ServerError::BadRequest));
+            return Err(anyhow!("server not initialized"));
         }
-        if !conf_opt.expect("checked above").users.contains(user_id) {
-            return Ok(Response::Error(ServerError::PermissionDenied));
+        let name = conf_opt.expect("checked above").name;
+        Ok(name)
+    }
+
+    ///checks whether the name is a valid channel name that is not yet taken on the server
+    async fn check_channel_name(server: &Database, name: &str) -> Result<Response> {
+        if let Err(e) = validate_channel_name(name) {
+            return Ok(Response::Error(ServerError::InvalidName(e)));
         }
-        if !server
-            .list_collection_names(None)
-            .await?
-            .contains(channel_name)
-        {
-            return Ok(Response::Error(ServerError::BadRequest));
+        if Self::find_channel(server, name).await?.is_some() {
+            return Ok(Response::Error(ServerError::InvalidName(
+                NameError::AlreadyTaken,
+            )));
         }
+        Ok(Response::Success)
+    }
 
-        let channel: Collection<Block> = server.collection(channel_name);
-        if let Some(block) = channel.find_one(doc! {"_id": block_id}, None).await? {
-            Ok(Response::MessagesFound(block.messages))
-        } else {
-            Ok(Response::EndOfChannel)
+    ///creates a new channel in the given server if the user has the required priviledges
+    ///returns bad request if the server has no valid config and invalid name if the channel name is
+    ///invalid or already taken
+    pub async fn new_channel(
+        user_id: &ID,
+        client: &Client,
+        name: &str,
+        server_id: &ID,
+    ) -> Result<Response> {
+        let db = client.database(&server_id.to_string());
+        match Self::check_priviledge(&db, user_id).await? {
+            Response::Success => {}
+            Response::Error(e) => return Ok(Response::Error(e)),
+            _other => return Ok(Response::Error(ServerError::InternalServerError)),
         }
-    }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::mongodb::connect_mongo;
-    use tokio::test;
+        match Self::check_channel_name(&db, name).await? {
+            Response::Success => {}
+            Response::Error(e) => return Ok(Response::Error(e)),
+            _other => return Ok(Response::Error(ServerError::InternalServerError)),
+        }
 
-    use super::*;
+        //new channels are appended at the end of the channel list
+        let channels: Collection<ChannelConfig> = db.collection("channels");
+        let position = Self::next_channel_position(&db).await?;
+        let config = ChannelConfig::new(name.to_string(), position);
+        channels.insert_one(&config, None).await?;
 
-    #[test]
-    async fn test_create_server() {
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
-        };
-        let client = connect_mongo(None).await.unwrap();
+        //insert the init message into the channel's collection in order to create the collection
+        let init_message = Message::new("channel created...".to_string(), "SERVER".to_string());
+        config.store(&db).append(init_message).await?;
 
-        let resp = ServerHandler::new_server(user_id.clone(), &client, "TEST_SERVER1".to_string())
-            .await
-            .unwrap();
-        let id = match resp {
-            Response::ServerCreated(id) => id,
-            other => panic!("got other: {:?}", other),
+        Ok(Response::Success)
+    }
+
+    ///delete a channel by its name, returns bad request if channel doesn't exist or the server
+    ///does not exist
+    pub async fn delete_channel(
+        user_id: &ID,
+        client: &Client,
+        name: &str,
+        server_id: &ID,
+    ) -> Result<Response> {
+        let db = client.database(&server_id.to_string());
+        match Self::check_priviledge(&db, user_id).await? {
+            Response::Success => {}
+            Response::Error(e) => return Ok(Response::Error(e)),
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+        let config = match Self::find_channel(&db, name).await? {
+            Some(config) => config,
+            //channel does not exist
+            None => return Ok(Response::Error(ServerError::BadRequest)),
         };
+        let channels: Collection<ChannelConfig> = db.collection("channels");
+        channels.delete_one(doc! {"_id": config._id}, None).await?;
+        config.store(&db).drop().await?;
+        let inbox = MentionInbox::new(&db);
+        let read_states = ReadStates::new(&db);
+        inbox.forget(&config.storage_name()).await?;
+        read_states.forget(&config.storage_name()).await?;
 
-        let db = client.database(&id.id);
-        let coll: Collection<ServerConfig> = db.collection("config");
-        let config = coll.find_one(None, None).await.unwrap().unwrap();
+        let threads: Collection<ThreadConfig> = db.collection("threads");
+        let mut cursor = threads.find(doc! {"channel": config._id}, None).await?;
+        while cursor.advance().await? {
+            let store = cursor.deserialize_current()?.store(&db);
+            store.drop().await?;
+            inbox.forget(store.storage()).await?;
+            read_states.forget(store.storage()).await?;
+        }
+        threads
+            .delete_many(doc! {"channel": config._id}, None)
+            .await?;
 
-        assert!(config.admins.contains(&user_id));
-        assert!(config.users.contains(&user_id));
-        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        conf_coll
+            .update_one(
+                doc! {"default_channel.id": config.storage_name()},
+                doc! {"$set": {"default_channel": None::<String>}},
+                None,
+            )
+            .await?;
+        Ok(Response::Success)
     }
 
-    #[test]
-    async fn test_delete_server() {
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
-        };
-        let client = connect_mongo(None).await.unwrap();
+    ///returns a response containing the metadata of all channels of the server ordered by their
+    ///position if the user is listed as user in the config document
+    pub async fn get_channels(client: &Client, server_id: &ID, user_id: &ID) -> Result<Response> {
+        let server = client.database(&server_id.id);
 
-        let db = client.database("120129184124124127777154");
-        let coll: Collection<ServerConfig> = db.collection("config");
-        let conf = ServerConfig::new("TEST SERVER2".to_string(), user_id.clone());
+        let conf_coll: Collection<ServerConfig> = server.collection("config");
+        let conf_opt = conf_coll.find_one(None, None).await?;
 
-        coll.insert_one(conf, None).await.unwrap();
+        if conf_opt.is_none() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
 
-        let resp = ServerHandler::delete_server(
-            &user_id,
-            &client,
-            &ID {
-                id: "120129184124124127777154".to_string(),
-            },
-        )
-        .await
-        .unwrap();
-        assert!(resp.succeeded());
-        assert!(coll.find_one(None, None).await.unwrap().is_none());
-        assert!(!client
-            .list_database_names(None, None)
-            .await
-            .unwrap()
-            .contains(&"120129184124124127777154".to_string()));
-        db.drop(None).await.unwrap();
+        if !conf_opt.expect("checked above").users.contains(user_id) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+
+        let channel_response = Self::list_channels(&server)
+            .await?
+            .iter()
+            .map(ChannelConfig::to_info)
+            .collect();
+        Ok(Response::ChannelList(channel_response))
     }
 
-    #[test]
-    async fn test_create_channel() {
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
-        };
-        let client = connect_mongo(None).await.unwrap();
-        let server_id = ID {
-            id: "120129184124124127777155".to_string(),
+    ///marks the message and all messages of the channel before it as read by the user
+    ///returns bad request if the channel or the message does not exist
+    pub async fn ack(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (_, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
         };
 
-        let db = client.database(&server_id.id);
-        let conf_coll: Collection<ServerConfig> = db.collection("config");
-        let conf = ServerConfig::new("TEST SERVER3".to_string(), user_id.clone());
+        let store = channel.store(&server);
+        let message = match store.find_message(message_id).await? {
+            Some(message) => message,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        ReadStates::new(&server)
+            .ack(user_id, store.storage(), message.seq)
+            .await?;
+        Ok(Response::Success)
+    }
 
-        conf_coll.insert_one(conf, None).await.unwrap();
+    ///returns the number of unread messages and unread mentions of the user for every channel of
+    ///the server ordered by their position, unread messages are counted in the blocks newer than
+    ///the last read message of each channel
+    pub async fn get_unread(client: &Client, server_id: &ID, user_id: &ID) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        if let FindError::Err(e) = Self::load_config(&server, user_id).await? {
+            return Ok(Response::Error(e));
+        }
 
-        let resp =
-            ServerHandler::new_channel(&user_id, &client, &"TEST_CHANNEL123".to_string(), &server_id)
-                .await
-                .unwrap();
-        match resp {
-            Response::Success => {}
-            other => panic!("unexpected enum variant: {:?}", other),
+        let channels = Self::list_channels(&server).await?;
+        let storages: Vec<String> = channels.iter().map(ChannelConfig::storage_name).collect();
+        let mut last_read = ReadStates::new(&server)
+            .last_read(user_id, &storages)
+            .await?;
+        for storage in &storages {
+            last_read.entry(storage.clone()).or_insert(0);
         }
+        let mentions = MentionInbox::new(&server)
+            .count_after(user_id, &last_read)
+            .await?;
+        let messages = MessageStore::count_after(&server, &last_read).await?;
 
-        let channel: Collection<Block> = db.collection("TEST_CHANNEL123");
-        let block = channel.find_one(None, None).await.unwrap().unwrap();
-        assert_eq!(1, block.messages.len());
-        let message = &block.messages[0];
-        assert_eq!(message.content, "channel created...");
-        db.drop(None).await.unwrap();
-        assert_eq!(message.author, "SERVER");
+        let unread = channels
+            .iter()
+            .map(|channel| {
+                let storage = channel.storage_name();
+                ChannelUnread {
+                    channel: channel.name.clone(),
+                    unread: messages.get(&storage).copied().unwrap_or(0),
+                    mentions: mentions.get(&storage).copied().unwrap_or(0),
+                }
+            })
+            .collect();
+        Ok(Response::Unread(unread))
     }
 
-    #[test]
-    async fn test_delete_channel() {
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
-        };
-        let client = connect_mongo(None).await.unwrap();
-        let server_id = ID {
-            id: "120129184124124127777156".to_string(),
-        };
+    ///applies the changes to the channel if the user has the required priviledges
+    ///returns bad request if the channel does not exist and invalid name if the new name is
+    ///invalid or already taken
+    pub async fn update_channel(
+        user_id: &ID,
+        client: &Client,
+        name: &str,
+        server_id: &ID,
+        update: ChannelUpdate,
+    ) -> Result<Response> {
         let db = client.database(&server_id.id);
-        let conf_coll: Collection<ServerConfig> = db.collection("config");
-        let conf = ServerConfig::new("TEST SERVER4".to_string(), user_id.clone());
-        conf_coll.insert_one(conf, None).await.unwrap();
+        match Self::check_priviledge(&db, user_id).await? {
+            Response::Success => {}
+            Response::Error(e) => return Ok(Response::Error(e)),
+            _other => return Ok(Response::Error(ServerError::InternalServerError)),
+        }
+        let config = match Self::find_channel(&db, name).await? {
+            Some(config) => config,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
 
-        let channel = db.collection("TEST_CHANNEL");
-        channel
-            .insert_one(
-                Message::new("starting...".to_string(), "SERVER".to_string()),
-                None,
-            )
-            .await
-            .unwrap();
+        let mut changes = doc! {};
+        if let Some(new_name) = update.name {
+            if new_name != config.name {
+                match Self::check_channel_name(&db, &new_name).await? {
+                    Response::Success => {}
+                    Response::Error(e) => return Ok(Response::Error(e)),
+                    _other => return Ok(Response::Error(ServerError::InternalServerError)),
+                }
+                changes.insert("name", new_name);
+            }
+        }
+        if let Some(topic) = update.topic {
+            if topic.is_empty() {
+                changes.insert("topic", None::<String>);
+            } else {
+                changes.insert("topic", topic);
+            }
+        }
 
-        let mut collections = db.list_collection_names(None).await.unwrap();
-        assert!(collections.contains(&"TEST_CHANNEL".to_string()));
+        if !changes.is_empty() {
+            let channels: Collection<ChannelConfig> = db.collection("channels");
+            channels
+                .update_one(doc! {"_id": config._id}, doc! {"$set": changes}, None)
+                .await?;
+        }
+        Ok(Response::Success)
+    }
 
-        assert!(ServerHandler::delete_channel(
-            &user_id,
-            &client,
-            &"TEST_CHANNEL".to_string(),
-            &server_id
-        )
-        .await
-        .unwrap()
-        .succeeded());
+    ///moves the channel to the given position and category if the user has the required
+    ///priviledges, the positions of all other channels are shifted accordingly
+    pub async fn move_channel(
+        user_id: &ID,
+        client: &Client,
+        name: &String,
+        server_id: &ID,
+        position: u32,
+        category: Option<String>,
+    ) -> Result<Response> {
+        let db = client.database(&server_id.id);
+        match Self::check_priviledge(&db, user_id).await? {
+            Response::Success => {}
+            Response::Error(e) => return Ok(Response::Error(e)),
+            _other => return Ok(Response::Error(ServerError::InternalServerError)),
+        }
 
-        collections = db.list_collection_names(None).await.unwrap();
-        assert!(!collections.contains(&"TEST_CHANNEL".to_string()));
+        let mut configs = Self::list_channels(&db).await?;
+        let index = match configs.iter().position(|config| &config.name == name) {
+            Some(index) => index,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let mut config = configs.remove(index);
+        config.category = category;
+        let position = (position as usize).min(configs.len());
+        configs.insert(position, config);
 
-        db.drop(None).await.unwrap();
+        //all positions are written in one update command instead of one request per channel
+        let updates: Vec<Document> = configs
+            .iter()
+            .enumerate()
+            .map(|(i, config)| {
+                doc! {
+                    "q": {"_id": config._id},
+                    "u": {"$set": {"position": i as u32, "category": config.category.clone()}},
+                }
+            })
+            .collect();
+        let result = db
+            .run_command(doc! {"update": "channels", "updates": updates}, None)
+            .await?;
+        if result.contains_key("writeErrors") {
+            return Err(anyhow!("failed to move the channels: {}", result));
+        }
+        Ok(Response::Success)
+    }
+
+    ///append a message to the channel, given that the user has the required priviledges to write
+    ///messages, a message of the channel can be referenced as the message that is replied to
+    ///mentions in the content are resolved against the members of the server
+    pub async fn send_message(
+        client: &Client,
+        user_handler: &UserHandler,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        content: String,
+        reply_to: Option<ID>,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let store = channel.store(&server);
+        Self::post_message(
+            &conf,
+            user_handler,
+            &server,
+            &store,
+            user_id,
+            content,
+            reply_to,
+            Vec::new(),
+        )
+        .await
+    }
+
+    ///sends a message with the attachments the user uploaded to the channel, returns bad request
+    ///if one of the attachments doesn't exist or was uploaded by another user and limit exceeded
+    ///for more than MAX_ATTACHMENTS_PER_MESSAGE attachments
+    pub async fn send_attachments(
+        client: &Client,
+        user_handler: &UserHandler,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        content: String,
+        attachment_ids: Vec<ID>,
+    ) -> Result<Response> {
+        if attachment_ids.is_empty() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        if attachment_ids.len() > MAX_ATTACHMENTS_PER_MESSAGE {
+            return Ok(Response::Error(ServerError::LimitExceeded));
+        }
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        let uploads = client.database(ATTACHMENT_DATABASE);
+        let attachments =
+            match AttachmentHandler::find_attachments(&uploads, user_id, &attachment_ids).await? {
+                Some(attachments) => attachments,
+                None => return Ok(Response::Error(ServerError::BadRequest)),
+            };
+
+        let store = channel.store(&server);
+        Self::post_message(
+            &conf,
+            user_handler,
+            &server,
+            &store,
+            user_id,
+            content,
+            None,
+            attachments,
+        )
+        .await
+    }
+
+    ///returns a chunk of an attachment of a message of the channel or of its thumbnail of the size
+    ///starting at the offset if the user can read the channel, returns bad request if the message
+    ///doesn't exist or has no such attachment
+    #[allow(clippy::too_many_arguments)]
+    pub async fn download_attachment(
+        client: &Client,
+        blobs: &BlobStore,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+        attachment_id: &ID,
+        thumbnail: Option<u32>,
+        offset: u64,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (_, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        let message = match channel.store(&server).find_message(message_id).await? {
+            Some(message) => message,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        match message.attachments.iter().find(|a| a.id == *attachment_id) {
+            Some(attachment) => {
+                AttachmentHandler::download(blobs, attachment, thumbnail, offset).await
+            }
+            None => Ok(Response::Error(ServerError::BadRequest)),
+        }
+    }
+
+    ///returns the id of the channel and the name the user is displayed with on the server if the
+    ///user can read the channel, the nickname if one is set, else the username
+    pub async fn get_channel_identity(
+        client: &Client,
+        user_handler: &UserHandler,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+    ) -> Result<FindError<(ID, String)>> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(FindError::Err(e)),
+        };
+        let channel_id = ID::new(channel.storage_name()).ok_or(anyhow!("invalid channel id"))?;
+        if let Some(nickname) = conf.nickname_of(user_id) {
+            return Ok(FindError::Ok((channel_id, nickname)));
+        }
+        let oid = ObjectId::parse_str(&user_id.id)?;
+        match user_handler.get_user(oid).await? {
+            Some(user) => Ok(FindError::Ok((channel_id, user.username))),
+            None => Ok(FindError::Err(ServerError::BadRequest)),
+        }
+    }
+
+    ///find a message block in the database and return it if the user has the required priviledges
+    ///authors that have a nickname on the server are displayed with their nickname
+    pub async fn get_block_content(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        block_id: u32,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let store = channel.store(&server);
+        if let Some(mut block) = store.get_block(block_id).await? {
+            Self::prepare_messages(&conf, &store, &mut block.messages, user_id).await?;
+            Ok(Response::MessagesFound(block.messages))
+        } else {
+            Ok(Response::EndOfChannel)
+        }
+    }
+
+    ///returns the part of the message history of the channel matching the query if the user has
+    ///the required priviledges, returns bad request if the cursor points to an unknown message
+    pub async fn get_history(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        Self::history_response(&conf, &channel.store(&server), user_id, query, order).await
+    }
+
+    ///creates a thread that hangs off the message of the channel if the user can read the channel
+    ///returns bad request if the message does not exist or already has a thread
+    pub async fn create_thread(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+        name: String,
+    ) -> Result<Response> {
+        if let Err(e) = validate_thread_name(&name) {
+            return Ok(Response::Error(ServerError::InvalidName(e)));
+        }
+        let server = client.database(&server_id.id);
+        let (_, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if channel
+            .store(&server)
+            .find_message(message_id)
+            .await?
+            .is_none()
+        {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let parent = ObjectId::parse_str(&message_id.id)?;
+        let thread = ThreadConfig::new(
+            parent,
+            name.trim().to_string(),
+            channel._id,
+            user_id.clone(),
+        );
+        let threads: Collection<ThreadConfig> = server.collection("threads");
+        match threads.insert_one(&thread, None).await {
+            Ok(_) => {}
+            //the message already has a thread
+            Err(e) if is_duplicate_key(&e) => return Ok(Response::Error(ServerError::BadRequest)),
+            Err(e) => return Err(e.into()),
+        }
+        Ok(Response::ThreadCreated(message_id.clone()))
+    }
+
+    ///returns the metadata of all threads of the channel, including archived threads, ordered by
+    ///their creation time if the user can read the channel
+    pub async fn get_threads(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (_, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let threads: Collection<ThreadConfig> = server.collection("threads");
+        let opts = FindOptions::builder()
+            .sort(doc! {"created_at.secs_since_epoch": 1, "created_at.nanos_since_epoch": 1})
+            .build();
+        let mut cursor = threads.find(doc! {"channel": channel._id}, opts).await?;
+        let mut infos = Vec::new();
+        while cursor.advance().await? {
+            infos.push(cursor.deserialize_current()?.to_info());
+        }
+        Ok(Response::ThreadList(infos))
+    }
+
+    ///appends a message to the thread and adds the user to its participants, given that the user
+    ///is on the server and the thread is not archived
+    pub async fn send_thread_message(
+        client: &Client,
+        user_handler: &UserHandler,
+        server_id: &ID,
+        thread_id: &ID,
+        user_id: &ID,
+        content: String,
+        reply_to: Option<ID>,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, thread) = match Self::load_thread(&server, thread_id, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if thread.archived {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+
+        let store = thread.store(&server);
+        let response = Self::post_message(
+            &conf,
+            user_handler,
+            &server,
+            &store,
+            user_id,
+            content,
+            reply_to,
+            Vec::new(),
+        )
+        .await?;
+        if response.succeeded() {
+            let threads: Collection<ThreadConfig> = server.collection("threads");
+            threads
+                .update_one(
+                    doc! {"_id": thread._id},
+                    doc! {"$addToSet": {"participants": to_bson(user_id)?}},
+                    None,
+                )
+                .await?;
+        }
+        Ok(response)
+    }
+
+    ///returns the part of the message history of the thread matching the query if the user is on
+    ///the server, returns bad request if the cursor points to an unknown message
+    pub async fn get_thread_history(
+        client: &Client,
+        server_id: &ID,
+        thread_id: &ID,
+        user_id: &ID,
+        query: HistoryQuery,
+        order: HistoryOrder,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, thread) = match Self::load_thread(&server, thread_id, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        Self::history_response(&conf, &thread.store(&server), user_id, query, order).await
+    }
+
+    ///returns up to limit of the latest messages the user was mentioned in on the server, newest
+    ///first, messages that were deleted in the meantime are left out
+    pub async fn get_mentions(
+        client: &Client,
+        server_id: &ID,
+        user_id: &ID,
+        limit: u32,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let conf = match Self::load_config(&server, user_id).await? {
+            FindError::Ok(conf) => conf,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let entries = MentionInbox::new(&server)
+            .latest(user_id, limit.min(MAX_MENTIONS_LIMIT))
+            .await?;
+        let mut mentions = Vec::new();
+        for entry in entries {
+            let store = MessageStore::new(&server, &entry.storage);
+            if let Some(message) = store.find_message(&entry.message_id).await? {
+                let mut messages = [message];
+                Self::prepare_messages(&conf, &store, &mut messages, user_id).await?;
+                let [message] = messages;
+                let channel = ID::new(entry.storage).ok_or(anyhow!("invalid storage name"))?;
+                mentions.push(Mention { channel, message });
+            }
+        }
+        Ok(Response::Mentions(mentions))
+    }
+
+    ///returns a page of the messages of the server matching the query and the filters if the user
+    ///is on the server, only channels and threads the user can read are searched
+    ///returns bad request if the query is empty or the channel of the filters does not exist
+    pub async fn search_messages(
+        client: &Client,
+        server_id: &ID,
+        user_id: &ID,
+        query: &str,
+        filters: SearchFilters,
+        page: u32,
+    ) -> Result<Response> {
+        if query.trim().is_empty() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let server = client.database(&server_id.id);
+        if let FindError::Err(e) = Self::load_config(&server, user_id).await? {
+            return Ok(Response::Error(e));
+        }
+
+        //all members can read all channels of a server
+        let channels = match &filters.channel {
+            Some(name) => match Self::find_channel(&server, name).await? {
+                Some(channel) => vec![channel],
+                None => return Ok(Response::Error(ServerError::BadRequest)),
+            },
+            None => Self::list_channels(&server).await?,
+        };
+        let channel_ids: Vec<ObjectId> = channels.iter().map(|channel| channel._id).collect();
+        let mut storages: Vec<String> = channels.iter().map(ChannelConfig::storage_name).collect();
+        let threads: Collection<ThreadConfig> = server.collection("threads");
+        let mut cursor = threads
+            .find(doc! {"channel": {"$in": channel_ids}}, None)
+            .await?;
+        while cursor.advance().await? {
+            storages.push(cursor.deserialize_current()?._id.to_hex());
+        }
+
+        let pinned = if filters.pinned {
+            let ids = channels
+                .iter()
+                .flat_map(|channel| channel.pins.iter())
+                .map(|pin| pin.message_id.id.clone())
+                .collect();
+            Some(ids)
+        } else {
+            None
+        };
+
+        let (results, has_more) = SearchIndex::new(&server)
+            .search(query, storages, &filters, pinned, page)
+            .await?;
+        Ok(Response::SearchResults(results, has_more))
+    }
+
+    ///archives or unarchives the thread if the user created it or has the permission to manage
+    ///messages
+    pub async fn archive_thread(
+        client: &Client,
+        server_id: &ID,
+        thread_id: &ID,
+        user_id: &ID,
+        archived: bool,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, thread) = match Self::load_thread(&server, thread_id, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if thread.creator != *user_id && !conf.has_permission(user_id, Permission::ManageMessages) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+
+        let threads: Collection<ThreadConfig> = server.collection("threads");
+        threads
+            .update_one(
+                doc! {"_id": thread._id},
+                doc! {"$set": {"archived": archived}},
+                None,
+            )
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///replaces the content of the message if the user is its author, the mentions are resolved
+    ///again and the mention inboxes updated
+    ///returns bad request if the channel or the message does not exist and invalid message if the
+    ///content violates the message limits of the server
+    pub async fn edit_message(
+        client: &Client,
+        user_handler: &UserHandler,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+        content: String,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if let Err(e) = conf.message_limits.validate(&content) {
+            return Ok(Response::Error(ServerError::InvalidMessage(e)));
+        }
+
+        let store = channel.store(&server);
+        let message = match store.find_message(message_id).await? {
+            Some(message) => message,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if message.author_id.as_ref() != Some(user_id) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+        let mut edited = Message {
+            content,
+            ..message.clone()
+        };
+        Self::resolve_mentions(&conf, user_handler, &mut edited).await?;
+        conf.resolve_emojis(&mut edited);
+        if !store.edit_message(&message, &edited).await? {
+            //the message was deleted or edited concurrently
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        MentionInbox::new(&server)
+            .update(&Self::notified(&edited), &edited, store.storage())
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///deletes and unpins the message if the user is its author or has the permission to manage
+    ///messages
+    ///returns bad request if the channel or the message does not exist
+    pub async fn delete_message(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let store = channel.store(&server);
+        let message = match store.find_message(message_id).await? {
+            Some(message) => message,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        if message.author_id.as_ref() != Some(user_id)
+            && !conf.has_permission(user_id, Permission::ManageMessages)
+        {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+        if !store.delete_message(message_id).await? {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        let channels: Collection<ChannelConfig> = server.collection("channels");
+        channels
+            .update_one(
+                doc! {"_id": channel._id},
+                doc! {"$pull": {"pins": {"message_id.id": &message_id.id}}},
+                None,
+            )
+            .await?;
+        Ok(Response::Success)
+    }
+
+    ///pins the message if the user has the permission to pin messages and adds a system message
+    ///to the channel, returns bad request if the message does not exist or is already pinned and
+    ///limit exceeded if MAX_PINS_PER_CHANNEL messages are pinned
+    pub async fn pin_message(
+        client: &Client,
+        user_handler: &UserHandler,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if !conf.has_permission(user_id, Permission::PinMessages) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+        let store = channel.store(&server);
+        if store.find_message(message_id).await?.is_none() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let pin = PinEntry {
+            message_id: message_id.clone(),
+            pinned_by: user_id.clone(),
+            pinned_at: SystemTime::now(),
+        };
+        let last_slot = format!("pins.{}", MAX_PINS_PER_CHANNEL - 1);
+        let channels: Collection<ChannelConfig> = server.collection("channels");
+        let pinned = channels
+            .update_one(
+                doc! {
+                    "_id": channel._id,
+                    "pins.message_id.id": {"$ne": &message_id.id},
+                    &last_slot: {"$exists": false},
+                },
+                doc! {"$push": {"pins": to_bson(&pin)?}},
+                None,
+            )
+            .await?;
+        if pinned.matched_count == 0 {
+            if channel.pins.iter().any(|pin| pin.message_id == *message_id) {
+                return Ok(Response::Error(ServerError::BadRequest));
+            }
+            return Ok(Response::Error(ServerError::LimitExceeded));
+        }
+
+        let oid = ObjectId::parse_str(&user_id.id)?;
+        let name = match conf.nickname_of(user_id) {
+            Some(nickname) => nickname,
+            None => match user_handler.get_user(oid).await? {
+                Some(user) => user.username,
+                None => "someone".to_string(),
+            },
+        };
+        let mut announcement =
+            Message::new(format!("{} pinned a message", name), "SERVER".to_string());
+        announcement.reply_to = Some(Reply::new(message_id.clone()));
+        store.append(announcement).await?;
+        Ok(Response::Success)
+    }
+
+    ///unpins the message if the user has the permission to pin messages, returns bad request if
+    ///the message is not pinned
+    pub async fn unpin_message(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if !conf.has_permission(user_id, Permission::PinMessages) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+
+        let channels: Collection<ChannelConfig> = server.collection("channels");
+        let unpinned = channels
+            .update_one(
+                doc! {"_id": channel._id},
+                doc! {"$pull": {"pins": {"message_id.id": &message_id.id}}},
+                None,
+            )
+            .await?;
+        if unpinned.modified_count == 0 {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        Ok(Response::Success)
+    }
+
+    ///returns the pinned messages of the channel, the latest pin first, if the user can read the
+    ///channel
+    pub async fn get_pins(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let store = channel.store(&server);
+        let mut pins = Vec::new();
+        for entry in channel.pins.into_iter().rev() {
+            if let Some(message) = store.find_message(&entry.message_id).await? {
+                let mut messages = [message];
+                Self::prepare_messages(&conf, &store, &mut messages, user_id).await?;
+                let [message] = messages;
+                pins.push(Pin {
+                    message,
+                    pinned_by: entry.pinned_by,
+                    pinned_at: entry.pinned_at,
+                });
+            }
+        }
+        Ok(Response::Pins(pins))
+    }
+
+    ///returns the emoji reactions with the custom emoji of the server are stored as, :name: is
+    ///resolved to the emoji with the name. returns None for custom emoji that don't exist on the
+    ///server
+    fn reaction_key(conf: &ServerConfig, emoji: &str) -> Option<String> {
+        let name = match emoji.strip_prefix(':').and_then(|e| e.strip_suffix(':')) {
+            Some(name) if !name.is_empty() => name,
+            _ => return Some(emoji.to_string()),
+        };
+        conf.find_emoji(name)
+            .map(|entry| entry.to_emoji().reference())
+    }
+
+    ///adds a reaction of the user with the emoji to the message if the user can read the channel,
+    ///custom emoji of the server are referenced with :name:
+    ///returns bad request if the emoji is invalid or the message does not exist and limit exceeded
+    ///if the message already has MAX_REACTIONS_PER_MESSAGE different emojis
+    pub async fn add_reaction(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+        emoji: &str,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        let emoji = match Self::reaction_key(&conf, emoji) {
+            Some(key) => key,
+            None => return Ok(Response::Error(ServerError::BadRequest)),
+        };
+        let valid = match parse_emoji_reference(&emoji) {
+            Some((_, id)) => conf.emoji.iter().any(|entry| entry.id == id),
+            None => is_valid_emoji(&emoji),
+        };
+        if !valid {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+
+        let store = channel.store(&server);
+        if store.find_message(message_id).await?.is_none() {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        if !store.add_reaction(message_id, &emoji, user_id).await? {
+            return Ok(Response::Error(ServerError::LimitExceeded));
+        }
+        Ok(Response::Success)
+    }
+
+    ///removes the reaction of the user with the emoji from the message, reactions with deleted
+    ///custom emoji are removed with the stored reference
+    ///returns bad request if the user did not react to the message with the emoji
+    pub async fn remove_reaction(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+        emoji: &str,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+
+        let emoji = Self::reaction_key(&conf, emoji).unwrap_or_else(|| emoji.to_string());
+        if !channel
+            .store(&server)
+            .remove_reaction(message_id, &emoji, user_id)
+            .await?
+        {
+            return Ok(Response::Error(ServerError::BadRequest));
+        }
+        Ok(Response::Success)
+    }
+
+    ///returns the previous versions of the message if the user has the permission to manage
+    ///messages
+    pub async fn get_edit_history(
+        client: &Client,
+        server_id: &ID,
+        channel_name: &str,
+        user_id: &ID,
+        message_id: &ID,
+    ) -> Result<Response> {
+        let server = client.database(&server_id.id);
+        let (conf, channel) = match Self::load_channel(&server, channel_name, user_id).await? {
+            FindError::Ok(loaded) => loaded,
+            FindError::Err(e) => return Ok(Response::Error(e)),
+        };
+        if !conf.has_permission(user_id, Permission::ManageMessages) {
+            return Ok(Response::Error(ServerError::PermissionDenied));
+        }
+        let history = channel.store(&server).get_edit_history(message_id).await?;
+        Ok(Response::EditHistory(history))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::message_store::Block;
+    use crate::mongodb::connect_mongo;
+    use crate::user::create_users;
+    use common::error::ContentError;
+    use common::messages::{MAX_MESSAGE_BYTES, REPLY_SNIPPET_LENGTH};
+    use tokio::test;
+
+    use super::*;
+
+    ///records the users of the server in the membership database like prepare_servers does for
+    ///servers whose config was inserted directly
+    async fn insert_memberships(client: &Client, user_handler: &UserHandler, server_id: &ID) {
+        let conf_coll: Collection<ServerConfig> =
+            client.database(&server_id.id).collection("config");
+        let conf = conf_coll.find_one(None, None).await.unwrap().unwrap();
+        let members = conf.member_keys(user_handler, &conf.users).await.unwrap();
+        Memberships::new(&client.database(MEMBERSHIP_DATABASE))
+            .set_members(server_id, &members)
+            .await
+            .unwrap();
+    }
+
+    ///inserts the metadata of a channel and returns the name of its storage collection
+    async fn insert_channel_config(db: &Database, name: &str, position: u32) -> String {
+        let channels: Collection<ChannelConfig> = db.collection("channels");
+        let config = ChannelConfig::new(name.to_string(), position);
+        channels.insert_one(&config, None).await.unwrap();
+        config.storage_name()
+    }
+
+    #[test]
+    async fn test_create_server() {
+        let client = connect_mongo(None).await.unwrap();
+        let users = client.database("TEST_SERVER_USERS1");
+        users.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &users, &["Some Dude"]).await;
+        let user_id = ids[0].clone();
+
+        let resp = ServerHandler::new_server(
+            user_id.clone(),
+            &client,
+            &user_handler,
+            "TEST_SERVER1".to_string(),
+        )
+        .await
+        .unwrap();
+        let id = match resp {
+            Response::ServerCreated(id) => id,
+            other => panic!("got other: {:?}", other),
+        };
+
+        let db = client.database(&id.id);
+        let coll: Collection<ServerConfig> = db.collection("config");
+        let config = coll.find_one(None, None).await.unwrap().unwrap();
+        let memberships = Memberships::new(&client.database(MEMBERSHIP_DATABASE));
+        let members = memberships.page(&id, 0, 10).await.unwrap();
+
+        assert!(config.admins.contains(&user_id));
+        assert!(config.users.contains(&user_id));
+        assert_eq!(members, vec![user_id.clone()]);
+        db.drop(None).await.unwrap();
+        memberships.remove_server(&id).await.unwrap();
+
+        let resp =
+            ServerHandler::new_server(user_id.clone(), &client, &user_handler, "   ".to_string())
+                .await
+                .unwrap();
+        users.drop(None).await.unwrap();
+        assert!(matches!(resp, Response::Error(ServerError::InvalidName(_))));
+    }
+
+    #[test]
+    async fn test_delete_server() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+
+        let db = client.database("120129184124124127777154");
+        let coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER2".to_string(), user_id.clone());
+
+        coll.insert_one(conf, None).await.unwrap();
+
+        let resp = ServerHandler::delete_server(
+            &user_id,
+            &client,
+            &ID {
+                id: "120129184124124127777154".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        assert!(resp.succeeded());
+        assert!(coll.find_one(None, None).await.unwrap().is_none());
+        assert!(!client
+            .list_database_names(None, None)
+            .await
+            .unwrap()
+            .contains(&"120129184124124127777154".to_string()));
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    async fn test_create_channel() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777155".to_string(),
+        };
+
+        let db = client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER3".to_string(), user_id.clone());
+
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        let resp = ServerHandler::new_channel(&user_id, &client, "TEST_CHANNEL123", &server_id)
+            .await
+            .unwrap();
+        match resp {
+            Response::Success => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        let config = ServerHandler::find_channel(&db, "TEST_CHANNEL123")
+            .await
+            .unwrap()
+            .unwrap();
+        let channel: Collection<Block> = db.collection(&config.storage_name());
+        let block = channel.find_one(None, None).await.unwrap().unwrap();
+        assert_eq!(1, block.messages.len());
+        let message = &block.messages[0];
+        assert_eq!(message.content, "channel created...");
+        db.drop(None).await.unwrap();
+        assert_eq!(message.author, "SERVER");
+    }
+
+    #[test]
+    async fn test_delete_channel() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777156".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER4".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        let storage = insert_channel_config(&db, "TEST_CHANNEL", 0).await;
+        let channel = db.collection(&storage);
+        channel
+            .insert_one(
+                Message::new("starting...".to_string(), "SERVER".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let mut collections = db.list_collection_names(None).await.unwrap();
+        assert!(collections.contains(&storage));
+
+        assert!(
+            ServerHandler::delete_channel(&user_id, &client, "TEST_CHANNEL", &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+
+        collections = db.list_collection_names(None).await.unwrap();
+        assert!(!collections.contains(&storage));
+        assert!(ServerHandler::find_channel(&db, "TEST_CHANNEL")
+            .await
+            .unwrap()
+            .is_none());
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    async fn test_get_channels_one_channel() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777157".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER5".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        let storage = insert_channel_config(&db, "TEST_CHANNEL", 0).await;
+        let channel = db.collection(&storage);
+        let mut block = Block::new(0);
+        block.add_message(Message::new(
+            "starting...".to_string(),
+            "SERVER".to_string(),
+        ));
+        channel.insert_one(block, None).await.unwrap();
+
+        let channel_response = ServerHandler::get_channels(&client, &server_id, &user_id)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        match channel_response {
+            Response::ChannelList(channels) => {
+                assert_eq!(channels.len(), 1);
+                assert_eq!(channels[0].name, "TEST_CHANNEL");
+                assert_eq!(channels[0].position, 0);
+            }
+            other => {
+                panic!("unexpected enum variant: {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    async fn test_get_channels_multiple_channels() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777158".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER6".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        let storage1 = insert_channel_config(&db, "TEST_CHANNEL1", 0).await;
+        let storage2 = insert_channel_config(&db, "TEST_CHANNEL2", 1).await;
+        let mut channel = db.collection(&storage1);
+        let mut block = Block::new(0);
+        block.add_message(Message::new(
+            "starting...".to_string(),
+            "SERVER".to_string(),
+        ));
+        channel.insert_one(&block, None).await.unwrap();
+
+        channel = db.collection(&storage2);
+        channel.insert_one(block, None).await.unwrap();
+
+        let channel_response = ServerHandler::get_channels(&client, &server_id, &user_id)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        match channel_response {
+            Response::ChannelList(channels) => {
+                assert_eq!(channels.len(), 2);
+                assert_eq!(channels[0].name, "TEST_CHANNEL1");
+                assert_eq!(channels[1].name, "TEST_CHANNEL2");
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+    }
+
+    #[test]
+    async fn test_send_message() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777159".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["Some Dude"]).await;
+        let user_id = ids[0].clone();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER7".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        let storage = insert_channel_config(&db, "TEST_CHANNEL1", 0).await;
+        let channel = db.collection(&storage);
+        let mut block = Block::new(0);
+        block.add_message(Message::new(
+            "starting...".to_string(),
+            "SERVER".to_string(),
+        ));
+        channel.insert_one(&block, None).await.unwrap();
+
+        let content = "I'm a message".to_string();
+        let author = "Some Dude".to_string();
+        assert!(ServerHandler::send_message(
+            &client,
+            &user_handler,
+            &server_id,
+            "TEST_CHANNEL1",
+            &user_id,
+            content.clone(),
+            None
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        let blk: Block = channel
+            .find_one(doc! {"filled": false}, None)
+            .await
+            .unwrap()
+            .unwrap();
+        db.drop(None).await.unwrap();
+        assert_eq!(blk.messages.len(), 2);
+        assert_eq!(blk.messages[0], block.messages[0]);
+        let message = &blk.messages[1];
+        assert_eq!(message.content, content);
+        assert_eq!(message.author, author);
+        assert_eq!(message.author_id, Some(user_id));
+        assert_ne!(message.id, blk.messages[0].id);
+        assert_eq!(message.edited_at, None);
+    }
+
+    #[test]
+    async fn test_get_block_content(){
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777715a".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER8".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        let storage = insert_channel_config(&db, "TEST_CHANNEL1", 0).await;
+        let channel: Collection<Block> = db.collection(&storage);
+        let mut block = Block::new(0);
+        let m = Message::new(
+            "starting...".to_string(),
+            "SERVER".to_string(),
+        );
+        block.add_message(m.clone());
+        channel.insert_one(&block, None).await.unwrap();
+
+        let resp =
+            ServerHandler::get_block_content(&client, &server_id, "TEST_CHANNEL1", &user_id, 0)
+                .await
+                .unwrap();
+        match resp {
+            Response::MessagesFound(messages) => {
+                assert_eq!(messages.len(), 1);
+                assert_eq!(messages[0], m);
+            }
+            other => {
+                db.drop(None).await.unwrap();
+                panic!("unexpected enum variant: {:?}", other)
+            }
+        }
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    async fn test_update_channel() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777715b".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER9".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        let name = "TEST_CHANNEL1".to_string();
+        assert!(
+            ServerHandler::new_channel(&user_id, &client, &name, &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let id = ServerHandler::find_channel(&db, &name)
+            .await
+            .unwrap()
+            .unwrap()
+            ._id;
+
+        let update = ChannelUpdate {
+            name: Some(" ".to_string()),
+            topic: None,
+        };
+        match ServerHandler::update_channel(&user_id, &client, &name, &server_id, update)
+            .await
+            .unwrap()
+        {
+            Response::Error(ServerError::InvalidName(NameError::Empty)) => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        let update = ChannelUpdate {
+            name: Some("TEST_CHANNEL2".to_string()),
+            topic: Some("some topic".to_string()),
+        };
+        assert!(
+            ServerHandler::update_channel(&user_id, &client, &name, &server_id, update)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+
+        let config = ServerHandler::find_channel(&db, "TEST_CHANNEL2")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config._id, id);
+        assert_eq!(config.topic, Some("some topic".to_string()));
+        let collections = db.list_collection_names(None).await.unwrap();
+        assert!(collections.contains(&config.storage_name()));
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    async fn test_move_channel() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777715c".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER10".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        for (i, name) in ["TEST_CHANNEL1", "TEST_CHANNEL2", "TEST_CHANNEL3"]
+            .iter()
+            .enumerate()
+        {
+            insert_channel_config(&db, name, i as u32).await;
+        }
+
+        assert!(ServerHandler::move_channel(
+            &user_id,
+            &client,
+            &"TEST_CHANNEL3".to_string(),
+            &server_id,
+            0,
+            Some("TEXT CHANNELS".to_string())
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        let channels = ServerHandler::list_channels(&db).await.unwrap();
+        db.drop(None).await.unwrap();
+        let names: Vec<&str> = channels.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["TEST_CHANNEL3", "TEST_CHANNEL1", "TEST_CHANNEL2"]
+        );
+        assert_eq!(channels[0].category, Some("TEXT CHANNELS".to_string()));
+        assert_eq!(channels[2].position, 2);
+    }
+
+    #[test]
+    async fn test_new_channel_position() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777716d".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER27".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        //a channel of a server created before the position counter existed
+        insert_channel_config(&db, "TEST_CHANNEL1", 0).await;
+
+        for name in ["TEST_CHANNEL2", "TEST_CHANNEL3"] {
+            assert!(
+                ServerHandler::new_channel(&user_id, &client, name, &server_id)
+                    .await
+                    .unwrap()
+                    .succeeded()
+            );
+        }
+        //the new channel must not take the position of the last channel after a delete
+        assert!(
+            ServerHandler::delete_channel(&user_id, &client, "TEST_CHANNEL1", &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        assert!(
+            ServerHandler::new_channel(&user_id, &client, "TEST_CHANNEL4", &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+
+        let channels = ServerHandler::list_channels(&db).await.unwrap();
+        db.drop(None).await.unwrap();
+        let positions: Vec<(&str, u32)> = channels
+            .iter()
+            .map(|c| (c.name.as_str(), c.position))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("TEST_CHANNEL2", 1),
+                ("TEST_CHANNEL3", 2),
+                ("TEST_CHANNEL4", 3)
+            ]
+        );
+    }
+
+    #[test]
+    async fn test_create_channel_reserved_and_duplicate_names() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777715d".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER11".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        //names of internal collections don't interfere with the server state
+        let name = "config".to_string();
+        assert!(
+            ServerHandler::new_channel(&user_id, &client, &name, &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        assert!(conf_coll.find_one(None, None).await.unwrap().is_some());
+
+        let resp = ServerHandler::new_channel(&user_id, &client, &name, &server_id)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        match resp {
+            Response::Error(ServerError::InvalidName(NameError::AlreadyTaken)) => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+    }
+
+    #[test]
+    async fn test_update_server() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777715e".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER12".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let storage = insert_channel_config(&db, "TEST_CHANNEL1", 0).await;
+
+        let update = ServerUpdate {
+            name: Some("RENAMED SERVER".to_string()),
+            description: Some("a server for testing".to_string()),
+            icon: None,
+            default_channel: Some("TEST_CHANNEL1".to_string()),
+            message_limits: Some(MessageLimits {
+                max_characters: 10,
+                max_bytes: 100,
+            }),
+        };
+        assert!(
+            ServerHandler::update_server(&user_id, &client, &server_id, update)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+
+        let update = ServerUpdate {
+            default_channel: Some("NOT A CHANNEL".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            !ServerHandler::update_server(&user_id, &client, &server_id, update)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let update = ServerUpdate {
+            message_limits: Some(MessageLimits {
+                max_characters: 10,
+                max_bytes: MAX_MESSAGE_BYTES + 1,
+            }),
+            ..Default::default()
+        };
+        assert!(
+            !ServerHandler::update_server(&user_id, &client, &server_id, update)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+
+        let resp = ServerHandler::get_server(&client, &server_id, &user_id)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        match resp {
+            Response::ServerInfo(info) => {
+                assert_eq!(info.name, "RENAMED SERVER");
+                assert_eq!(info.description, Some("a server for testing".to_string()));
+                assert_eq!(info.icon, None);
+                assert_eq!(info.default_channel, ID::new(storage));
+                assert_eq!(info.owner, user_id);
+                assert_eq!(info.message_limits.max_characters, 10);
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+    }
+
+    #[test]
+    async fn test_transfer_ownership() {
+        let user_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let other_id = ID {
+            id: "123123123123123123123124".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777715f".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER13".to_string(), user_id.clone());
+        conf.users.push(other_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+
+        //only the owner can transfer the ownership
+        assert!(
+            !ServerHandler::transfer_ownership(&other_id, &client, &server_id, &other_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        assert!(
+            ServerHandler::transfer_ownership(&user_id, &client, &server_id, &other_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+
+        let conf = conf_coll.find_one(None, None).await.unwrap().unwrap();
+        assert_eq!(conf.owner, other_id);
+        assert!(conf.admins.contains(&other_id));
+        assert!(conf.admins.contains(&user_id));
+
+        //the previous owner is still admin but can't delete the server anymore
+        assert!(!ServerHandler::delete_server(&user_id, &client, &server_id)
+            .await
+            .unwrap()
+            .succeeded());
+        assert!(ServerHandler::delete_server(&other_id, &client, &server_id)
+            .await
+            .unwrap()
+            .succeeded());
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    async fn test_get_members() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777160".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["owner", "bob", "Alice"]).await;
+        let (owner, bob, alice) = (ids[0].clone(), ids[1].clone(), ids[2].clone());
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER14".to_string(), owner.clone());
+        conf.users.push(bob.clone());
+        conf.users.push(alice.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        insert_memberships(&client, &user_handler, &server_id).await;
+
+        let resp = ServerHandler::get_members(&client, &server_id, &bob, 0)
+            .await
+            .unwrap();
+        let outsider = ServerHandler::get_members(
+            &client,
+            &server_id,
+            &ID {
+                id: "123123123123123123123125".to_string(),
+            },
+            0,
+        )
+        .await
+        .unwrap();
+        //the new owner is listed first, the previous owner stays admin
+        assert!(
+            ServerHandler::transfer_ownership(&owner, &client, &server_id, &bob)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let transferred = ServerHandler::get_members(&client, &server_id, &bob, 0)
+            .await
+            .unwrap();
+        let empty_page = ServerHandler::get_members(&client, &server_id, &bob, 1)
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        Memberships::new(&client.database(MEMBERSHIP_DATABASE))
+            .remove_server(&server_id)
+            .await
+            .unwrap();
+
+        match resp {
+            FindError::Ok((members, has_more)) => {
+                assert!(!has_more);
+                let ids: Vec<&ID> = members.iter().map(|member| &member.user_id).collect();
+                assert_eq!(ids, vec![&owner, &alice, &bob]);
+                assert_eq!(
+                    members[0].roles,
+                    vec![Role::Owner, Role::Admin, Role::Member]
+                );
+                assert_eq!(members[1].roles, vec![Role::Member]);
+            }
+            FindError::Err(e) => panic!("unexpected error: {:?}", e),
+        }
+        match transferred {
+            FindError::Ok((members, _)) => {
+                let ids: Vec<&ID> = members.iter().map(|member| &member.user_id).collect();
+                assert_eq!(ids, vec![&bob, &owner, &alice]);
+                assert_eq!(members[1].roles, vec![Role::Admin, Role::Member]);
+            }
+            FindError::Err(e) => panic!("unexpected error: {:?}", e),
+        }
+        assert!(matches!(empty_page, FindError::Ok((members, false)) if members.is_empty()));
+        assert!(matches!(
+            outsider,
+            FindError::Err(ServerError::PermissionDenied)
+        ));
+    }
+
+    #[test]
+    async fn test_set_nickname() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777161".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["Some Dude"]).await;
+        let user_id = ids[0].clone();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER15".to_string(), user_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        insert_memberships(&client, &user_handler, &server_id).await;
+        let channel_name = "TEST_CHANNEL1".to_string();
+        assert!(
+            ServerHandler::new_channel(&user_id, &client, &channel_name, &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        assert!(ServerHandler::send_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &user_id,
+            "hello".to_string(),
+            None
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        assert!(ServerHandler::set_nickname(
+            &user_id,
+            &client,
+            &user_handler,
+            &server_id,
+            "Dude".to_string()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        let resp =
+            ServerHandler::get_block_content(&client, &server_id, &channel_name, &user_id, 0)
+                .await
+                .unwrap();
+        match resp {
+            Response::MessagesFound(messages) => {
+                assert_eq!(messages[0].author, "SERVER");
+                assert_eq!(messages[1].author, "Dude");
+                assert_eq!(messages[1].author_id, Some(user_id.clone()));
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+        match ServerHandler::get_members(&client, &server_id, &user_id, 0)
+            .await
+            .unwrap()
+        {
+            FindError::Ok((members, _)) => {
+                assert_eq!(members[0].nickname, Some("Dude".to_string()))
+            }
+            FindError::Err(e) => panic!("unexpected error: {:?}", e),
+        }
+
+        assert!(ServerHandler::set_nickname(
+            &user_id,
+            &client,
+            &user_handler,
+            &server_id,
+            String::new()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let conf = conf_coll.find_one(None, None).await.unwrap().unwrap();
+        db.drop(None).await.unwrap();
+        Memberships::new(&client.database(MEMBERSHIP_DATABASE))
+            .remove_server(&server_id)
+            .await
+            .unwrap();
+        assert!(conf.nicknames.is_empty());
+    }
+
+    #[test]
+    async fn test_edit_and_delete_message_permissions() {
+        let owner_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let member_id = ID {
+            id: "123123123123123123123124".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777162".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER16".to_string(), owner_id.clone());
+        conf.users.push(member_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let user_handler = UserHandler::from_names(&client, db.name(), "users");
+        let channel_name = "TEST_CHANNEL1".to_string();
+        let storage = insert_channel_config(&db, &channel_name, 0).await;
+        let store = MessageStore::new(&db, &storage);
+
+        let owner_message =
+            Message::from_user("hi".to_string(), "owner".to_string(), owner_id.clone());
+        let member_message =
+            Message::from_user("helo".to_string(), "member".to_string(), member_id.clone());
+        store.append(owner_message.clone()).await.unwrap();
+        store.append(member_message.clone()).await.unwrap();
+
+        //only the author can edit a message
+        assert!(!ServerHandler::edit_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &owner_id,
+            &member_message.id,
+            "hello".to_string()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        assert!(ServerHandler::edit_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &member_message.id,
+            "hello".to_string()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        //only users that can manage messages can view the edit history
+        match ServerHandler::get_edit_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &member_message.id,
+        )
+        .await
+        .unwrap()
+        {
+            Response::Error(ServerError::PermissionDenied) => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+        match ServerHandler::get_edit_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &owner_id,
+            &member_message.id,
+        )
+        .await
+        .unwrap()
+        {
+            Response::EditHistory(history) => {
+                assert_eq!(history.len(), 1);
+                assert_eq!(history[0].content, "helo");
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        //members can only delete their own messages, admins can delete all messages
+        assert!(!ServerHandler::delete_message(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &owner_message.id
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        assert!(ServerHandler::delete_message(
+            &client,
+            &server_id,
+            &channel_name,
+            &owner_id,
+            &member_message.id
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        assert!(ServerHandler::delete_message(
+            &client,
+            &server_id,
+            &channel_name,
+            &owner_id,
+            &owner_message.id
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        let block = store.get_block(0).await.unwrap().unwrap();
+        db.drop(None).await.unwrap();
+        assert!(block.messages.is_empty());
+    }
+
+    #[test]
+    async fn test_reactions() {
+        let member_id = ID {
+            id: "123123123123123123123123".to_string(),
+        };
+        let outsider_id = ID {
+            id: "123123123123123123123124".to_string(),
+        };
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777163".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER17".to_string(), member_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        let storage = insert_channel_config(&db, &channel_name, 0).await;
+        let store = MessageStore::new(&db, &storage);
+        let message = store
+            .append(Message::new("hi".to_string(), "SERVER".to_string()))
+            .await
+            .unwrap();
+
+        assert!(ServerHandler::add_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &message.id,
+            "👋"
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        //users that are not on the server can't react
+        assert!(!ServerHandler::add_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &outsider_id,
+            &message.id,
+            "👋"
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        assert!(!ServerHandler::add_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &message.id,
+            "no spaces"
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            HistoryQuery::Latest(1),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => {
+                assert_eq!(history.messages[0].reactions.len(), 1);
+                assert_eq!(history.messages[0].reactions[0].count, 1);
+                assert!(history.messages[0].reactions[0].me);
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        assert!(ServerHandler::remove_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &message.id,
+            "👋"
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        assert!(!ServerHandler::remove_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &message.id,
+            "👋"
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    async fn test_replies() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777164".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["Some Dude"]).await;
+        let user_id = ids[0].clone();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER18".to_string(), user_id.clone());
+        conf.nicknames
+            .insert(user_id.id.clone(), "Dude".to_string());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        let storage = insert_channel_config(&db, &channel_name, 0).await;
+        let store = MessageStore::new(&db, &storage);
+        let parent = store
+            .append(Message::from_user(
+                "a".repeat(300),
+                "Some Dude".to_string(),
+                user_id.clone(),
+            ))
+            .await
+            .unwrap();
+
+        assert!(ServerHandler::send_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &user_id,
+            "reply".to_string(),
+            Some(parent.id.clone())
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        //the replied message has to exist in the channel
+        let unknown = ID::new("123123123123123123123123".to_string()).unwrap();
+        assert!(!ServerHandler::send_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &user_id,
+            "reply".to_string(),
+            Some(unknown)
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        let history = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &user_id,
+            HistoryQuery::Latest(10),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        db.drop(None).await.unwrap();
+        assert_eq!(history.messages.len(), 2);
+        assert_eq!(history.messages[0].reply_to, None);
+        let reply = history.messages[1].reply_to.clone().unwrap();
+        assert_eq!(reply.message_id, parent.id);
+        assert_eq!(reply.author, Some("Dude".to_string()));
+        assert_eq!(reply.snippet, Some("a".repeat(REPLY_SNIPPET_LENGTH)));
+    }
+
+    #[test]
+    async fn test_threads() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777165".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["owner", "member"]).await;
+        let (owner_id, member_id) = (ids[0].clone(), ids[1].clone());
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER19".to_string(), owner_id.clone());
+        conf.users.push(member_id.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        let storage = insert_channel_config(&db, &channel_name, 0).await;
+        let parent = MessageStore::new(&db, &storage)
+            .append(Message::from_user(
+                "topic".to_string(),
+                "member".to_string(),
+                member_id.clone(),
+            ))
+            .await
+            .unwrap();
+
+        let thread_id = match ServerHandler::create_thread(
+            &client,
+            &server_id,
+            &channel_name,
+            &member_id,
+            &parent.id,
+            "discussion".to_string(),
+        )
+        .await
+        .unwrap()
+        {
+            Response::ThreadCreated(id) => id,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        assert_eq!(thread_id, parent.id);
+        //a message can only have one thread
+        match ServerHandler::create_thread(
+            &client,
+            &server_id,
+            &channel_name,
+            &owner_id,
+            &parent.id,
+            "discussion".to_string(),
+        )
+        .await
+        .unwrap()
+        {
+            Response::Error(ServerError::BadRequest) => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        assert!(ServerHandler::send_thread_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &thread_id,
+            &owner_id,
+            "hi".to_string(),
+            None
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        match ServerHandler::get_thread_history(
+            &client,
+            &server_id,
+            &thread_id,
+            &member_id,
+            HistoryQuery::Latest(10),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => {
+                assert_eq!(history.messages.len(), 1);
+                assert_eq!(history.messages[0].content, "hi");
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        //archived threads are read only
+        assert!(
+            ServerHandler::archive_thread(&client, &server_id, &thread_id, &member_id, true)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        assert!(!ServerHandler::send_thread_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &thread_id,
+            &member_id,
+            "hi".to_string(),
+            None
+        )
+        .await
+        .unwrap()
+        .succeeded());
+
+        match ServerHandler::get_threads(&client, &server_id, &channel_name, &member_id)
+            .await
+            .unwrap()
+        {
+            Response::ThreadList(threads) => {
+                assert_eq!(threads.len(), 1);
+                assert_eq!(threads[0].name, "discussion");
+                assert_eq!(
+                    threads[0].participants,
+                    vec![member_id.clone(), owner_id.clone()]
+                );
+                assert!(threads[0].archived);
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+        //threads are not listed as channels
+        match ServerHandler::get_channels(&client, &server_id, &member_id)
+            .await
+            .unwrap()
+        {
+            Response::ChannelList(channels) => assert_eq!(channels.len(), 1),
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        //threads are deleted with their channel
+        assert!(
+            ServerHandler::delete_channel(&owner_id, &client, &channel_name, &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let threads: Collection<ThreadConfig> = db.collection("threads");
+        let remaining = threads.count_documents(None, None).await.unwrap();
+        let collections = db.list_collection_names(None).await.unwrap();
+        db.drop(None).await.unwrap();
+        assert_eq!(remaining, 0);
+        assert!(!collections.contains(&thread_id.id));
     }
 
     #[test]
-    async fn test_get_channels_one_channel() {
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
+    async fn test_mentions() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777166".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["alice", "bob"]).await;
+        let (alice, bob) = (ids[0].clone(), ids[1].clone());
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER20".to_string(), alice.clone());
+        conf.users.push(bob.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        insert_channel_config(&db, &channel_name, 0).await;
+
+        //members are not allowed to mention everyone, so only alice is mentioned
+        let messages = [
+            (&bob, "hey @alice and @everyone"),
+            (&alice, "@everyone hello"),
+            (&bob, "ping @admin"),
+        ];
+        for (user_id, content) in messages {
+            assert!(ServerHandler::send_message(
+                &client,
+                &user_handler,
+                &server_id,
+                &channel_name,
+                user_id,
+                content.to_string(),
+                None
+            )
+            .await
+            .unwrap()
+            .succeeded());
+        }
+
+        let alice_mentions = match ServerHandler::get_mentions(&client, &server_id, &alice, 10)
+            .await
+            .unwrap()
+        {
+            Response::Mentions(mentions) => mentions,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        let bob_mentions = match ServerHandler::get_mentions(&client, &server_id, &bob, 10)
+            .await
+            .unwrap()
+        {
+            Response::Mentions(mentions) => mentions,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+
+        //edits resolve the mentions again
+        let everyone_message = bob_mentions[0].message.id.clone();
+        assert!(ServerHandler::edit_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &alice,
+            &everyone_message,
+            "hello @bob".to_string()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let edited_mentions = match ServerHandler::get_mentions(&client, &server_id, &bob, 10)
+            .await
+            .unwrap()
+        {
+            Response::Mentions(mentions) => mentions,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        assert!(ServerHandler::edit_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &alice,
+            &everyone_message,
+            "hello".to_string()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let removed_mentions = match ServerHandler::get_mentions(&client, &server_id, &bob, 10)
+            .await
+            .unwrap()
+        {
+            Response::Mentions(mentions) => mentions,
+            other => panic!("unexpected enum variant: {:?}", other),
         };
+        db.drop(None).await.unwrap();
+
+        assert_eq!(alice_mentions.len(), 2);
+        assert_eq!(alice_mentions[0].message.content, "ping @admin");
+        assert_eq!(alice_mentions[1].message.mentions, vec![alice.clone()]);
+        assert!(!alice_mentions[1].message.mentions_everyone);
+        //users are not notified about their own mentions
+        assert_eq!(bob_mentions.len(), 1);
+        assert_eq!(bob_mentions[0].message.content, "@everyone hello");
+        assert!(bob_mentions[0].message.mentions.is_empty());
+        assert!(bob_mentions[0].message.mentions_everyone);
+        assert_eq!(edited_mentions.len(), 1);
+        assert_eq!(edited_mentions[0].message.mentions, vec![bob]);
+        assert!(!edited_mentions[0].message.mentions_everyone);
+        assert!(removed_mentions.is_empty());
+    }
+
+    #[test]
+    async fn test_pins() {
         let client = connect_mongo(None).await.unwrap();
         let server_id = ID {
-            id: "120129184124124127777157".to_string(),
+            id: "120129184124124127777167".to_string(),
         };
         let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["alice", "bob"]).await;
+        let (alice, bob) = (ids[0].clone(), ids[1].clone());
         let conf_coll: Collection<ServerConfig> = db.collection("config");
-        let conf = ServerConfig::new("TEST SERVER5".to_string(), user_id.clone());
+        let mut conf = ServerConfig::new("TEST SERVER21".to_string(), alice.clone());
+        conf.users.push(bob.clone());
         conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        let storage = insert_channel_config(&db, &channel_name, 0).await;
+        let store = MessageStore::new(&db, &storage);
+        let mut messages = Vec::new();
+        for i in 0..=MAX_PINS_PER_CHANNEL {
+            let message = Message::from_user(i.to_string(), "bob".to_string(), bob.clone());
+            messages.push(store.append(message).await.unwrap());
+        }
 
-        let channel = db.collection("TEST_CHANNEL");
-        let mut block = Block::new(0);
-        block.add_message(Message::new(
-            "starting...".to_string(),
-            "SERVER".to_string(),
-        ));
-        channel.insert_one(block, None).await.unwrap();
+        //members can't pin messages
+        assert!(!ServerHandler::pin_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &bob,
+            &messages[0].id
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        assert!(ServerHandler::pin_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &alice,
+            &messages[0].id
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        assert!(!ServerHandler::pin_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &alice,
+            &messages[0].id
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let announcement = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            HistoryQuery::Latest(1),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history.messages[0].clone(),
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        assert_eq!(announcement.author, "SERVER");
+        assert_eq!(announcement.content, "alice pinned a message");
+        assert_eq!(announcement.reply_to.unwrap().message_id, messages[0].id);
+
+        for message in &messages[1..MAX_PINS_PER_CHANNEL] {
+            assert!(ServerHandler::pin_message(
+                &client,
+                &user_handler,
+                &server_id,
+                &channel_name,
+                &alice,
+                &message.id
+            )
+            .await
+            .unwrap()
+            .succeeded());
+        }
+        match ServerHandler::pin_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &alice,
+            &messages[MAX_PINS_PER_CHANNEL].id,
+        )
+        .await
+        .unwrap()
+        {
+            Response::Error(ServerError::LimitExceeded) => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        assert!(ServerHandler::unpin_message(
+            &client,
+            &server_id,
+            &channel_name,
+            &alice,
+            &messages[0].id
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let pins = match ServerHandler::get_pins(&client, &server_id, &channel_name, &bob)
+            .await
+            .unwrap()
+        {
+            Response::Pins(pins) => pins,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        db.drop(None).await.unwrap();
+        assert_eq!(pins.len(), MAX_PINS_PER_CHANNEL - 1);
+        assert_eq!(pins[0].message.id, messages[MAX_PINS_PER_CHANNEL - 1].id);
+        assert_eq!(pins[0].pinned_by, alice);
+    }
+
+    #[test]
+    async fn test_search_messages() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777168".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["alice", "mallory"]).await;
+        let (alice, mallory) = (ids[0].clone(), ids[1].clone());
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let conf = ServerConfig::new("TEST SERVER22".to_string(), alice.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        ServerHandler::create_indexes(&db).await.unwrap();
+        let general = "general".to_string();
+        let random = "random".to_string();
+        insert_channel_config(&db, &general, 0).await;
+        insert_channel_config(&db, &random, 1).await;
+
+        let start = SystemTime::now();
+        for (channel, content) in [(&general, "release notes"), (&random, "release party")] {
+            assert!(ServerHandler::send_message(
+                &client,
+                &user_handler,
+                &server_id,
+                channel,
+                &alice,
+                content.to_string(),
+                None
+            )
+            .await
+            .unwrap()
+            .succeeded());
+        }
+
+        let search = |filters: SearchFilters| {
+            ServerHandler::search_messages(&client, &server_id, &alice, "release", filters, 0)
+        };
+        match search(SearchFilters::default()).await.unwrap() {
+            Response::SearchResults(results, has_more) => {
+                assert_eq!(results.len(), 2);
+                assert!(!has_more);
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+        let in_general = SearchFilters {
+            channel: Some(general.clone()),
+            ..Default::default()
+        };
+        match search(in_general).await.unwrap() {
+            Response::SearchResults(results, _) => {
+                assert_eq!(results.len(), 1);
+                assert_eq!(results[0].snippet, "release notes");
+            }
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+        let before_start = SearchFilters {
+            before: Some(start),
+            ..Default::default()
+        };
+        match search(before_start).await.unwrap() {
+            Response::SearchResults(results, _) => assert!(results.is_empty()),
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+
+        //users that are not on the server can't search it
+        match ServerHandler::search_messages(
+            &client,
+            &server_id,
+            &mallory,
+            "release",
+            SearchFilters::default(),
+            0,
+        )
+        .await
+        .unwrap()
+        {
+            Response::Error(ServerError::PermissionDenied) => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+        //messages of deleted channels are not found anymore
+        assert!(
+            ServerHandler::delete_channel(&alice, &client, &random, &server_id)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let remaining = match search(SearchFilters::default()).await.unwrap() {
+            Response::SearchResults(results, _) => results,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        db.drop(None).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+    }
+
+    #[test]
+    async fn test_message_limits() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "120129184124124127777169".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["alice"]).await;
+        let alice = ids[0].clone();
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER23".to_string(), alice.clone());
+        conf.message_limits = MessageLimits {
+            max_characters: 5,
+            max_bytes: 8,
+        };
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        insert_channel_config(&db, &channel_name, 0).await;
+
+        let cases = [
+            ("hello", None),
+            ("hello\nyou", Some(ContentError::TooLarge(8))),
+            ("hello!", Some(ContentError::TooLong(5))),
+            ("äöüß", None),
+            ("äöüßä", Some(ContentError::TooLarge(8))),
+            (" \n\t", Some(ContentError::Empty)),
+            ("a\u{7}b", Some(ContentError::InvalidCharacter('\u{7}'))),
+            ("a\r\nb", None),
+        ];
+        for (content, expected) in cases {
+            let response = ServerHandler::send_message(
+                &client,
+                &user_handler,
+                &server_id,
+                &channel_name,
+                &alice,
+                content.to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+            match (response, expected) {
+                (Response::Success, None) => {}
+                (Response::Error(ServerError::InvalidMessage(e)), Some(expected)) => {
+                    assert_eq!(e, expected)
+                }
+                (other, expected) => panic!(
+                    "unexpected response {:?} for {:?}, expected {:?}",
+                    other, content, expected
+                ),
+            }
+        }
+
+        //edits have to respect the limits as well
+        let message = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &alice,
+            HistoryQuery::Latest(1),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history.messages[0].clone(),
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        let response = ServerHandler::edit_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &alice,
+            &message.id,
+            String::new(),
+        )
+        .await
+        .unwrap();
+        db.drop(None).await.unwrap();
+        match response {
+            Response::Error(ServerError::InvalidMessage(ContentError::Empty)) => {}
+            other => panic!("unexpected enum variant: {:?}", other),
+        }
+    }
+
+    #[test]
+    async fn test_unread() {
+        let client = connect_mongo(None).await.unwrap();
+        let server_id = ID {
+            id: "12012918412412412777716a".to_string(),
+        };
+        let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let (user_handler, ids) = create_users(&client, &db, &["alice", "bob"]).await;
+        let (alice, bob) = (ids[0].clone(), ids[1].clone());
+        let conf_coll: Collection<ServerConfig> = db.collection("config");
+        let mut conf = ServerConfig::new("TEST SERVER24".to_string(), alice.clone());
+        conf.users.push(bob.clone());
+        conf_coll.insert_one(conf, None).await.unwrap();
+        let general = "TEST_CHANNEL1".to_string();
+        let random = "TEST_CHANNEL2".to_string();
+        insert_channel_config(&db, &general, 0).await;
+        insert_channel_config(&db, &random, 1).await;
+
+        let messages = [
+            (&bob, "hello"),
+            (&bob, "@alice are you there?"),
+            (&bob, "hello?"),
+            (&alice, "hi"),
+        ];
+        for (user_id, content) in messages {
+            assert!(ServerHandler::send_message(
+                &client,
+                &user_handler,
+                &server_id,
+                &general,
+                user_id,
+                content.to_string(),
+                None
+            )
+            .await
+            .unwrap()
+            .succeeded());
+        }
+        assert!(ServerHandler::send_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &random,
+            &alice,
+            "anyone here?".to_string(),
+            None
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let ids: Vec<ID> = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &general,
+            &bob,
+            HistoryQuery::Latest(10),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history
+                .messages
+                .into_iter()
+                .map(|message| message.id)
+                .collect(),
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+
+        //bob read up to the question, alice read everything by answering
+        assert!(
+            ServerHandler::ack(&client, &server_id, &general, &bob, &ids[1])
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let unknown = ServerHandler::ack(&client, &server_id, &random, &bob, &ids[1])
+            .await
+            .unwrap();
+        let bob_unread = match ServerHandler::get_unread(&client, &server_id, &bob)
+            .await
+            .unwrap()
+        {
+            Response::Unread(unread) => unread,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        let alice_unread = match ServerHandler::get_unread(&client, &server_id, &alice)
+            .await
+            .unwrap()
+        {
+            Response::Unread(unread) => unread,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
 
-        let channel_response = ServerHandler::get_channels(&client, &server_id, &user_id)
+        //deleted messages are not unread, also when the newest message is deleted
+        assert!(
+            ServerHandler::delete_message(&client, &server_id, &general, &alice, &ids[3])
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let after_delete = match ServerHandler::get_unread(&client, &server_id, &bob)
             .await
-            .unwrap();
+            .unwrap()
+        {
+            Response::Unread(unread) => unread,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        assert!(
+            ServerHandler::ack(&client, &server_id, &general, &bob, &ids[2])
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        let after_ack = match ServerHandler::get_unread(&client, &server_id, &bob)
+            .await
+            .unwrap()
+        {
+            Response::Unread(unread) => unread,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
         db.drop(None).await.unwrap();
-        match channel_response {
-            Response::ChannelList(channels) => {
-                assert_eq!(channels.len(), 1);
-                assert_eq!(channels[0], "TEST_CHANNEL");
-            }
-            other => {
-                panic!("unexpected enum variant: {:?}", other)
-            }
-        }
+
+        assert!(matches!(unknown, Response::Error(ServerError::BadRequest)));
+        assert_eq!(bob_unread.len(), 2);
+        assert_eq!(bob_unread[0].channel, general);
+        assert_eq!(bob_unread[0].unread, 1);
+        assert_eq!(bob_unread[1].channel, random);
+        assert_eq!(bob_unread[1].unread, 1);
+        assert_eq!(alice_unread[0].unread, 0);
+        assert_eq!(alice_unread[0].mentions, 0);
+        assert_eq!(after_delete[0].unread, 0);
+        assert_eq!(after_ack[0].unread, 0);
     }
 
     #[test]
-    async fn test_get_channels_multiple_channels() {
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
-        };
+    async fn test_attachments() {
         let client = connect_mongo(None).await.unwrap();
         let server_id = ID {
-            id: "120129184124124127777158".to_string(),
+            id: "12012918412412412777716b".to_string(),
         };
         let db = client.database(&server_id.id);
+        db.drop(None).await.unwrap();
+        let root = std::env::temp_dir().join(&server_id.id);
+        let blobs = BlobStore::new(&root);
+        let (user_handler, ids) = create_users(&client, &db, &["alice", "bob", "carol"]).await;
+        let (alice, bob, carol) = (ids[0].clone(), ids[1].clone(), ids[2].clone());
         let conf_coll: Collection<ServerConfig> = db.collection("config");
-        let conf = ServerConfig::new("TEST SERVER6".to_string(), user_id.clone());
+        let mut conf = ServerConfig::new("TEST SERVER25".to_string(), alice.clone());
+        conf.users.push(bob.clone());
         conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        insert_channel_config(&db, &channel_name, 0).await;
 
-        let mut channel = db.collection("TEST_CHANNEL1");
-        let mut block = Block::new(0);
-        block.add_message(Message::new(
-            "starting...".to_string(),
-            "SERVER".to_string(),
-        ));
-        channel.insert_one(&block, None).await.unwrap();
-
-        channel = db.collection("TEST_CHANNEL2");
-        channel.insert_one(block, None).await.unwrap();
+        let uploads = client.database(ATTACHMENT_DATABASE);
+        let upload_id = match AttachmentHandler::start_upload(
+            &uploads,
+            &alice,
+            "notes.txt".to_string(),
+            5,
+            "text/plain".to_string(),
+        )
+        .await
+        .unwrap()
+        {
+            Response::UploadStarted(id) => id,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        assert!(AttachmentHandler::upload_chunk(
+            &uploads,
+            &alice,
+            &upload_id,
+            0,
+            b"hello".to_vec()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let attachment =
+            match AttachmentHandler::finish_upload(&uploads, &blobs, &alice, &upload_id)
+                .await
+                .unwrap()
+            {
+                Response::Attachment(attachment) => attachment,
+                other => panic!("unexpected enum variant: {:?}", other),
+            };
 
-        let channel_response = ServerHandler::get_channels(&client, &server_id, &user_id)
-            .await
-            .unwrap();
-        db.drop(None).await.unwrap();
-        match channel_response {
-            Response::ChannelList(channels) => {
-                assert_eq!(channels.len(), 2);
-                assert!(channels.contains(&"TEST_CHANNEL1".to_string()));
-                assert!(channels.contains(&"TEST_CHANNEL2".to_string()));
-            }
+        //only the uploader can attach the file
+        let foreign = ServerHandler::send_attachments(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &bob,
+            String::new(),
+            vec![attachment.id.clone()],
+        )
+        .await
+        .unwrap();
+        assert!(ServerHandler::send_attachments(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &alice,
+            String::new(),
+            vec![attachment.id.clone()]
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let message = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &alice,
+            HistoryQuery::Latest(10),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history.messages[0].clone(),
             other => panic!("unexpected enum variant: {:?}", other),
-        }
+        };
+        let downloaded = ServerHandler::download_attachment(
+            &client,
+            &blobs,
+            &server_id,
+            &channel_name,
+            &bob,
+            &message.id,
+            &attachment.id,
+            None,
+            1,
+        )
+        .await
+        .unwrap();
+        //carol is not a member of the server
+        let denied = ServerHandler::download_attachment(
+            &client,
+            &blobs,
+            &server_id,
+            &channel_name,
+            &carol,
+            &message.id,
+            &attachment.id,
+            None,
+            0,
+        )
+        .await
+        .unwrap();
+        db.drop(None).await.unwrap();
+        let _ = tokio::fs::remove_dir_all(&root).await;
+
+        assert!(matches!(foreign, Response::Error(ServerError::BadRequest)));
+        assert_eq!(message.content, "");
+        assert_eq!(message.attachments, vec![attachment]);
+        assert!(matches!(downloaded, Response::Chunk(bytes) if bytes == b"ello"));
+        assert!(matches!(denied, Response::Error(_)));
     }
 
     #[test]
-    async fn test_send_message() {
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
-        };
+    async fn test_custom_emoji() {
         let client = connect_mongo(None).await.unwrap();
         let server_id = ID {
-            id: "120129184124124127777159".to_string(),
+            id: "12012918412412412777716c".to_string(),
         };
         let db = client.database(&server_id.id);
         db.drop(None).await.unwrap();
+        let root = std::env::temp_dir().join(&server_id.id);
+        let blobs = BlobStore::new(&root);
+        let (user_handler, ids) = create_users(&client, &db, &["alice", "bob"]).await;
+        let (alice, bob) = (ids[0].clone(), ids[1].clone());
         let conf_coll: Collection<ServerConfig> = db.collection("config");
-        let conf = ServerConfig::new("TEST SERVER7".to_string(), user_id.clone());
+        let mut conf = ServerConfig::new("TEST SERVER26".to_string(), alice.clone());
+        conf.users.push(bob.clone());
         conf_coll.insert_one(conf, None).await.unwrap();
+        let channel_name = "TEST_CHANNEL1".to_string();
+        insert_channel_config(&db, &channel_name, 0).await;
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
 
-        let channel = db.collection("TEST_CHANNEL1");
-        let mut block = Block::new(0);
-        block.add_message(Message::new(
-            "starting...".to_string(),
-            "SERVER".to_string(),
-        ));
-        channel.insert_one(&block, None).await.unwrap();
-
-        let content = "I'm a message".to_string();
-        let author = "Some Dude".to_string();
-        assert!(ServerHandler::send_message(
+        //members can't manage emoji
+        let denied = ServerHandler::create_emoji(
             &client,
+            &blobs,
             &server_id,
-            &"TEST_CHANNEL1".to_string(),
-            &user_id,
-            content.clone(),
-            author.clone()
+            &bob,
+            "wave".to_string(),
+            png.clone(),
+        )
+        .await
+        .unwrap();
+        let wave = match ServerHandler::create_emoji(
+            &client,
+            &blobs,
+            &server_id,
+            &alice,
+            "wave".to_string(),
+            png.clone(),
         )
         .await
         .unwrap()
-        .succeeded());
-
-        block.add_message(Message::new(content, author));
-        let blk: Block = channel
-            .find_one(doc! {"filled": false}, None)
+        {
+            Response::EmojiCreated(id) => id,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        let duplicate = ServerHandler::create_emoji(
+            &client,
+            &blobs,
+            &server_id,
+            &alice,
+            "wave".to_string(),
+            png.clone(),
+        )
+        .await
+        .unwrap();
+        let invalid_name = ServerHandler::create_emoji(
+            &client,
+            &blobs,
+            &server_id,
+            &alice,
+            "party parrot".to_string(),
+            png.clone(),
+        )
+        .await
+        .unwrap();
+        let svg = ServerHandler::create_emoji(
+            &client,
+            &blobs,
+            &server_id,
+            &alice,
+            "logo".to_string(),
+            b"<svg></svg>".to_vec(),
+        )
+        .await
+        .unwrap();
+        for i in 1..MAX_EMOJI_PER_SERVER {
+            assert!(matches!(
+                ServerHandler::create_emoji(
+                    &client,
+                    &blobs,
+                    &server_id,
+                    &alice,
+                    format!("emoji{}", i),
+                    png.clone()
+                )
+                .await
+                .unwrap(),
+                Response::EmojiCreated(_)
+            ));
+        }
+        let limit = ServerHandler::create_emoji(
+            &client,
+            &blobs,
+            &server_id,
+            &alice,
+            "one_more".to_string(),
+            png.clone(),
+        )
+        .await
+        .unwrap();
+        let emoji = match ServerHandler::list_emoji(&client, &server_id, &bob)
             .await
             .unwrap()
+        {
+            Response::EmojiList(emoji) => emoji,
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        let image = ServerHandler::get_emoji_image(&client, &blobs, &server_id, &bob, &wave)
+            .await
             .unwrap();
-        assert_eq!(blk, block);
-        db.drop(None).await.unwrap();
-    }
 
-    #[test]
-    async fn test_get_block_content(){
-        let user_id = ID {
-            id: "123123123123123123123123".to_string(),
+        //emoji are resolved when the message is sent
+        assert!(ServerHandler::send_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &bob,
+            "hi :wave: :unknown:".to_string(),
+            None
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let message = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            HistoryQuery::Latest(1),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history.messages[0].clone(),
+            other => panic!("unexpected enum variant: {:?}", other),
         };
-        let client = connect_mongo(None).await.unwrap();
-        let server_id = ID {
-            id: "12012918412412412777715a".to_string(),
+        assert!(ServerHandler::add_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            &message.id,
+            ":wave:"
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let unknown_reaction = ServerHandler::add_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            &message.id,
+            ":unknown:",
+        )
+        .await
+        .unwrap();
+        let forged_reaction = ServerHandler::add_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            &message.id,
+            &format!("<:wave:{}>", bob.id),
+        )
+        .await
+        .unwrap();
+        let history = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            HistoryQuery::Latest(1),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history.messages[0].clone(),
+            other => panic!("unexpected enum variant: {:?}", other),
         };
-        let db = client.database(&server_id.id);
-        db.drop(None).await.unwrap();
-        let conf_coll: Collection<ServerConfig> = db.collection("config");
-        let conf = ServerConfig::new("TEST SERVER8".to_string(), user_id.clone());
-        conf_coll.insert_one(conf, None).await.unwrap();
 
-        let channel: Collection<Block> = db.collection("TEST_CHANNEL1");
-        let mut block = Block::new(0);
-        let m = Message::new(
-            "starting...".to_string(),
-            "SERVER".to_string(),
+        assert!(
+            !ServerHandler::delete_emoji(&client, &server_id, &bob, &wave)
+                .await
+                .unwrap()
+                .succeeded()
         );
-        block.add_message(m);
-        channel.insert_one(&block, None).await.unwrap();
-
-        let m = Message::new(
-            "starting...".to_string(),
-            "SERVER".to_string(),
+        assert!(
+            ServerHandler::delete_emoji(&client, &server_id, &alice, &wave)
+                .await
+                .unwrap()
+                .succeeded()
         );
-        let resp = ServerHandler::get_block_content(&client, &server_id, &"TEST_CHANNEL1".to_string(), &user_id, 0).await.unwrap();
-        match resp {
-            Response::MessagesFound(messages) => {
-                assert_eq!(messages.len(), 1);
-                assert_eq!(messages[0], m);
-            }
-            other => {
-                db.drop(None).await.unwrap();
-                panic!("unexpected enum variant: {:?}", other)
-            }
-        }
+        assert!(
+            !ServerHandler::delete_emoji(&client, &server_id, &alice, &wave)
+                .await
+                .unwrap()
+                .succeeded()
+        );
+        //reactions with deleted emoji can still be removed
+        assert!(ServerHandler::remove_reaction(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            &message.id,
+            &format!("<:wave:{}>", wave.id)
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let deleted = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            HistoryQuery::Latest(1),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history.messages[0].clone(),
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
+        //edits resolve the emoji again, the deleted emoji is no emoji of the server anymore
+        assert!(ServerHandler::edit_message(
+            &client,
+            &user_handler,
+            &server_id,
+            &channel_name,
+            &bob,
+            &message.id,
+            "bye :wave:".to_string()
+        )
+        .await
+        .unwrap()
+        .succeeded());
+        let edited = match ServerHandler::get_history(
+            &client,
+            &server_id,
+            &channel_name,
+            &bob,
+            HistoryQuery::Latest(1),
+            HistoryOrder::OldestFirst,
+        )
+        .await
+        .unwrap()
+        {
+            Response::History(history) => history.messages[0].clone(),
+            other => panic!("unexpected enum variant: {:?}", other),
+        };
         db.drop(None).await.unwrap();
+        let _ = tokio::fs::remove_dir_all(&root).await;
+
+        assert!(matches!(
+            denied,
+            Response::Error(ServerError::PermissionDenied)
+        ));
+        assert!(matches!(
+            duplicate,
+            Response::Error(ServerError::InvalidName(NameError::AlreadyTaken))
+        ));
+        assert!(matches!(
+            invalid_name,
+            Response::Error(ServerError::InvalidName(NameError::InvalidCharacter(' ')))
+        ));
+        assert!(matches!(svg, Response::Error(ServerError::InvalidFile(_))));
+        assert!(matches!(limit, Response::Error(ServerError::LimitExceeded)));
+        assert_eq!(emoji.len(), MAX_EMOJI_PER_SERVER);
+        assert_eq!(emoji[0].id, wave);
+        assert_eq!(emoji[0].name, "wave");
+        assert_eq!(emoji[0].created_by, alice);
+        assert!(matches!(image, Response::Image(bytes) if bytes == png));
+        assert!(matches!(
+            unknown_reaction,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert!(matches!(
+            forged_reaction,
+            Response::Error(ServerError::BadRequest)
+        ));
+        assert_eq!(history.emojis, vec![wave.clone()]);
+        assert_eq!(history.reactions.len(), 1);
+        assert_eq!(history.reactions[0].emoji, format!("<:wave:{}>", wave.id));
+        //messages keep the emoji they were sent with
+        assert_eq!(deleted.emojis, vec![wave.clone()]);
+        assert!(deleted.reactions.is_empty());
+        assert!(edited.emojis.is_empty());
     }
 }
@@ -0,0 +1,143 @@
+use common::{
+    id::ID,
+    messages::{Typing, TYPING_TIMEOUT},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use tokio::sync::broadcast;
+
+///number of changes a subscriber can fall behind before it misses changes
+const SUBSCRIBER_CAPACITY: usize = 64;
+
+///the users typing in a channel changed, sent to all subscribers
+#[derive(Clone, Debug)]
+pub struct TypingChange {
+    ///the key of the channel, see TypingTracker::key
+    pub channel: String,
+    ///all users typing in the channel
+    pub typing: Vec<Typing>,
+}
+
+///the users that are typing in every channel, only kept in memory, entries expire
+///TYPING_TIMEOUT after the last StartTyping request of the user
+#[derive(Clone)]
+pub struct TypingTracker {
+    channels: Arc<Mutex<HashMap<String, Vec<Typing>>>>,
+    changes: broadcast::Sender<TypingChange>,
+}
+
+impl TypingTracker {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(SUBSCRIBER_CAPACITY);
+        Self {
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            changes,
+        }
+    }
+
+    ///the key of the channel of the server, channels are identified by their id so renaming a
+    ///channel keeps the typing users
+    pub fn key(server_id: &ID, channel_id: &ID) -> String {
+        format!("{}/{}", server_id.id, channel_id.id)
+    }
+
+    ///marks the user as typing in the channel and notifies the subscribers
+    pub fn start(&self, channel: &str, user_id: &ID, name: String) {
+        let now = SystemTime::now();
+        let typing = {
+            let mut channels = self.channels.lock().expect("lock is not poisoned");
+            //channels without typing users are removed so the map doesn't grow forever
+            channels.retain(|_, typing| {
+                typing.retain(|entry| entry.expires_at > now);
+                !typing.is_empty()
+            });
+            let typing = channels.entry(channel.to_string()).or_default();
+            typing.retain(|entry| entry.user_id != *user_id);
+            typing.push(Typing {
+                user_id: user_id.clone(),
+                name,
+                expires_at: now + TYPING_TIMEOUT,
+            });
+            typing.clone()
+        };
+        //there may be no subscribers
+        let _ = self.changes.send(TypingChange {
+            channel: channel.to_string(),
+            typing,
+        });
+    }
+
+    ///returns the users that are typing in the channel
+    pub fn typing(&self, channel: &str) -> Vec<Typing> {
+        let now = SystemTime::now();
+        let channels = self.channels.lock().expect("lock is not poisoned");
+        channels
+            .get(channel)
+            .map(|typing| {
+                typing
+                    .iter()
+                    .filter(|entry| entry.expires_at > now)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    ///returns a receiver for all changes of all channels made after the call
+    pub fn subscribe(&self) -> broadcast::Receiver<TypingChange> {
+        self.changes.subscribe()
+    }
+}
+
+impl Default for TypingTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::test;
+
+    use super::*;
+
+    #[test]
+    async fn test_typing_tracker() {
+        let tracker = TypingTracker::new();
+        let max = ID::new("123123123123123123123123".to_string()).unwrap();
+        let moritz = ID::new("123123123123123123123124".to_string()).unwrap();
+        let general = TypingTracker::key(&max, &moritz);
+        let mut subscriber = tracker.subscribe();
+
+        tracker.start(&general, &max, "Max".to_string());
+        tracker.start(&general, &moritz, "Moritz".to_string());
+        tracker.start(&general, &max, "Max".to_string());
+        tracker.start("other", &max, "Max".to_string());
+
+        let typing = tracker.typing(&general);
+        assert_eq!(typing.len(), 2);
+        assert_eq!(typing[0].user_id, moritz);
+        assert!(tracker.typing("unknown").is_empty());
+
+        let first = subscriber.recv().await.unwrap();
+        assert_eq!(first.channel, general);
+        assert_eq!(first.typing.len(), 1);
+        assert_eq!(subscriber.recv().await.unwrap().typing.len(), 2);
+        assert_eq!(subscriber.recv().await.unwrap().typing.len(), 2);
+        assert_eq!(subscriber.recv().await.unwrap().channel, "other");
+
+        //expired entries are not returned
+        tracker
+            .channels
+            .lock()
+            .unwrap()
+            .get_mut(&general)
+            .unwrap()
+            .iter_mut()
+            .for_each(|entry| entry.expires_at = SystemTime::now());
+        assert!(tracker.typing(&general).is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 use anyhow::Result;
 use common::error::ServerError;
-use common::user::User;
+use common::id::ID;
+use common::user::{CustomStatus, Presence, Profile, User, IDLE_AFTER};
 use log::debug;
-use mongodb::bson::doc;
+use mongodb::bson::{doc, to_bson};
 use mongodb::options::UpdateModifications;
 use mongodb::{bson::oid::ObjectId, Client, Collection, Database};
 use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
 
 //TODO add email address and email address sign in option
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,6 +16,33 @@ struct SensitiveUser {
     is_online: bool,
     username: String,
     password: String,
+    ///presence the user chose, other users see it while the user is signed in and active
+    #[serde(default)]
+    presence: Presence,
+    #[serde(default)]
+    custom_status: Option<CustomStatus>,
+    ///time of the last request of the user
+    #[serde(default)]
+    last_active: Option<SystemTime>,
+    ///the presence other users were last notified about
+    #[serde(default = "offline")]
+    shown: Presence,
+    #[serde(default)]
+    profile: StoredProfile,
+}
+
+///the profile of a user as it is stored, images are referenced by the hash of their blob
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct StoredProfile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub avatar: Option<String>,
+    pub banner: Option<String>,
+}
+
+fn offline() -> Presence {
+    Presence::Offline
 }
 
 #[derive(Clone)]
@@ -40,6 +69,11 @@ impl SensitiveUser {
             is_online,
             username,
             password,
+            presence: Presence::Online,
+            custom_status: None,
+            last_active: is_online.then(SystemTime::now),
+            shown: Presence::Offline,
+            profile: StoredProfile::default(),
         }
     }
 
@@ -47,8 +81,52 @@ impl SensitiveUser {
         self.password == pwd && self.username == username
     }
 
+    ///the presence other users see at the time, users that chose to be online are shown as idle
+    ///if they didn't send a request for IDLE_AFTER
+    fn effective_presence(&self, now: SystemTime) -> Presence {
+        if !self.is_online || self.presence == Presence::Invisible {
+            return Presence::Offline;
+        }
+        let inactive = self
+            .last_active
+            .and_then(|last_active| now.duration_since(last_active).ok())
+            .is_some_and(|elapsed| elapsed >= IDLE_AFTER);
+        if self.presence == Presence::Online && inactive {
+            Presence::Idle
+        } else {
+            self.presence
+        }
+    }
+
+    ///the user as other users see it, invisible users are shown as offline in both the presence
+    ///and the online flag
     fn to_user(&self) -> User {
-        User::new(self.username.clone(), self.is_online)
+        let id = ID::new(self._id.to_hex()).expect("is an object id");
+        let now = SystemTime::now();
+        let presence = self.effective_presence(now);
+        let mut user = User::new(id, self.username.clone(), presence != Presence::Offline);
+        user.presence = presence;
+        //the custom status is hidden while the user is shown as offline
+        if user.presence != Presence::Offline {
+            user.custom_status = self
+                .custom_status
+                .clone()
+                .filter(|status| !status.is_expired(now));
+        }
+        user
+    }
+
+    ///the profile of the user, users were created when their id was
+    fn to_profile(&self) -> Profile {
+        Profile {
+            user: self.to_user(),
+            display_name: self.profile.display_name.clone(),
+            bio: self.profile.bio.clone(),
+            pronouns: self.profile.pronouns.clone(),
+            avatar: self.profile.avatar.clone(),
+            banner: self.profile.banner.clone(),
+            created_at: self._id.timestamp().to_system_time(),
+        }
     }
 }
 
@@ -100,6 +178,46 @@ impl UserHandler {
         }
     }
 
+    ///returns the Users matching the oids, oids without a matching user are ignored
+    pub async fn get_users(&self, user_ids: &[ObjectId]) -> Result<Vec<User>> {
+        let mut cursor = self
+            .collection
+            .find(doc! {"_id": {"$in": user_ids}}, None)
+            .await?;
+        let mut users = Vec::new();
+
+        while cursor.advance().await? {
+            let sensitive = cursor.deserialize_current()?;
+            users.push(sensitive.to_user());
+        }
+
+        Ok(users)
+    }
+
+    ///returns the profile of the user matching the oid
+    pub async fn get_profile(&self, user_id: ObjectId) -> Result<Option<Profile>> {
+        let option = self.get_user_sensitive(user_id).await?;
+        Ok(option.map(|sensitive| sensitive.to_profile()))
+    }
+
+    ///returns the profile of the user matching the oid as it is stored
+    pub async fn get_stored_profile(&self, user_id: ObjectId) -> Result<Option<StoredProfile>> {
+        let option = self.get_user_sensitive(user_id).await?;
+        Ok(option.map(|sensitive| sensitive.profile))
+    }
+
+    ///replaces the profile of the user
+    pub async fn set_profile(&self, user_id: ObjectId, profile: &StoredProfile) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! {"_id": user_id},
+                doc! {"$set": {"profile": to_bson(profile)?}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
     ///find all users matching the username
     pub async fn find_user_by_name(&self, username: String) -> Result<Vec<User>> {
         let mut cursor = self
@@ -116,18 +234,85 @@ impl UserHandler {
         Ok(users)
     }
 
-    ///sets the online status of the user to status
+    ///sets the online status of the user to status, signing in counts as activity
     pub async fn set_user_status(&self, user_id: ObjectId, status: bool) -> Result<()> {
+        let mut update = doc! {"is_online": status};
+        if status {
+            update.insert("last_active", to_bson(&SystemTime::now())?);
+        }
+        self.collection
+            .update_one(doc! {"_id": user_id}, doc! {"$set": update}, None)
+            .await?;
+        Ok(())
+    }
+
+    ///records a request of the user for the idle detection
+    pub async fn touch(&self, user_id: ObjectId) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! {"_id": user_id},
+                doc! {"$set": {"last_active": to_bson(&SystemTime::now())?}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///sets the presence the user chose and the custom status, None removes the custom status
+    pub async fn set_presence(
+        &self,
+        user_id: ObjectId,
+        presence: Presence,
+        custom_status: Option<CustomStatus>,
+    ) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! {"_id": user_id},
+                doc! {"$set": {
+                    "presence": to_bson(&presence)?,
+                    "custom_status": to_bson(&custom_status)?,
+                }},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    ///records the presence other users were notified about
+    pub async fn set_shown_presence(&self, user_id: ObjectId, presence: Presence) -> Result<()> {
         self.collection
             .update_one(
                 doc! {"_id": user_id},
-                doc! {"$set": doc! {"is_online": status}},
+                doc! {"$set": {"shown": to_bson(&presence)?}},
                 None,
             )
             .await?;
         Ok(())
     }
 
+    ///returns the users whose presence differs from the presence other users were last notified
+    ///about, e.g. because they became idle or signed out
+    pub async fn get_unannounced_presence_changes(&self) -> Result<Vec<User>> {
+        let filter = doc! {"$or": [
+            {"is_online": true},
+            {"shown": {"$ne": to_bson(&Presence::Offline)?}},
+        ]};
+        let mut cursor = self.collection.find(filter, None).await?;
+        let now = SystemTime::now();
+        let mut users = Vec::new();
+        while cursor.advance().await? {
+            let sensitive = cursor.deserialize_current()?;
+            if sensitive.effective_presence(now) != sensitive.shown {
+                users.push(sensitive.to_user());
+            }
+        }
+        Ok(users)
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
     ///returns true if the user exitsts, and the credentials are correct
     pub async fn check_user_credentials(
         &self,
@@ -143,6 +328,26 @@ impl UserHandler {
     }
 }
 
+///creates a signed out user for every name in the users collection of the database and returns a
+///user handler for the collection together with the ids of the users
+#[cfg(test)]
+pub async fn create_users(
+    client: &Client,
+    db: &Database,
+    names: &[&str],
+) -> (UserHandler, Vec<ID>) {
+    let handler = UserHandler::from_names(client, db.name(), "users");
+    let mut ids = Vec::new();
+    for name in names {
+        let oid = handler
+            .create_new_user(name.to_string(), "Passwort".to_string(), false)
+            .await
+            .unwrap();
+        ids.push(ID::new(oid.to_hex()).unwrap());
+    }
+    (handler, ids)
+}
+
 #[cfg(test)]
 mod test {
     use crate::mongodb::{self, connect_mongo};
@@ -151,18 +356,54 @@ mod test {
     use super::*;
     #[test]
     async fn test_pwd_check() {
-        let u = SensitiveUser {
-            _id: ObjectId::new(),
-            is_online: false,
-            username: "Bob".to_string(),
-            password: "#Passwort123".to_string(),
-        };
+        let u = SensitiveUser::new(
+            ObjectId::new(),
+            false,
+            "Bob".to_string(),
+            "#Passwort123".to_string(),
+            Vec::new(),
+        );
 
         assert!(u.check_credentials("#Passwort123", "Bob"));
         assert!(!u.check_credentials("falsches Passwort", "Bob"));
         assert!(!u.check_credentials("#Passwort123", "Paul"));
     }
 
+    #[test]
+    async fn test_effective_presence() {
+        let now = SystemTime::now();
+        let mut u = SensitiveUser::new(
+            ObjectId::new(),
+            true,
+            "Bob".to_string(),
+            "#Passwort123".to_string(),
+            Vec::new(),
+        );
+        u.custom_status = Some(CustomStatus::new("busy".to_string(), None));
+        assert_eq!(u.effective_presence(now), Presence::Online);
+        assert_eq!(u.to_user().custom_status, u.custom_status);
+
+        u.last_active = Some(now - IDLE_AFTER);
+        assert_eq!(u.effective_presence(now), Presence::Idle);
+        u.presence = Presence::DoNotDisturb;
+        assert_eq!(u.effective_presence(now), Presence::DoNotDisturb);
+
+        u.custom_status = Some(CustomStatus::new("busy".to_string(), Some(now)));
+        assert_eq!(u.to_user().custom_status, None);
+
+        u.presence = Presence::Invisible;
+        u.custom_status = Some(CustomStatus::new("busy".to_string(), None));
+        assert_eq!(u.effective_presence(now), Presence::Offline);
+        assert_eq!(u.to_user().custom_status, None);
+        //invisible users read as offline in both fields
+        let invisible = u.to_user();
+        assert_eq!(invisible.presence, Presence::Offline);
+        assert!(!invisible.is_online);
+        u.presence = Presence::Online;
+        u.is_online = false;
+        assert_eq!(u.effective_presence(now), Presence::Offline);
+    }
+
     async fn setup_test_database(client: &Client) {
         let mut db = client.database("TEST");
         db.drop(None).await.unwrap();
@@ -267,6 +508,29 @@ mod test {
         db.drop(None).await.unwrap();
     }
 
+    #[test]
+    async fn test_get_users() {
+        let client = connect_mongo(None).await.unwrap();
+        setup_test_database(&client).await;
+        let db = client.database("TEST");
+        let coll = db.collection("user");
+        let handler = UserHandler::new(db.clone(), coll.clone());
+        let users = handler
+            .get_users(&[
+                ObjectId::parse_str("123123123123123123123124").unwrap(),
+                ObjectId::parse_str("123123123123123123123127").unwrap(),
+                ObjectId::parse_str("123123123123123123123120").unwrap(),
+            ])
+            .await
+            .unwrap();
+        db.drop(None).await.unwrap();
+        assert_eq!(users.len(), 2);
+        let malte = users.iter().find(|u| u.username == "Malte").unwrap();
+        assert_eq!(malte.id.id, "123123123123123123123127");
+        assert!(malte.is_online);
+        assert!(users.iter().any(|u| u.username == "Moritz"));
+    }
+
     #[test]
     async fn test_find_user_by_name() {
         let client = connect_mongo(None).await.unwrap();